@@ -0,0 +1,35 @@
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Bake what !version reports into the binary: the git commit it was built from,
+// when it was built, and which compiler built it
+fn main() {
+    let commit = command_output("git", &["rev-parse", "--short", "HEAD"]);
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let rustc_version = command_output(&rustc, &["--version"]);
+    let built_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0);
+
+    println!("cargo:rustc-env=SHAMEBOT_GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=SHAMEBOT_RUSTC_VERSION={}", rustc_version);
+    println!("cargo:rustc-env=SHAMEBOT_BUILT_AT={}", built_at);
+
+    // Pick up a new commit without rebuilding on every source change
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs/heads");
+}
+
+// A command's trimmed output, or `unknown` if it couldn't be run (e.g. no git)
+fn command_output(program: &str, args: &[&str]) -> String {
+    Command::new(program)
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|output| output.trim().to_string())
+        .filter(|output| !output.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
@@ -0,0 +1,297 @@
+use clap::{Parser, Subcommand};
+
+use crate::user;
+
+// Command line for running the bot or doing maintenance without Discord
+#[derive(Parser)]
+#[command(
+    name = "shame-bot",
+    about = "A Discord bot that shames microtransaction spending"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Combine two users files, summing totals for games both have
+    Mergefile {
+        users_a: String,
+        users_b: String,
+        out: String,
+        /// Report what would be merged without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Work on the stored data directly, bypassing Discord
+    Admin {
+        /// The id of the server whose data to work on
+        #[arg(long)]
+        guild: u64,
+        #[command(subcommand)]
+        action: AdminAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AdminAction {
+    /// Show all users and their games
+    ListUsers,
+    /// Create a new user with their first game
+    AddUser {
+        user: String,
+        game: String,
+        total: String,
+    },
+    /// Add a new game to an existing user
+    AddGame {
+        user: String,
+        game: String,
+        total: String,
+    },
+    /// Remove a game from a user
+    RemoveGame { user: String, game: String },
+    /// Delete a user and all their games
+    DeleteUser { user: String },
+    /// Write every user to a JSON file
+    Export { path: String },
+    /// Load users from a JSON file into the current ones
+    Import {
+        path: String,
+        /// How to settle a game a user already has: replace, sum or max
+        #[arg(long, default_value = "replace")]
+        strategy: user::MergeStrategy,
+        /// Check the file and report what would change without writing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Run an offline command, printing what happened
+pub fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Mergefile {
+            users_a,
+            users_b,
+            out,
+            dry_run,
+        } => {
+            let report = user::merge_data_files(&users_a, &users_b, &out, dry_run)?;
+            let summary = format!(
+                "{} users merged, {} copied, {} overlapping games summed",
+                report.users_merged, report.users_copied, report.games_conflicting
+            );
+
+            if dry_run {
+                println!("Dry run, nothing written. Would write {}: {}", out, summary);
+            } else {
+                println!("✅ Wrote {}: {}", out, summary);
+            }
+        }
+        Command::Admin { guild, action } => run_admin(guild, action)?,
+    }
+
+    Ok(())
+}
+
+// What list-users prints: each user, then their games in name order
+fn user_list(users: &[user::User]) -> Vec<String> {
+    if users.is_empty() {
+        return vec!["No users are currently added to the bot".to_string()];
+    }
+
+    let mut lines = Vec::new();
+    for user in users {
+        match &user.alias_of {
+            Some(canonical) => lines.push(format!("{} (linked to {})", user.user, canonical)),
+            None => lines.push(user.user.clone()),
+        }
+
+        let mut games: Vec<_> = user.games.iter().collect();
+        games.sort_by(|a, b| a.0.cmp(b.0));
+        for (game, record) in games {
+            lines.push(format!(
+                "  • {}: {}",
+                game,
+                user::format_money(record.total)
+            ));
+        }
+    }
+    lines
+}
+
+fn run_admin(guild_id: u64, action: AdminAction) -> Result<()> {
+    match action {
+        AdminAction::ListUsers => {
+            for line in user_list(&user::get_users(guild_id, true)?) {
+                println!("{}", line);
+            }
+        }
+        AdminAction::AddUser { user, game, total } => {
+            user::add_user(guild_id, &user, &game, &total)?;
+        }
+        AdminAction::AddGame { user, game, total } => {
+            user::add_game(guild_id, &user, &game, &total)?;
+        }
+        AdminAction::RemoveGame { user, game } => user::remove_game(guild_id, &user, &game)?,
+        AdminAction::DeleteUser { user } => user::delete_user(guild_id, &user)?,
+        AdminAction::Export { path } => {
+            let count = user::export_users(guild_id, &path)?;
+            println!("✅ Exported {} users to {}", count, path);
+        }
+        AdminAction::Import {
+            path,
+            strategy,
+            dry_run,
+        } => {
+            let report = user::import_users(guild_id, &path, strategy, dry_run)?;
+            if dry_run {
+                println!(
+                    "Dry run, nothing written. Would import {} users from {}",
+                    report.imported, path
+                );
+            } else {
+                println!("✅ Imported {} users from {}", report.imported, path);
+            }
+            for conflict in report.conflicts {
+                println!("  • {}", conflict);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn admin(args: &[&str]) -> Result<()> {
+        let guild = user::TEST_GUILD.to_string();
+        let cli = Cli::try_parse_from(
+            ["shame-bot", "admin", "--guild", guild.as_str()]
+                .iter()
+                .chain(args),
+        )?;
+        run(cli.command.unwrap())
+    }
+
+    #[test]
+    fn admin_commands_change_the_stored_users() {
+        let _store = user::fresh_store();
+        admin(&["add-user", "Q", "Tekken 8", "100"]).unwrap();
+        admin(&["add-game", "Q", "Street Fighter 6", "20"]).unwrap();
+        admin(&["list-users"]).unwrap();
+        assert_eq!(
+            user::get_user_total_all_games(user::TEST_GUILD, "Q").unwrap(),
+            12000
+        );
+
+        admin(&["remove-game", "Q", "Tekken 8"]).unwrap();
+        assert_eq!(
+            user::get_user_games(user::TEST_GUILD, "Q").unwrap().len(),
+            1
+        );
+        admin(&["delete-user", "Q"]).unwrap();
+        assert!(user::get_users(user::TEST_GUILD, true).unwrap().is_empty());
+
+        // Errors come back instead of being printed and ignored
+        assert!(admin(&["remove-game", "Q", "Tekken 8"]).is_err());
+        assert!(admin(&["add-user", "Q"]).is_err());
+    }
+
+    #[test]
+    fn list_users_shows_each_user_and_their_games() {
+        let _store = user::fresh_store();
+        let listed = || user_list(&user::get_users(user::TEST_GUILD, true).unwrap());
+        assert_eq!(listed(), vec!["No users are currently added to the bot"]);
+
+        admin(&["add-user", "Q", "Tekken 8", "100"]).unwrap();
+        admin(&["add-game", "Q", "Halo", "5"]).unwrap();
+        admin(&["add-user", "Q2", "Halo", "1"]).unwrap();
+        user::link_account(user::TEST_GUILD, "Q2", "Q").unwrap();
+
+        let mut lines = listed();
+        lines.sort();
+        assert_eq!(
+            lines,
+            vec![
+                "  • Halo: $1.00",
+                "  • Halo: $5.00",
+                "  • Tekken 8: $100.00",
+                "Q",
+                "Q2 (linked to Q)",
+            ]
+        );
+    }
+
+    #[test]
+    fn mergefile_writes_the_combined_users_unless_its_a_dry_run() {
+        let _store = user::fresh_store();
+        let (a, b, out) = (
+            user::scratch_file("cli_merge_a.json"),
+            user::scratch_file("cli_merge_b.json"),
+            user::scratch_file("cli_merge_out.json"),
+        );
+        std::fs::write(&a, r#"[{"user": "Q", "games": {"Tekken 8": 100}}]"#).unwrap();
+        std::fs::write(&b, r#"[{"user": "Q", "games": {"Tekken 8": 50}}]"#).unwrap();
+        let _ = std::fs::remove_file(&out);
+        let mergefile = |extra: &[&str]| {
+            let args = [
+                "shame-bot",
+                "mergefile",
+                a.as_str(),
+                b.as_str(),
+                out.as_str(),
+            ];
+            let cli = Cli::try_parse_from(args.iter().chain(extra)).unwrap();
+            run(cli.command.unwrap())
+        };
+
+        mergefile(&["--dry-run"]).unwrap();
+        assert!(!std::path::Path::new(&out).exists());
+
+        mergefile(&[]).unwrap();
+        let merged: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&out).unwrap()).unwrap();
+        assert_eq!(merged[0]["games"]["Tekken 8"]["total"], 15000);
+    }
+
+    #[test]
+    fn export_and_import_round_trip() {
+        let _store = user::fresh_store();
+        let path = user::scratch_file("cli_export.json");
+        admin(&["add-user", "Q", "Tekken 8", "100"]).unwrap();
+        admin(&["export", &path]).unwrap();
+
+        admin(&["delete-user", "Q"]).unwrap();
+        admin(&["import", &path]).unwrap();
+        assert_eq!(
+            user::get_user_total_all_games(user::TEST_GUILD, "Q").unwrap(),
+            10000
+        );
+
+        // Summing on a re-import adds the file's totals again
+        admin(&["import", &path, "--strategy", "sum"]).unwrap();
+        assert_eq!(
+            user::get_user_total_all_games(user::TEST_GUILD, "Q").unwrap(),
+            20000
+        );
+        assert!(admin(&["import", &path, "--strategy", "average"]).is_err());
+
+        // A dry run reports without importing anything
+        admin(&["import", &path, "--strategy", "sum", "--dry-run"]).unwrap();
+        assert_eq!(
+            user::get_user_total_all_games(user::TEST_GUILD, "Q").unwrap(),
+            20000
+        );
+
+        // A file that doesn't parse leaves the current users alone
+        std::fs::write(&path, "not json").unwrap();
+        assert!(admin(&["import", &path]).is_err());
+        assert_eq!(user::get_users(user::TEST_GUILD, true).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,449 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use tracing::{info, warn};
+
+use crate::db;
+use crate::user::lock_store;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Settings that admins can change from Discord. Each server keeps its own in the
+// settings table of its database, with the maps stored as JSON
+const ALIASES_KEY: &str = "global_aliases"; // short name -> full game name
+const WEIGHTS_KEY: &str = "game_weights"; // game name -> shame multiplier (default 1.0)
+const SHAME_ROLE_KEY: &str = "shame_role_id"; // role pinged by threshold callouts instead of @here
+const SHAME_CHANNEL_KEY: &str = "shame_channel_id"; // where callouts go instead of the command's channel
+const BOARD_THREAD_KEY: &str = "board_thread"; // where the self-updating leaderboard lives
+const CONSENT_KEY: &str = "require_consent"; // new people are only tracked after !consent yes
+
+// The leaderboard message the bot keeps up to date inside its own thread
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct BoardThread {
+    pub thread_id: u64,
+    pub message_id: u64,
+    pub last_edit: i64, // Unix seconds of the last post or edit
+}
+
+// Least time between two edits of the thread leaderboard, in seconds
+pub const BOARD_EDIT_INTERVAL: i64 = 60;
+
+// Where the settings were kept before they moved into each server's database, relative
+// to the working directory unless SHAMEBOT_CONFIG_PATH says otherwise
+#[cfg(not(test))]
+const LEGACY_CONFIG_FILE: &str = "../config.json";
+
+#[cfg(not(test))]
+fn legacy_config_file() -> String {
+    std::env::var("SHAMEBOT_CONFIG_PATH").unwrap_or_else(|_| LEGACY_CONFIG_FILE.to_string())
+}
+
+#[cfg(test)]
+fn legacy_config_file() -> String {
+    format!("{}/config.json", db::data_dir())
+}
+
+// The old bot-wide settings file
+#[derive(Deserialize, Default)]
+struct LegacyConfig {
+    #[serde(default)]
+    global_aliases: HashMap<String, String>,
+    #[serde(default)]
+    game_weights: HashMap<String, f64>,
+    #[serde(default)]
+    shame_role_id: Option<u64>,
+    #[serde(default)]
+    shame_channel_id: Option<u64>,
+    #[serde(default)]
+    board_thread: Option<BoardThread>,
+    #[serde(default)]
+    require_consent: bool,
+}
+
+// The first server to read its settings takes over the old config.json, which is then
+// moved aside like users.json. Anything the server has already set itself is kept.
+// A file that can't be read or parsed is an error, so it's never quietly dropped
+fn claim_legacy_config(guild_id: u64, path: &str) -> Result<()> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("Couldn't read '{}': {}", path, e).into()),
+    };
+    let legacy: LegacyConfig = if contents.trim().is_empty() {
+        LegacyConfig::default()
+    } else {
+        serde_json::from_str(&contents).map_err(|e| format!("Couldn't parse '{}': {}", path, e))?
+    };
+
+    let settings = [
+        (
+            ALIASES_KEY,
+            Some(serde_json::to_string(&legacy.global_aliases)?),
+        ),
+        (
+            WEIGHTS_KEY,
+            Some(serde_json::to_string(&legacy.game_weights)?),
+        ),
+        (
+            SHAME_ROLE_KEY,
+            legacy.shame_role_id.map(|id| id.to_string()),
+        ),
+        (
+            SHAME_CHANNEL_KEY,
+            legacy.shame_channel_id.map(|id| id.to_string()),
+        ),
+        (
+            BOARD_THREAD_KEY,
+            legacy
+                .board_thread
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()?,
+        ),
+        (
+            CONSENT_KEY,
+            legacy.require_consent.then(|| "on".to_string()),
+        ),
+    ];
+    for (key, value) in settings {
+        if let Some(value) = value
+            && db::get_setting(guild_id, key)?.is_none()
+        {
+            db::set_setting(guild_id, key, Some(&value))?;
+        }
+    }
+
+    std::fs::rename(path, format!("{}.imported", path))
+        .map_err(|e| format!("Couldn't move '{}' aside: {}", path, e))?;
+    info!("Imported '{}' into server {}", path, guild_id);
+    Ok(())
+}
+
+// Read one of a server's settings. Callers hold the store lock, since the first read
+// may import the old config file
+fn get_setting(guild_id: u64, key: &str) -> Result<Option<String>> {
+    claim_legacy_config(guild_id, &legacy_config_file())?;
+    db::get_setting(guild_id, key)
+}
+
+fn load_json<T: DeserializeOwned>(guild_id: u64, key: &str) -> Result<Option<T>> {
+    match get_setting(guild_id, key)? {
+        Some(json) => {
+            Ok(Some(serde_json::from_str(&json).map_err(|e| {
+                format!("Couldn't parse the {} setting: {}", key, e)
+            })?))
+        }
+        None => Ok(None),
+    }
+}
+
+fn save_json<T: Serialize>(guild_id: u64, key: &str, value: Option<&T>) -> Result<()> {
+    let json = value.map(serde_json::to_string).transpose()?;
+    db::set_setting(guild_id, key, json.as_deref())
+}
+
+fn load_map<T: DeserializeOwned>(guild_id: u64, key: &str) -> Result<HashMap<String, T>> {
+    Ok(load_json(guild_id, key)?.unwrap_or_default())
+}
+
+// Function to add (or replace) a server-wide game alias
+pub fn set_global_alias(guild_id: u64, short: &str, full: &str) -> Result<()> {
+    let _store = lock_store();
+    let mut aliases: HashMap<String, String> = load_map(guild_id, ALIASES_KEY)?;
+    aliases.insert(short.to_string(), full.to_string());
+    save_json(guild_id, ALIASES_KEY, Some(&aliases))?;
+
+    info!(
+        "Added global alias '{}' -> '{}' in server {}",
+        short, full, guild_id
+    );
+    Ok(())
+}
+
+// Function to remove a server-wide game alias
+pub fn remove_global_alias(guild_id: u64, short: &str) -> Result<()> {
+    let _store = lock_store();
+    let mut aliases: HashMap<String, String> = load_map(guild_id, ALIASES_KEY)?;
+
+    if aliases.remove(short).is_none() {
+        return Err(format!("No global alias named '{}'", short).into());
+    }
+
+    save_json(guild_id, ALIASES_KEY, Some(&aliases))?;
+    info!("Removed global alias '{}' in server {}", short, guild_id);
+    Ok(())
+}
+
+// Resolve a game name typed in a command to the name it stands for.
+// Precedence is user alias > global alias > literal; there are no per-user
+// aliases yet, so a global alias wins and anything else is taken literally.
+pub fn resolve_game_name(guild_id: u64, game: &str) -> Result<String> {
+    let _store = lock_store();
+    let aliases: HashMap<String, String> = load_map(guild_id, ALIASES_KEY)?;
+
+    Ok(match aliases.get(game) {
+        Some(full) => full.clone(),
+        None => game.to_string(),
+    })
+}
+
+// Function to set how much shame each dollar in a game is worth. A weight of 1.0 clears it
+pub fn set_game_weight(guild_id: u64, game: &str, weight: f64) -> Result<()> {
+    if !weight.is_finite() || weight < 0.0 {
+        return Err("Weight must be a number of zero or more".into());
+    }
+
+    let _store = lock_store();
+    let mut weights: HashMap<String, f64> = load_map(guild_id, WEIGHTS_KEY)?;
+    if weight == 1.0 {
+        weights.remove(game);
+    } else {
+        weights.insert(game.to_string(), weight);
+    }
+    save_json(guild_id, WEIGHTS_KEY, Some(&weights))?;
+
+    info!(
+        "Set weight for '{}' to {} in server {}",
+        game, weight, guild_id
+    );
+    Ok(())
+}
+
+// Function to get a server's per-game weights for the weighted leaderboard
+pub fn get_game_weights(guild_id: u64) -> Result<HashMap<String, f64>> {
+    let _store = lock_store();
+    load_map(guild_id, WEIGHTS_KEY)
+}
+
+// Function to remember the thread and message a server's leaderboard was just posted to
+pub fn set_board_thread(
+    guild_id: u64,
+    thread_id: u64,
+    message_id: u64,
+    posted_at: i64,
+) -> Result<()> {
+    let _store = lock_store();
+    let board = BoardThread {
+        thread_id,
+        message_id,
+        last_edit: posted_at,
+    };
+    save_json(guild_id, BOARD_THREAD_KEY, Some(&board))?;
+
+    info!(
+        "Tracking leaderboard message {} in thread {} for server {}",
+        message_id, thread_id, guild_id
+    );
+    Ok(())
+}
+
+// Function to stop updating a server's thread leaderboard, e.g. once its message is gone
+pub fn clear_board_thread(guild_id: u64) -> Result<()> {
+    let _store = lock_store();
+    if get_setting(guild_id, BOARD_THREAD_KEY)?.is_some() {
+        save_json::<BoardThread>(guild_id, BOARD_THREAD_KEY, None)?;
+        info!(
+            "Stopped tracking the leaderboard thread for server {}",
+            guild_id
+        );
+    }
+    Ok(())
+}
+
+// Function to claim the next edit of a server's thread leaderboard. Returns where it lives
+// if there is one and the last edit was at least BOARD_EDIT_INTERVAL ago, and counts `now`
+// as the latest edit so callers arriving in the meantime are turned away
+pub fn claim_board_edit(guild_id: u64, now: i64) -> Result<Option<BoardThread>> {
+    let _store = lock_store();
+    let Some(mut board) = load_json::<BoardThread>(guild_id, BOARD_THREAD_KEY)? else {
+        return Ok(None);
+    };
+    if now - board.last_edit < BOARD_EDIT_INTERVAL {
+        return Ok(None);
+    }
+
+    board.last_edit = now;
+    save_json(guild_id, BOARD_THREAD_KEY, Some(&board))?;
+    Ok(Some(board))
+}
+
+// Role and channel ids are server specific, so they're kept in each server's settings
+fn get_id(guild_id: u64, key: &str) -> Result<Option<u64>> {
+    let _store = lock_store();
+    Ok(get_setting(guild_id, key)?
+        .map(|value| value.parse::<u64>())
+        .transpose()?)
+}
+
+fn set_id(guild_id: u64, key: &str, id: Option<u64>) -> Result<()> {
+    let _store = lock_store();
+    db::set_setting(guild_id, key, id.map(|id| id.to_string()).as_deref())
+}
+
+// Function to get the role a server's threshold callouts ping, if one is set
+pub fn get_shame_role(guild_id: u64) -> Result<Option<u64>> {
+    get_id(guild_id, SHAME_ROLE_KEY)
+}
+
+// Function to set (or with None, clear) the role a server's threshold callouts ping
+pub fn set_shame_role(guild_id: u64, role_id: Option<u64>) -> Result<()> {
+    set_id(guild_id, SHAME_ROLE_KEY, role_id)?;
+    info!("Set shame role for server {} to {:?}", guild_id, role_id);
+    Ok(())
+}
+
+// Function to get the channel a server's threshold callouts are posted in, if one is set
+pub fn get_shame_channel(guild_id: u64) -> Result<Option<u64>> {
+    get_id(guild_id, SHAME_CHANNEL_KEY)
+}
+
+// Function to set (or with None, clear) the channel a server's threshold callouts are posted in
+pub fn set_shame_channel(guild_id: u64, channel_id: Option<u64>) -> Result<()> {
+    set_id(guild_id, SHAME_CHANNEL_KEY, channel_id)?;
+    info!(
+        "Set shame channel for server {} to {:?}",
+        guild_id, channel_id
+    );
+    Ok(())
+}
+
+// Function to check whether a server only tracks new people after !consent yes.
+// An unreadable setting leaves the gate off, like it was before it existed
+pub fn requires_consent(guild_id: u64) -> bool {
+    let _store = lock_store();
+    match get_setting(guild_id, CONSENT_KEY) {
+        Ok(value) => value.as_deref() == Some("on"),
+        Err(e) => {
+            warn!("Couldn't read the consent setting for server {guild_id}: {e}");
+            false
+        }
+    }
+}
+
+// Function to turn a server's consent gate on or off. Off keeps tracking immediate
+pub fn set_require_consent(guild_id: u64, on: bool) -> Result<()> {
+    let _store = lock_store();
+    db::set_setting(guild_id, CONSENT_KEY, on.then_some("on"))?;
+
+    info!("Set require consent for server {} to {}", guild_id, on);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user::{TEST_GUILD, fresh_store};
+
+    #[test]
+    fn board_thread_edits_are_throttled() {
+        let _store = fresh_store();
+        let guild = TEST_GUILD;
+        assert_eq!(claim_board_edit(guild, 1000).unwrap(), None);
+
+        set_board_thread(guild, 11, 22, 1000).unwrap();
+        assert_eq!(
+            claim_board_edit(guild, 1000 + BOARD_EDIT_INTERVAL - 1).unwrap(),
+            None
+        );
+        // Other servers don't have one
+        assert_eq!(claim_board_edit(guild + 1, i64::MAX).unwrap(), None);
+
+        let claimed = claim_board_edit(guild, 1000 + BOARD_EDIT_INTERVAL)
+            .unwrap()
+            .unwrap();
+        assert_eq!((claimed.thread_id, claimed.message_id), (11, 22));
+        // The claim itself restarts the wait
+        assert_eq!(
+            claim_board_edit(guild, 1000 + BOARD_EDIT_INTERVAL).unwrap(),
+            None
+        );
+
+        clear_board_thread(guild).unwrap();
+        assert_eq!(claim_board_edit(guild, i64::MAX).unwrap(), None);
+    }
+
+    #[test]
+    fn global_aliases_win_over_the_literal_name() {
+        let _store = fresh_store();
+        set_global_alias(TEST_GUILD, "sf6", "Street Fighter 6").unwrap();
+
+        assert_eq!(
+            resolve_game_name(TEST_GUILD, "sf6").unwrap(),
+            "Street Fighter 6"
+        );
+        assert_eq!(
+            resolve_game_name(TEST_GUILD, "Tekken 8").unwrap(),
+            "Tekken 8"
+        );
+
+        remove_global_alias(TEST_GUILD, "sf6").unwrap();
+        assert_eq!(resolve_game_name(TEST_GUILD, "sf6").unwrap(), "sf6");
+        assert!(remove_global_alias(TEST_GUILD, "sf6").is_err());
+    }
+
+    #[test]
+    fn aliases_and_weights_are_per_server() {
+        let _store = fresh_store();
+        let other = TEST_GUILD + 1;
+        set_global_alias(TEST_GUILD, "sf6", "Street Fighter 6").unwrap();
+        set_game_weight(TEST_GUILD, "Tekken 8", 2.0).unwrap();
+
+        assert_eq!(resolve_game_name(other, "sf6").unwrap(), "sf6");
+        assert!(get_game_weights(other).unwrap().is_empty());
+        assert_eq!(get_game_weights(TEST_GUILD).unwrap()["Tekken 8"], 2.0);
+
+        set_game_weight(TEST_GUILD, "Tekken 8", 1.0).unwrap();
+        assert!(get_game_weights(TEST_GUILD).unwrap().is_empty());
+        assert!(set_game_weight(TEST_GUILD, "Tekken 8", -1.0).is_err());
+    }
+
+    #[test]
+    fn a_corrupt_setting_is_an_error() {
+        let _store = fresh_store();
+        db::set_setting(TEST_GUILD, ALIASES_KEY, Some("{not json")).unwrap();
+
+        assert!(resolve_game_name(TEST_GUILD, "sf6").is_err());
+        assert!(set_global_alias(TEST_GUILD, "sf6", "Street Fighter 6").is_err());
+        assert_eq!(
+            db::get_setting(TEST_GUILD, ALIASES_KEY).unwrap().as_deref(),
+            Some("{not json")
+        );
+    }
+
+    #[test]
+    fn the_old_config_file_is_imported_once() {
+        let _store = fresh_store();
+        let path = legacy_config_file();
+        std::fs::create_dir_all(db::data_dir()).unwrap();
+        std::fs::write(
+            &path,
+            r#"{"global_aliases": {"sf6": "Street Fighter 6"}, "shame_role_id": 55,
+                "require_consent": true}"#,
+        )
+        .unwrap();
+        db::set_setting(TEST_GUILD, SHAME_ROLE_KEY, Some("77")).unwrap();
+
+        assert_eq!(
+            resolve_game_name(TEST_GUILD, "sf6").unwrap(),
+            "Street Fighter 6"
+        );
+        assert!(!std::path::Path::new(&path).exists());
+        assert!(requires_consent(TEST_GUILD));
+        // The server's own role wins over the imported one
+        assert_eq!(get_shame_role(TEST_GUILD).unwrap(), Some(77));
+        // A second server doesn't get a copy
+        assert_eq!(resolve_game_name(TEST_GUILD + 1, "sf6").unwrap(), "sf6");
+    }
+
+    #[test]
+    fn an_unparseable_old_config_file_is_left_alone() {
+        let _store = fresh_store();
+        let path = legacy_config_file();
+        std::fs::create_dir_all(db::data_dir()).unwrap();
+        std::fs::write(&path, "{broken").unwrap();
+
+        assert!(resolve_game_name(TEST_GUILD, "sf6").is_err());
+        assert!(std::path::Path::new(&path).exists());
+    }
+}
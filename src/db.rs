@@ -0,0 +1,421 @@
+use rusqlite::{Connection, params};
+use std::collections::HashMap;
+
+use crate::user::{GameRecord, Transaction, User};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// Each server gets its own database named after its guild id, so servers never see
+// each other's users. They live in a directory relative to the working directory
+// unless SHAMEBOT_DATA_DIR says otherwise
+#[cfg(not(test))]
+const DEFAULT_DATA_DIR: &str = "data";
+
+#[cfg(not(test))]
+pub fn data_dir() -> String {
+    std::env::var("SHAMEBOT_DATA_DIR").unwrap_or_else(|_| DEFAULT_DATA_DIR.to_string())
+}
+
+// Tests never touch real data: each run gets a directory of its own under the temp dir
+#[cfg(test)]
+pub fn data_dir() -> String {
+    format!(
+        "{}/shamebot-test-{}/data",
+        std::env::temp_dir().display(),
+        std::process::id()
+    )
+}
+
+pub fn db_path(guild_id: u64) -> String {
+    format!("{}/{}.db", data_dir(), guild_id)
+}
+
+// The single database from before servers were kept apart. SHAMEBOT_DB_PATH still points at it
+#[cfg(not(test))]
+const LEGACY_DB_FILE: &str = "shamebot.db";
+
+#[cfg(not(test))]
+fn legacy_db_path() -> String {
+    std::env::var("SHAMEBOT_DB_PATH").unwrap_or_else(|_| LEGACY_DB_FILE.to_string())
+}
+
+#[cfg(test)]
+pub fn legacy_db_path() -> String {
+    format!(
+        "{}/shamebot-test-{}/shamebot.db",
+        std::env::temp_dir().display(),
+        std::process::id()
+    )
+}
+
+// Whether a server's database has been created yet
+pub fn exists(guild_id: u64) -> bool {
+    std::path::Path::new(&db_path(guild_id)).exists()
+}
+
+// Move the old single database over to a server, if it's still there to claim
+pub fn claim_legacy(guild_id: u64) -> Result<bool> {
+    let legacy = legacy_db_path();
+    if !std::path::Path::new(&legacy).exists() {
+        return Ok(false);
+    }
+
+    let path = db_path(guild_id);
+    std::fs::create_dir_all(data_dir())?;
+    std::fs::rename(&legacy, &path)
+        .map_err(|e| format!("Couldn't move '{}' to '{}': {}", legacy, path, e))?;
+    Ok(true)
+}
+
+// Open a server's database, creating it and its tables the first time
+fn open(guild_id: u64) -> Result<Connection> {
+    let path = db_path(guild_id);
+    std::fs::create_dir_all(data_dir())
+        .map_err(|e| format!("Couldn't create '{}': {}", data_dir(), e))?;
+    let conn = Connection::open(&path).map_err(|e| format!("Couldn't open '{}': {}", path, e))?;
+
+    conn.execute_batch(
+        "PRAGMA foreign_keys = ON;
+        CREATE TABLE IF NOT EXISTS users (
+            id           INTEGER PRIMARY KEY,
+            name         TEXT NOT NULL UNIQUE,
+            alias_of     TEXT,
+            poke_opt_out INTEGER NOT NULL DEFAULT 0,
+            display_name TEXT,
+            consented    INTEGER NOT NULL DEFAULT 0,
+            discord_id   INTEGER,
+            archived     INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE TABLE IF NOT EXISTS games (
+            id           INTEGER PRIMARY KEY,
+            user_id      INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+            name         TEXT NOT NULL,
+            total        INTEGER NOT NULL,
+            cap          INTEGER,
+            goal         INTEGER,
+            tags         TEXT,
+            created_at   INTEGER NOT NULL DEFAULT 0,
+            last_updated INTEGER NOT NULL DEFAULT 0,
+            UNIQUE (user_id, name)
+        );
+        CREATE TABLE IF NOT EXISTS transactions (
+            id        INTEGER PRIMARY KEY,
+            timestamp INTEGER NOT NULL,
+            username  TEXT NOT NULL,
+            game      TEXT NOT NULL,
+            delta     INTEGER NOT NULL,
+            total     INTEGER NOT NULL,
+            undoes    INTEGER
+        );
+        CREATE TABLE IF NOT EXISTS settings (
+            key   TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );",
+    )?;
+
+    // Databases from before mentions were supported don't have the discord_id column yet
+    if conn
+        .prepare("SELECT discord_id FROM users LIMIT 0")
+        .is_err()
+    {
+        conn.execute("ALTER TABLE users ADD COLUMN discord_id INTEGER", [])?;
+    }
+
+    // Databases from before !setgoal don't have the goal column yet
+    if conn.prepare("SELECT goal FROM games LIMIT 0").is_err() {
+        conn.execute("ALTER TABLE games ADD COLUMN goal INTEGER", [])?;
+    }
+
+    // Databases from before !archive don't have the archived column yet
+    if conn.prepare("SELECT archived FROM users LIMIT 0").is_err() {
+        conn.execute(
+            "ALTER TABLE users ADD COLUMN archived INTEGER NOT NULL DEFAULT 0",
+            [],
+        )?;
+    }
+
+    // Databases from before !tag don't have the tags column yet
+    if conn.prepare("SELECT tags FROM games LIMIT 0").is_err() {
+        conn.execute("ALTER TABLE games ADD COLUMN tags TEXT", [])?;
+    }
+
+    // Databases from before game timestamps don't have them; those games read back as 0 (unknown)
+    if conn
+        .prepare("SELECT created_at FROM games LIMIT 0")
+        .is_err()
+    {
+        conn.execute(
+            "ALTER TABLE games ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0",
+            [],
+        )?;
+        conn.execute(
+            "ALTER TABLE games ADD COLUMN last_updated INTEGER NOT NULL DEFAULT 0",
+            [],
+        )?;
+    }
+
+    // Logs from before !undo don't have the undoes column yet
+    if conn
+        .prepare("SELECT undoes FROM transactions LIMIT 0")
+        .is_err()
+    {
+        conn.execute("ALTER TABLE transactions ADD COLUMN undoes INTEGER", [])?;
+    }
+
+    Ok(conn)
+}
+
+// Read every user in a server and their games, in the order they were added
+pub fn load_users(guild_id: u64) -> Result<Vec<User>> {
+    let conn = open(guild_id)?;
+    let mut users: Vec<User> = Vec::new();
+    let mut index_by_id: HashMap<i64, usize> = HashMap::new();
+
+    let mut select_users = conn.prepare(
+        "SELECT id, name, alias_of, poke_opt_out, display_name, consented, discord_id, archived
+        FROM users ORDER BY id",
+    )?;
+    let mut rows = select_users.query([])?;
+    while let Some(row) = rows.next()? {
+        index_by_id.insert(row.get(0)?, users.len());
+        users.push(User {
+            user: row.get(1)?,
+            games: HashMap::new(),
+            alias_of: row.get(2)?,
+            poke_opt_out: row.get(3)?,
+            caps: HashMap::new(),
+            goals: HashMap::new(),
+            tags: HashMap::new(),
+            display_name: row.get(4)?,
+            consented: row.get(5)?,
+            in_cents: true,
+            discord_id: row.get::<_, Option<i64>>(6)?.map(|id| id as u64),
+            archived: row.get(7)?,
+        });
+    }
+
+    let mut select_games = conn.prepare(
+        "SELECT user_id, name, total, cap, goal, tags, created_at, last_updated FROM games",
+    )?;
+    let mut rows = select_games.query([])?;
+    while let Some(row) = rows.next()? {
+        let Some(&index) = index_by_id.get(&row.get::<_, i64>(0)?) else {
+            continue;
+        };
+        let user = &mut users[index];
+        let game: String = row.get(1)?;
+
+        if let Some(cap) = row.get::<_, Option<i64>>(3)? {
+            user.caps.insert(game.clone(), cap);
+        }
+        if let Some(goal) = row.get::<_, Option<i64>>(4)? {
+            user.goals.insert(game.clone(), goal);
+        }
+        // Tags are kept as a JSON list
+        if let Some(tags) = row.get::<_, Option<String>>(5)? {
+            user.tags.insert(game.clone(), serde_json::from_str(&tags)?);
+        }
+        user.games.insert(
+            game,
+            GameRecord {
+                total: row.get(2)?,
+                created_at: row.get(6)?,
+                last_updated: row.get(7)?,
+            },
+        );
+    }
+
+    Ok(users)
+}
+
+// Replace a server's users with these, all or nothing
+pub fn save_users(guild_id: u64, users: &[User]) -> Result<()> {
+    let mut conn = open(guild_id)?;
+    let tx = conn.transaction()?;
+
+    tx.execute("DELETE FROM games", [])?;
+    tx.execute("DELETE FROM users", [])?;
+
+    {
+        let mut insert_user = tx.prepare(
+            "INSERT INTO users (name, alias_of, poke_opt_out, display_name, consented, discord_id, archived)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?;
+        let mut insert_game = tx.prepare(
+            "INSERT INTO games (user_id, name, total, cap, goal, tags, created_at, last_updated)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )?;
+
+        for user in users {
+            let user_id = insert_user.insert(params![
+                user.user,
+                user.alias_of,
+                user.poke_opt_out,
+                user.display_name,
+                user.consented,
+                user.discord_id.map(|id| id as i64),
+                user.archived
+            ])?;
+
+            for (game, record) in &user.games {
+                insert_game.execute(params![
+                    user_id,
+                    game,
+                    record.total,
+                    user.caps.get(game),
+                    user.goals.get(game),
+                    user.tags.get(game).map(serde_json::to_string).transpose()?,
+                    record.created_at,
+                    record.last_updated
+                ])?;
+            }
+        }
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+// Read one of a server's settings, if it has been set
+pub fn get_setting(guild_id: u64, key: &str) -> Result<Option<String>> {
+    let conn = open(guild_id)?;
+    let mut stmt = conn.prepare("SELECT value FROM settings WHERE key = ?1")?;
+    let mut rows = stmt.query(params![key])?;
+
+    match rows.next()? {
+        Some(row) => Ok(Some(row.get(0)?)),
+        None => Ok(None),
+    }
+}
+
+// Set (or with None, clear) one of a server's settings
+pub fn set_setting(guild_id: u64, key: &str, value: Option<&str>) -> Result<()> {
+    let conn = open(guild_id)?;
+    match value {
+        Some(value) => conn.execute(
+            "INSERT INTO settings (key, value) VALUES (?1, ?2)
+            ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            params![key, value],
+        )?,
+        None => conn.execute("DELETE FROM settings WHERE key = ?1", params![key])?,
+    };
+    Ok(())
+}
+
+// Append a change to the transaction log
+pub fn record_transaction(guild_id: u64, transaction: &Transaction) -> Result<()> {
+    let conn = open(guild_id)?;
+    conn.execute(
+        "INSERT INTO transactions (timestamp, username, game, delta, total, undoes)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            transaction.timestamp,
+            transaction.username,
+            transaction.game,
+            transaction.delta,
+            transaction.total,
+            transaction.undoes
+        ],
+    )?;
+    Ok(())
+}
+
+fn transaction_from_row(row: &rusqlite::Row) -> rusqlite::Result<Transaction> {
+    Ok(Transaction {
+        timestamp: row.get(0)?,
+        username: row.get(1)?,
+        game: row.get(2)?,
+        delta: row.get(3)?,
+        total: row.get(4)?,
+        undoes: row.get(5)?,
+    })
+}
+
+// Every logged change, oldest first
+pub fn load_all_transactions(guild_id: u64) -> Result<Vec<Transaction>> {
+    let conn = open(guild_id)?;
+    let mut stmt = conn.prepare(
+        "SELECT timestamp, username, game, delta, total, undoes FROM transactions ORDER BY id",
+    )?;
+
+    let transactions = stmt
+        .query_map([], transaction_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(transactions)
+}
+
+// Forget every logged change in a server
+pub fn clear_transactions(guild_id: u64) -> Result<()> {
+    let conn = open(guild_id)?;
+    conn.execute("DELETE FROM transactions", [])?;
+    Ok(())
+}
+
+// Move a user's logged changes over to their new name
+pub fn rename_transactions(guild_id: u64, old: &str, new: &str) -> Result<()> {
+    let conn = open(guild_id)?;
+    conn.execute(
+        "UPDATE transactions SET username = ?2 WHERE username = ?1",
+        params![old, new],
+    )?;
+    Ok(())
+}
+
+// Point one user's logged changes for a game at its new name
+pub fn rename_game_transactions(guild_id: u64, username: &str, old: &str, new: &str) -> Result<()> {
+    let conn = open(guild_id)?;
+    conn.execute(
+        "UPDATE transactions SET game = ?3 WHERE username = ?1 AND game = ?2",
+        params![username, old, new],
+    )?;
+    Ok(())
+}
+
+// Every logged change for a user (and optionally one game), newest first
+pub fn load_transactions(
+    guild_id: u64,
+    username: &str,
+    game: Option<&str>,
+) -> Result<Vec<Transaction>> {
+    let conn = open(guild_id)?;
+    let mut stmt = conn.prepare(
+        "SELECT timestamp, username, game, delta, total, undoes FROM transactions
+        WHERE username = ?1 AND (?2 IS NULL OR game = ?2)
+        ORDER BY id DESC",
+    )?;
+
+    let transactions = stmt
+        .query_map(params![username, game], transaction_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(transactions)
+}
+
+// A user's most recent change that isn't an undo and hasn't been undone, with its id
+pub fn last_undoable_transaction(
+    guild_id: u64,
+    username: &str,
+) -> Result<Option<(i64, Transaction)>> {
+    let conn = open(guild_id)?;
+    let mut stmt = conn.prepare(
+        "SELECT timestamp, username, game, delta, total, undoes, id FROM transactions AS t
+        WHERE username = ?1 AND undoes IS NULL
+            AND NOT EXISTS (SELECT 1 FROM transactions WHERE undoes = t.id)
+        ORDER BY id DESC LIMIT 1",
+    )?;
+
+    let mut rows = stmt.query(params![username])?;
+    match rows.next()? {
+        Some(row) => Ok(Some((row.get(6)?, transaction_from_row(row)?))),
+        None => Ok(None),
+    }
+}
+
+// Hand one user's logged changes to a game over to someone else
+pub fn reassign_transactions(guild_id: u64, from: &str, to: &str, game: &str) -> Result<()> {
+    let conn = open(guild_id)?;
+    conn.execute(
+        "UPDATE transactions SET username = ?3 WHERE username = ?1 AND game = ?2",
+        params![from, game, to],
+    )?;
+    Ok(())
+}
@@ -0,0 +1,50 @@
+// Edit distance between two strings: the fewest single-character inserts,
+// deletes and substitutions that turn one into the other
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+// The candidate closest to the input, if any is within max_distance edits
+pub fn closest<'a>(input: &str, candidates: &[&'a str], max_distance: usize) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|candidate| (levenshtein(input, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levenshtein_counts_edits() {
+        assert_eq!(levenshtein("", ""), 0);
+        assert_eq!(levenshtein("tekken", "tekken"), 0);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("tekken8", "tekken 8"), 1);
+        assert_eq!(levenshtein("café", "cafe"), 1);
+    }
+
+    #[test]
+    fn closest_stays_within_the_distance() {
+        let candidates = ["!updatetotal", "!usertotal", "!gametotal"];
+        assert_eq!(closest("!usertotl", &candidates, 2), Some("!usertotal"));
+        assert_eq!(closest("!leaderboard", &candidates, 2), None);
+        assert_eq!(closest("!gametotal", &[], 2), None);
+    }
+}
@@ -1,18 +1,937 @@
+use clap::Parser;
 use dotenv::dotenv;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
 use std::env;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use serenity::async_trait;
-use serenity::builder::{CreateEmbed, CreateMessage};
-use serenity::model::channel::Message;
+use serenity::builder::{
+    CreateAllowedMentions, CreateAttachment, CreateAutocompleteResponse, CreateCommand,
+    CreateCommandOption, CreateEmbed, CreateInteractionResponse, CreateInteractionResponseMessage,
+    CreateMessage, CreateThread, EditMessage,
+};
+use serenity::http::{Http, HttpBuilder};
+use serenity::model::application::{
+    Command, CommandInteraction, CommandOptionType, Interaction, ResolvedOption, ResolvedValue,
+};
+use serenity::model::channel::{Channel, ChannelType, Message, Reaction, ReactionType};
 use serenity::model::gateway::Ready;
+use serenity::model::id::{ChannelId, MessageId};
+use serenity::model::permissions::Permissions;
 use serenity::prelude::*;
+use tracing::{Instrument, error, info, warn};
 
+mod cli;
+mod config;
+mod db;
+mod fuzzy;
+mod messages;
+mod roast;
 mod user;
 
 struct Handler;
 
-// Helper function to parse commands with quotes (like Unix shell)
+// Sends that fail (rate limits, missing permissions) are logged rather than dropped.
+// Inside a command the log carries that command's span, so it's clear which one failed
+trait LogSendError {
+    fn log_send_error(self);
+}
+
+impl<T> LogSendError for serenity::Result<T> {
+    fn log_send_error(self) {
+        if let Err(error) = self {
+            warn!("Couldn't send message: {error}");
+        }
+    }
+}
+
+// Which logs are shown: RUST_LOG when it's set and valid, otherwise our info
+// messages plus warnings from everything else
+fn log_filter(setting: Option<&str>) -> tracing_subscriber::EnvFilter {
+    setting
+        .and_then(|directives| tracing_subscriber::EnvFilter::try_new(directives).ok())
+        .unwrap_or_else(|| tracing_subscriber::EnvFilter::new("warn,shame_bot=info"))
+}
+
+// Where a command is listed in !help
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum HelpSection {
+    Users,
+    Games,
+    Info,
+    Rankings,
+    Admin,
+}
+
+impl HelpSection {
+    const ALL: [HelpSection; 5] = [
+        HelpSection::Users,
+        HelpSection::Games,
+        HelpSection::Info,
+        HelpSection::Rankings,
+        HelpSection::Admin,
+    ];
+
+    fn title(self) -> &'static str {
+        match self {
+            HelpSection::Users => "👤 User Management",
+            HelpSection::Games => "🎯 Game Management",
+            HelpSection::Info => "📊 Information & Viewing",
+            HelpSection::Rankings => "🏆 Rankings",
+            HelpSection::Admin => "🛠️ Admin",
+        }
+    }
+}
+
+// Everything !help knows about a command
+struct CommandInfo {
+    name: &'static str,
+    aliases: &'static [&'static str], // other names that run the same command
+    usage: &'static str,
+    description: &'static str,
+    arguments: &'static [(&'static str, &'static str)], // argument -> what goes there
+    example: &'static str,
+    section: HelpSection,
+    admin: bool, // only server managers can run it
+}
+
+// Every command the bot answers to. Both !help and typo suggestions are built from
+// this, so keep it in step with the dispatch in handle_command
+const COMMANDS: &[CommandInfo] = &[
+    CommandInfo {
+        name: "!adduser",
+        aliases: &[],
+        usage: "!adduser <user> \"<game>\" <total>",
+        description: "Create new user with first game",
+        arguments: &[
+            ("user", "The name to track them under, with no spaces"),
+            ("game", "Their first game, in quotes if it has spaces"),
+            ("total", "What they've spent on it so far"),
+        ],
+        example: "!adduser Q \"Tekken 8\" 200",
+        section: HelpSection::Users,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!deleteuser",
+        aliases: &[],
+        usage: "!deleteuser <user>",
+        description: "Delete user and all their games",
+        arguments: &[("user", "Who to delete")],
+        example: "!deleteuser Bob",
+        section: HelpSection::Users,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!renameuser",
+        aliases: &[],
+        usage: "!renameuser <old> <new>",
+        description: "Rename a user, keeping all their games",
+        arguments: &[
+            ("old", "Their current name"),
+            ("new", "The name to use from now on"),
+        ],
+        example: "!renameuser Q Quentin",
+        section: HelpSection::Users,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!merge",
+        aliases: &[],
+        usage: "!merge <from> <into> [preview]",
+        description: "Fold a duplicate user's games into another user",
+        arguments: &[
+            ("from", "The duplicate, which is deleted afterwards"),
+            ("into", "The user who keeps everything"),
+            ("preview", "Say what would happen without doing it"),
+        ],
+        example: "!merge Q2 Q",
+        section: HelpSection::Users,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!setdisplayname",
+        aliases: &[],
+        usage: "!setdisplayname <user> \"<name>\"",
+        description: "Change how a user is shown (`none` to reset)",
+        arguments: &[
+            ("user", "Who to rename in messages"),
+            ("name", "What to show instead, or `none`"),
+        ],
+        example: "!setdisplayname Q \"Quentin the Whale\"",
+        section: HelpSection::Users,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!nopoke",
+        aliases: &[],
+        usage: "!nopoke <user>",
+        description: "Opt out of the daily poke",
+        arguments: &[("user", "Who to leave out")],
+        example: "!nopoke Q",
+        section: HelpSection::Users,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!allowpoke",
+        aliases: &[],
+        usage: "!allowpoke <user>",
+        description: "Opt back into the daily poke",
+        arguments: &[("user", "Who to poke again")],
+        example: "!allowpoke Q",
+        section: HelpSection::Users,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!archive",
+        aliases: &[],
+        usage: "!archive <user>",
+        description: "Hide a user who's left from listings and rankings, keeping their totals",
+        arguments: &[("user", "Who to archive")],
+        example: "!archive Bob",
+        section: HelpSection::Users,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!unarchive",
+        aliases: &[],
+        usage: "!unarchive <user>",
+        description: "Bring an archived user back",
+        arguments: &[("user", "Who to bring back")],
+        example: "!unarchive Bob",
+        section: HelpSection::Users,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!linkaccount",
+        aliases: &[],
+        usage: "!linkaccount <alias> <user>",
+        description: "Send an alias's commands to another user (shared accounts)",
+        arguments: &[
+            ("alias", "The other name they go by"),
+            ("user", "The user commands for the alias should act on"),
+        ],
+        example: "!linkaccount Q_alt Q",
+        section: HelpSection::Users,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!unlink",
+        aliases: &[],
+        usage: "!unlink <alias>",
+        description: "Remove an account link",
+        arguments: &[("alias", "The linked name to unlink")],
+        example: "!unlink Q_alt",
+        section: HelpSection::Users,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!addgame",
+        aliases: &[],
+        usage: "!addgame <user> \"<game>\" <total>",
+        description: "Add new game to existing user",
+        arguments: &[
+            ("user", "Who the game belongs to"),
+            ("game", "The game, in quotes if it has spaces"),
+            ("total", "What they've spent on it so far"),
+        ],
+        example: "!addgame Alice \"Street Fighter 6\" 150",
+        section: HelpSection::Games,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!removegame",
+        aliases: &[],
+        usage: "!removegame <user> \"<game>\"",
+        description: "Remove specific game from user",
+        arguments: &[
+            ("user", "Who the game belongs to"),
+            ("game", "The game to remove"),
+        ],
+        example: "!removegame Alice \"Street Fighter 6\"",
+        section: HelpSection::Games,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!movegame",
+        aliases: &[],
+        usage: "!movegame <from> <to> \"<game>\" [history]",
+        description: "Move a game logged under the wrong person",
+        arguments: &[
+            ("from", "Who it's logged under now"),
+            ("to", "Who it should belong to"),
+            ("game", "The game to move"),
+            ("history", "Move its logged changes too"),
+        ],
+        example: "!movegame Q Alice \"Tekken 8\"",
+        section: HelpSection::Games,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!removegames",
+        aliases: &[],
+        usage: "!removegames <user> \"<pattern>\" [confirm]",
+        description: "Remove every game matching a pattern (`*` and `?` wildcards)",
+        arguments: &[
+            ("user", "Whose games to remove"),
+            ("pattern", "Game names to match, like `Tekken*`"),
+            ("confirm", "Leave off to see what would go first"),
+        ],
+        example: "!removegames Q \"Tekken*\" confirm",
+        section: HelpSection::Games,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!updatetotal",
+        aliases: &[],
+        usage: "!updatetotal <user> \"<game>\" <amount> [override] [confirm]",
+        description: "Add money to game total",
+        arguments: &[
+            ("user", "Who spent it"),
+            ("game", "What they spent it on"),
+            ("amount", "How much to add, like `50` or `9.99`"),
+            ("override", "Go past the game's cap (admin)"),
+            (
+                "confirm",
+                "Go ahead with a large update the bot asked about",
+            ),
+        ],
+        example: "!updatetotal Q \"Tekken 8\" 50",
+        section: HelpSection::Games,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!addmultiple",
+        aliases: &[],
+        usage: "!addmultiple <user> \"<game>\" <amount> ...",
+        description: "Add to several games at once (all or nothing)",
+        arguments: &[
+            ("user", "Who spent it"),
+            ("game amount", "One pair per game, as many as you like"),
+        ],
+        example: "!addmultiple Q \"Tekken 8\" 50 \"Street Fighter 6\" 30",
+        section: HelpSection::Games,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!preview",
+        aliases: &[],
+        usage: "!preview <user> \"<game>\" <amount>",
+        description: "See what an update would do without saving it",
+        arguments: &[
+            ("user", "Who would spend it"),
+            ("game", "What they'd spend it on"),
+            ("amount", "How much would be added"),
+        ],
+        example: "!preview Q \"Tekken 8\" 300",
+        section: HelpSection::Games,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!refund",
+        aliases: &[],
+        usage: "!refund <user> \"<game>\" <amount>",
+        description: "Take money back off a game total",
+        arguments: &[
+            ("user", "Who got the refund"),
+            ("game", "The game it was for"),
+            ("amount", "How much came back"),
+        ],
+        example: "!refund Q \"Tekken 8\" 20",
+        section: HelpSection::Games,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!undo",
+        aliases: &["!undouser"],
+        usage: "!undo <user>",
+        description: "Revert that user's last update, leaving everyone else's alone",
+        arguments: &[("user", "Whose last update to revert")],
+        example: "!undo Q",
+        section: HelpSection::Games,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!import",
+        aliases: &[],
+        usage: "!import <user> [preview]",
+        description: "Set games from `game,total` lines in an attached CSV file",
+        arguments: &[
+            ("user", "Whose games to set"),
+            ("preview", "Say what would be imported without doing it"),
+        ],
+        example: "!import Q (with games.csv attached)",
+        section: HelpSection::Games,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!renamegame",
+        aliases: &[],
+        usage: "!renamegame \"<old>\" \"<new>\" [preview]",
+        description: "Rename a game for everyone who has it",
+        arguments: &[
+            ("old", "The game's current name"),
+            ("new", "The name to use from now on"),
+            ("preview", "Say who would be changed without doing it"),
+        ],
+        example: "!renamegame \"Tekken8\" \"Tekken 8\"",
+        section: HelpSection::Games,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!tag",
+        aliases: &[],
+        usage: "!tag <user> \"<game>\" <tag>",
+        description: "Put a game in a category, like `gacha` or `fighting`",
+        arguments: &[
+            ("user", "Whose game to tag"),
+            ("game", "The game to tag"),
+            ("tag", "The category, as one word"),
+        ],
+        example: "!tag Q \"Genshin Impact\" gacha",
+        section: HelpSection::Games,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!untag",
+        aliases: &[],
+        usage: "!untag <user> \"<game>\" <tag>",
+        description: "Take a game out of a category",
+        arguments: &[
+            ("user", "Whose game to untag"),
+            ("game", "The game to untag"),
+            ("tag", "The category to remove"),
+        ],
+        example: "!untag Q \"Genshin Impact\" gacha",
+        section: HelpSection::Games,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!setcap",
+        aliases: &[],
+        usage: "!setcap <user> \"<game>\" <amount|none>",
+        description: "Hard-cap a game so updates past it are refused",
+        arguments: &[
+            ("user", "Whose game to cap"),
+            ("game", "The game to cap"),
+            ("amount", "The most it can reach, or `none` to lift it"),
+        ],
+        example: "!setcap Q \"Tekken 8\" 400",
+        section: HelpSection::Games,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!setgoal",
+        aliases: &[],
+        usage: "!setgoal <user> \"<game>\" <amount|none>",
+        description: "Set a spending goal to track progress against",
+        arguments: &[
+            ("user", "Whose game to budget"),
+            ("game", "The game to budget"),
+            ("amount", "The goal, or `none` to clear it"),
+        ],
+        example: "!setgoal Q \"Tekken 8\" 200",
+        section: HelpSection::Games,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!categorytotal",
+        aliases: &[],
+        usage: "!categorytotal <tag>",
+        description: "Show what everyone has spent on games with a tag",
+        arguments: &[("tag", "The category, like `gacha`")],
+        example: "!categorytotal gacha",
+        section: HelpSection::Info,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!getusers",
+        aliases: &[],
+        usage: "!getusers [--all]",
+        description: "Show all users and their games",
+        arguments: &[("--all", "Include archived users")],
+        example: "!getusers",
+        section: HelpSection::Info,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!usergames",
+        aliases: &[],
+        usage: "!usergames <user>",
+        description: "Show all games for specific user",
+        arguments: &[("user", "Whose games to show")],
+        example: "!usergames Q",
+        section: HelpSection::Info,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!gametotal",
+        aliases: &[],
+        usage: "!gametotal <user> \"<game>\"",
+        description: "Show total for specific game and when it last changed",
+        arguments: &[
+            ("user", "Whose total to show"),
+            ("game", "The game to show"),
+        ],
+        example: "!gametotal Q \"Tekken 8\"",
+        section: HelpSection::Info,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!search",
+        aliases: &[],
+        usage: "!search <text>",
+        description: "Find everyone's games with the text in their name",
+        arguments: &[("text", "Part of a game name")],
+        example: "!search tekken",
+        section: HelpSection::Info,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!stats",
+        aliases: &[],
+        usage: "!stats",
+        description: "Show the server's spending in numbers",
+        arguments: &[],
+        example: "!stats",
+        section: HelpSection::Info,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!usertotal",
+        aliases: &[],
+        usage: "!usertotal <user>",
+        description: "Show user's total across all games",
+        arguments: &[("user", "Whose total to show")],
+        example: "!usertotal Q",
+        section: HelpSection::Info,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!mystats",
+        aliases: &["!whoami"],
+        usage: "!mystats",
+        description: "Show your own games and total",
+        arguments: &[],
+        example: "!mystats",
+        section: HelpSection::Info,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!export",
+        aliases: &[],
+        usage: "!export <user> [json|csv]",
+        description: "Download a user's games as a file",
+        arguments: &[
+            ("user", "Whose games to export"),
+            ("json|csv", "The file format, JSON unless you say otherwise"),
+        ],
+        example: "!export Q csv",
+        section: HelpSection::Info,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!history",
+        aliases: &[],
+        usage: "!history <user> [\"<game>\"]",
+        description: "Show a user's last 10 updates",
+        arguments: &[
+            ("user", "Whose updates to show"),
+            ("game", "Only show updates to this game"),
+        ],
+        example: "!history Q \"Tekken 8\"",
+        section: HelpSection::Info,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!help",
+        aliases: &["!commands"],
+        usage: "!help [command]",
+        description: "Show this help message, or the details of one command",
+        arguments: &[("command", "The command to explain, like `updatetotal`")],
+        example: "!help updatetotal",
+        section: HelpSection::Info,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!consent",
+        aliases: &[],
+        usage: "!consent <yes|no>",
+        description: "Answer a request to track your spending",
+        arguments: &[("yes|no", "Whether the bot may track you")],
+        example: "!consent yes",
+        section: HelpSection::Info,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!version",
+        aliases: &[],
+        usage: "!version",
+        description: "Show which build of the bot is running",
+        arguments: &[],
+        example: "!version",
+        section: HelpSection::Info,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!quickhelp",
+        aliases: &[],
+        usage: "!quickhelp",
+        description: "Show the most used commands in one line",
+        arguments: &[],
+        example: "!quickhelp",
+        section: HelpSection::Info,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!whale",
+        aliases: &[],
+        usage: "!whale",
+        description: "Put the server's biggest spender in the spotlight",
+        arguments: &[],
+        example: "!whale",
+        section: HelpSection::Rankings,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!cheapskates",
+        aliases: &[],
+        usage: "!cheapskates [count]",
+        description: "Show who's spending the least",
+        arguments: &[("count", "How many to show")],
+        example: "!cheapskates 5",
+        section: HelpSection::Rankings,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!onthebrink",
+        aliases: &[],
+        usage: "!onthebrink [margin]",
+        description: "Show who's within `margin` (default 50) of a shame threshold",
+        arguments: &[("margin", "How close counts")],
+        example: "!onthebrink 25",
+        section: HelpSection::Rankings,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!inactive",
+        aliases: &[],
+        usage: "!inactive <days>",
+        description: "Show games nobody has updated in `days` days, oldest first",
+        arguments: &[("days", "How many days without an update counts as inactive")],
+        example: "!inactive 30",
+        section: HelpSection::Rankings,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!leaderboard",
+        aliases: &[],
+        usage: "!leaderboard [weighted|both|weekend|weekdays|nights|weeknights|thread] [count]",
+        description: "Show the biggest spenders across all games (with no count, everyone ten at a time, ◀️/▶️ to turn pages)",
+        arguments: &[
+            ("weighted", "Apply the per-game shame weights"),
+            (
+                "both [all|week]",
+                "Show all-time totals next to this week's spending",
+            ),
+            (
+                "weekend|weekdays|nights|weeknights",
+                "Rank by spending in that part of the week",
+            ),
+            (
+                "thread",
+                "Post it in its own thread and keep it updated (admin)",
+            ),
+            ("count", "How many to show"),
+        ],
+        example: "!leaderboard weighted 5",
+        section: HelpSection::Rankings,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!leaderboarddiff",
+        aliases: &[],
+        usage: "!leaderboarddiff <snapshot> <snapshot>",
+        description: "Show how the ranking moved between two snapshots",
+        arguments: &[(
+            "snapshot",
+            "A name saved with `!snapshot`, or `now` for the live board",
+        )],
+        example: "!leaderboarddiff season1 now",
+        section: HelpSection::Rankings,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!rank",
+        aliases: &[],
+        usage: "!rank <user>",
+        description: "Show where a user stands on the leaderboard",
+        arguments: &[("user", "Whose rank to show")],
+        example: "!rank Q",
+        section: HelpSection::Rankings,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!top",
+        aliases: &[],
+        usage: "!top [week|month|all]",
+        description: "Show the biggest spenders over a recent window",
+        arguments: &[("week|month|all", "How far back to look (default week)")],
+        example: "!top month",
+        section: HelpSection::Rankings,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!compare",
+        aliases: &[],
+        usage: "!compare <user> <user>",
+        description: "Put two users head to head",
+        arguments: &[("user user", "The two users to compare")],
+        example: "!compare Q Alice",
+        section: HelpSection::Rankings,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!topgame",
+        aliases: &[],
+        usage: "!topgame \"<game>\"",
+        description: "Show the biggest spenders on one game",
+        arguments: &[("game", "The game to rank")],
+        example: "!topgame \"Tekken 8\"",
+        section: HelpSection::Rankings,
+        admin: false,
+    },
+    CommandInfo {
+        name: "!globalalias",
+        aliases: &[],
+        usage: "!globalalias <short> \"<game>\"",
+        description: "Let everyone use a short name for a game",
+        arguments: &[
+            ("short", "The short name"),
+            ("game", "The full game name it stands for"),
+        ],
+        example: "!globalalias sf6 \"Street Fighter 6\"",
+        section: HelpSection::Admin,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!globalunalias",
+        aliases: &[],
+        usage: "!globalunalias <short>",
+        description: "Remove a global game alias",
+        arguments: &[("short", "The short name to remove")],
+        example: "!globalunalias sf6",
+        section: HelpSection::Admin,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!debug",
+        aliases: &[],
+        usage: "!debug <user>",
+        description: "Show a user's raw stored record",
+        arguments: &[("user", "Whose record to show")],
+        example: "!debug Q",
+        section: HelpSection::Admin,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!setweight",
+        aliases: &[],
+        usage: "!setweight \"<game>\" <weight>",
+        description: "Make a game's dollars count for more (or less) shame",
+        arguments: &[
+            ("game", "The game to weight"),
+            ("weight", "The multiplier, where 1 is normal"),
+        ],
+        example: "!setweight \"Genshin Impact\" 1.5",
+        section: HelpSection::Admin,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!reload",
+        aliases: &[],
+        usage: "!reload",
+        description: "Re-read the database after changing it outside the bot",
+        arguments: &[],
+        example: "!reload",
+        section: HelpSection::Admin,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!snapshot",
+        aliases: &[],
+        usage: "!snapshot <name>",
+        description: "Save the current leaderboard for !leaderboarddiff",
+        arguments: &[("name", "What to call it")],
+        example: "!snapshot season1",
+        section: HelpSection::Admin,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!setshamerole",
+        aliases: &[],
+        usage: "!setshamerole <@role|none>",
+        description: "Ping this role instead of @here on callouts",
+        arguments: &[("@role", "The role to ping, or `none` for @here")],
+        example: "!setshamerole @Whales",
+        section: HelpSection::Admin,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!setshamechannel",
+        aliases: &[],
+        usage: "!setshamechannel <#channel|none>",
+        description: "Post callouts in this channel",
+        arguments: &[(
+            "#channel",
+            "Where callouts go, or `none` for wherever the update was",
+        )],
+        example: "!setshamechannel #hall-of-shame",
+        section: HelpSection::Admin,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!requireconsent",
+        aliases: &[],
+        usage: "!requireconsent <on|off>",
+        description: "Ask new people by DM before tracking them",
+        arguments: &[("on|off", "Whether to ask first")],
+        example: "!requireconsent on",
+        section: HelpSection::Admin,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!setthreshold",
+        aliases: &[],
+        usage: "!setthreshold <amount|none>",
+        description: "Change the total that sets off the first callout",
+        arguments: &[("amount", "The new threshold, or `none` for the default")],
+        example: "!setthreshold 150",
+        section: HelpSection::Admin,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!reset",
+        aliases: &[],
+        usage: "!reset [confirm]",
+        description: "Wipe every user and game for a new season",
+        arguments: &[("confirm", "Send within 30 seconds of `!reset` to go ahead")],
+        example: "!reset confirm",
+        section: HelpSection::Admin,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!backup",
+        aliases: &[],
+        usage: "!backup",
+        description: "Save a snapshot of this server's users and games",
+        arguments: &[],
+        example: "!backup",
+        section: HelpSection::Admin,
+        admin: true,
+    },
+    CommandInfo {
+        name: "!restore",
+        aliases: &[],
+        usage: "!restore [backup] [confirm]",
+        description: "List backups, or put one back in place of the current data",
+        arguments: &[
+            ("backup", "The backup to restore, from the list"),
+            ("confirm", "Send within 30 seconds to go ahead"),
+        ],
+        example: "!restore users-20250101-120000.json confirm",
+        section: HelpSection::Admin,
+        admin: true,
+    },
+];
+
+// Every name a command can be typed as, aliases included
+fn command_names() -> Vec<&'static str> {
+    COMMANDS
+        .iter()
+        .flat_map(|command| std::iter::once(command.name).chain(command.aliases.iter().copied()))
+        .collect()
+}
+
+// The command with this name or alias. The `!` is optional, so `!help updatetotal` works
+fn find_command(name: &str) -> Option<&'static CommandInfo> {
+    let name = name.strip_prefix('!').unwrap_or(name).to_lowercase();
+    COMMANDS.iter().find(|command| {
+        std::iter::once(command.name)
+            .chain(command.aliases.iter().copied())
+            .any(|known| known[1..] == name)
+    })
+}
+
+// The registered command a message runs: its first word, matched exactly, so one
+// command's name being the start of another's can't run the wrong handler
+fn command_name(content: &str) -> Option<&str> {
+    content
+        .split_whitespace()
+        .next()
+        .filter(|name| command_names().contains(name))
+}
+
+// Anything that looks like a command but isn't one gets a nudge instead of silence.
+// Normal chat, and a lone `!`, get nothing
+fn unknown_command_reply(content: &str) -> Option<String> {
+    let name = content.split_whitespace().next()?;
+    if !name.starts_with('!') || name.len() < 2 || command_name(content).is_some() {
+        return None;
+    }
+
+    Some(match suggest_command(name, &command_names()) {
+        Some(suggestion) => format!(
+            "Unknown command `{}`. Did you mean `{}`? Try `!help`.",
+            name, suggestion
+        ),
+        None => format!("Unknown command `{}`. Try `!help`.", name),
+    })
+}
+
+// Guess which command a typo was meant to be. Close spellings (two edits or fewer)
+// win, then abbreviations like `!uptotal` that keep a command's letters in order
+fn suggest_command(input: &str, known: &[&str]) -> Option<String> {
+    if let Some(command) = fuzzy::closest(input, known, 2) {
+        return Some(command.to_string());
+    }
+
+    // Abbreviations need a few letters past the `!` to mean anything
+    if input.chars().count() < 4 {
+        return None;
+    }
+
+    known
+        .iter()
+        .filter(|command| {
+            let mut letters = command.chars();
+            input.chars().all(|ch| letters.any(|c| c == ch))
+        })
+        .min_by_key(|command| command.len())
+        .map(|command| command.to_string())
+}
+
+// Phone keyboards often swap straight quotes for curly "smart" quotes
+fn normalize_quotes(input: &str) -> String {
+    input.replace(
+        ['\u{201C}', '\u{201D}', '\u{201E}', '\u{201F}', '\u{2033}'],
+        "\"",
+    )
+}
+
+// Helper function to parse commands with quotes (like Unix shell).
+// handle_command turns away unmatched quotes before any handler calls this
 fn parse_command_with_quotes(input: &str) -> Vec<String> {
+    split_command(input).unwrap_or_default()
+}
+
+// Split a command into words, keeping "quoted phrases" together.
+// `\"` is a literal quote, so game titles can contain them
+fn split_command(input: &str) -> Result<Vec<String>, String> {
+    let input = normalize_quotes(input);
     let mut parts = Vec::new();
     let mut current_part = String::new();
     let mut in_quotes = false;
@@ -20,6 +939,10 @@ fn parse_command_with_quotes(input: &str) -> Vec<String> {
 
     while let Some(ch) = chars.next() {
         match ch {
+            '\\' if chars.peek() == Some(&'"') => {
+                current_part.push('"');
+                chars.next();
+            }
             '"' => {
                 in_quotes = !in_quotes;
                 // Don't include the quote character in the result
@@ -36,416 +959,5619 @@ fn parse_command_with_quotes(input: &str) -> Vec<String> {
         }
     }
 
+    if in_quotes {
+        return Err("Unmatched quote in command".to_string());
+    }
+
     // Don't forget the last part
     if !current_part.is_empty() {
         parts.push(current_part);
     }
 
-    parts
+    Ok(parts)
 }
 
-#[async_trait]
-impl EventHandler for Handler {
-    async fn message(&self, ctx: Context, msg: Message) {
-        // Help message
-        if msg.content == "!help" || msg.content == "!commands" {
-            let help_embed = CreateEmbed::new()
-                .title("🎮 ShameBot - Command List")
-                .description("Track your gaming totals across different games! When the totals get high, it puts you on blast for your spending!")
-                .color(0x00ff00) // Green color
-                .field(
-                            "👤 User Management",
-                            "• `!adduser <user> \"<game>\" <total>` - Create new user with first game\n• `!deleteuser <user>` - Delete user and all their games",
-                            false
-                        )
-                        .field(
-                            "🎯 Game Management",
-                            "• `!addgame <user> \"<game>\" <total>` - Add new game to existing user\n• `!removegame <user> \"<game>\"` - Remove specific game from user\n• `!updatetotal <user> \"<game>\" <amount>` - Add money to game total",
-                            false
-                        )
-                        .field(
-                            "📊 Information & Viewing",
-                            "• `!getusers` - Show all users and their games\n• `!usergames <user>` - Show all games for specific user\n• `!gametotal <user> \"<game>\"` - Show total for specific game\n• `!usertotal <user>` - Show user's total across all games\n• `!help` or `!commands` - Show this help message",
-                            false
-                        )
-                        .field(
-                            "💡 Command Examples",
-                            "```\n!adduser Q \"Tekken 8\" 200\n!addgame Alice \"Street Fighter 6\" 150\n!updatetotal Q \"Tekken 8\" 50\n!usergames Q\n!gametotal Q \"Tekken 8\"\n!usertotal Q\n!removegame Alice \"Street Fighter 6\"\n!deleteuser Bob```",
-                            false
-                        )
-                        .field(
-                            "⚠️ Important Notes",
-                            "• Use quotes around game names with spaces\n• Game names are case-sensitive\n• Amounts must be valid numbers\n• User names cannot contain spaces",
-                            false
-                        );
+// Discord refuses messages longer than this many characters
+const DISCORD_MESSAGE_LIMIT: usize = 2000;
 
-            let builder = CreateMessage::new().embed(help_embed);
+// Break text into chunks of at most `limit` characters, splitting between lines where it can
+fn split_lines(text: &str, limit: usize) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut current = String::new();
 
-            if let Err(error) = msg.channel_id.send_message(&ctx.http, builder).await {
-                println!("Error sending help message: {error:?}");
-                // Fallback to simple text if embed fails
-                let fallback_text = r#"
-        **🎮 Game Tracker Bot Commands:**
+    for line in text.lines() {
+        // A single line too long for one message gets cut up on its own
+        let mut line: Vec<char> = line.chars().collect();
+        while line.len() > limit {
+            let rest = line.split_off(limit);
+            if !current.is_empty() {
+                messages.push(std::mem::take(&mut current));
+            }
+            messages.push(line.into_iter().collect());
+            line = rest;
+        }
+        let line: String = line.into_iter().collect();
 
-        **User Management:**
-        • !adduser <user> "<game>" <total> - Create new user
-        • !deleteuser <user> - Delete user and all games
+        let needed = if current.is_empty() { 0 } else { 1 } + line.chars().count();
+        if current.chars().count() + needed > limit {
+            messages.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(&line);
+    }
 
-        **Game Management:**
-        • !addgame <user> "<game>" <total> - Add game to user
-        • !removegame <user> "<game>" - Remove game from user
-        • !updatetotal <user> "<game>" <amount> - Add to game total
+    if !current.is_empty() {
+        messages.push(current);
+    }
+    messages
+}
 
-        **Information:**
-        • !getusers - Show all users and games
-        • !usergames <user> - Show user's games
-        • !help - Show commands
+// Admin and destructive commands require the Manage Server permission in the current
+// channel. Server administrators have it whatever their roles say
+fn grants_admin(permissions: Permissions) -> bool {
+    permissions.manage_guild() || permissions.administrator()
+}
 
-        **Examples:**
-        !adduser Q "Tekken 8" 200
-        !updatetotal Q "Tekken 8" 50
+// The author's permissions in the message's channel. The cache answers most of the
+// time; otherwise the member, channel and server are fetched so a cold cache doesn't
+// lock admins out
+async fn author_permissions(ctx: &Context, msg: &Message) -> Option<Permissions> {
+    if let Some(permissions) = msg.author_permissions(&ctx.cache) {
+        return Some(permissions);
+    }
 
-        **Note:** Use quotes around game names with spaces!
-        "#;
-                msg.channel_id.say(&ctx.http, fallback_text).await.ok();
-            }
-        }
+    let guild_id = msg.guild_id?;
+    let (Ok(Channel::Guild(channel)), Ok(member), Ok(guild)) = (
+        msg.channel(&ctx).await,
+        msg.member(&ctx).await,
+        guild_id.to_partial_guild(&ctx).await,
+    ) else {
+        return None;
+    };
 
-        if msg.content == "!quickhelp" {
-            let quick_help = "**Quick Commands:** `!adduser`, `!addgame`, `!updatetotal`, `!getusers`, `!usergames`, `!deleteuser`, `!removegame` | Use `!help` for details";
-            msg.channel_id.say(&ctx.http, quick_help).await.ok();
-        }
+    Some(guild.user_permissions_in(&channel, &member))
+}
 
-        // !adduser Q "Tekken 8" 200
-        if msg.content.starts_with("!adduser") {
-            let parts = parse_command_with_quotes(&msg.content);
+async fn is_admin(ctx: &Context, msg: &Message) -> bool {
+    author_permissions(ctx, msg).await.is_some_and(grants_admin)
+}
 
-            if parts.len() != 4 {
-                msg.channel_id
-                    .say(
-                        &ctx.http,
-                        "Usage: !adduser <username> \"<game name>\" <total>",
-                    )
-                    .await
-                    .ok();
-                return;
-            }
+// How long someone has to confirm a command that can't be taken back
+const CONFIRMATION_WINDOW: Duration = Duration::from_secs(30);
 
-            let username = &parts[1];
-            let game = &parts[2];
-            let total = &parts[3];
+// A command waiting for its author to confirm it
+struct PendingConfirmation {
+    guild_id: u64,
+    author_id: u64,
+    action: String,
+    requested: Instant,
+}
 
-            match user::add_user(username, game, total) {
-                Ok(_) => {
-                    let mes = format!(
-                        "Added user {} with game '{}' and total ${}",
-                        username, game, total
-                    );
-                    msg.channel_id.say(&ctx.http, mes).await.ok();
-                }
-                Err(e) => {
-                    msg.channel_id
-                        .say(&ctx.http, format!("Error: {}", e))
-                        .await
-                        .ok();
-                }
-            }
-        }
+static PENDING_CONFIRMATIONS: Mutex<Vec<PendingConfirmation>> = Mutex::new(Vec::new());
 
-        // !addgame Q "Street Fighter 6" 150
-        if msg.content.starts_with("!addgame") {
-            let parts = parse_command_with_quotes(&msg.content);
+// Remember that an author has asked for an action, replacing any earlier request for it
+fn request_confirmation(guild_id: u64, author_id: u64, action: &str) {
+    let mut pending = PENDING_CONFIRMATIONS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
 
-            if parts.len() != 4 {
-                msg.channel_id
-                    .say(
-                        &ctx.http,
-                        "Usage: !addgame <username> \"<game name>\" <starting_total>",
-                    )
-                    .await
-                    .ok();
-                return;
-            }
+    pending.retain(|request| {
+        request.requested.elapsed() < CONFIRMATION_WINDOW
+            && !(request.guild_id == guild_id
+                && request.author_id == author_id
+                && request.action == action)
+    });
+    pending.push(PendingConfirmation {
+        guild_id,
+        author_id,
+        action: action.to_string(),
+        requested: Instant::now(),
+    });
+}
 
-            let username = &parts[1];
-            let game = &parts[2];
-            let total = &parts[3];
+// Whether the author asked for this action within the window. Each request confirms once
+fn take_confirmation(guild_id: u64, author_id: u64, action: &str) -> bool {
+    let mut pending = PENDING_CONFIRMATIONS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
 
-            match user::add_game(username, game, total) {
-                Ok(_) => {
-                    let mes = format!(
-                        "Added game '{}' with total ${} to user {}",
-                        game, total, username
-                    );
-                    msg.channel_id.say(&ctx.http, mes).await.ok();
-                }
-                Err(e) => {
-                    msg.channel_id
-                        .say(&ctx.http, format!("Error: {}", e))
-                        .await
-                        .ok();
-                }
-            }
-        }
+    let found = pending.iter().position(|request| {
+        request.guild_id == guild_id && request.author_id == author_id && request.action == action
+    });
 
-        // !updatetotal Q "Tekken 8" 50
-        if msg.content.starts_with("!updatetotal") {
-            let parts = parse_command_with_quotes(&msg.content);
+    match found {
+        Some(index) => pending.remove(index).requested.elapsed() < CONFIRMATION_WINDOW,
+        None => false,
+    }
+}
 
-            if parts.len() != 4 {
-                msg.channel_id
-                    .say(
-                        &ctx.http,
-                        "Usage: !updatetotal <username> \"<game name>\" <additional_amount>",
-                    )
-                    .await
-                    .ok();
-                return;
-            }
+// The pending confirmation for a large update, shared by !updatetotal and /updatetotal
+fn large_update_action(username: &str, game: &str, amount: i64) -> String {
+    format!(
+        "updatetotal {} {} {}",
+        username.to_lowercase(),
+        game,
+        amount
+    )
+}
 
-            let username = &parts[1];
-            let game = &parts[2];
-            let total = &parts[3];
+// A game name as typed, with the server's aliases applied. If they can't be read the
+// name is used as typed, so one bad setting doesn't stop every command
+fn game_name(guild_id: u64, game: &str) -> String {
+    config::resolve_game_name(guild_id, game).unwrap_or_else(|e| {
+        warn!("Couldn't read the game aliases for server {guild_id}: {e}");
+        game.to_string()
+    })
+}
 
-            match user::update_total(username, game, total) {
-                Ok((new_total, crossed_threshold)) => {
-                    let mes = format!(
-                        "{}'s total for '{}' was updated by ${}",
-                        username, game, new_total
-                    );
-                    msg.channel_id.say(&ctx.http, mes).await.ok();
+// Where a server's threshold callouts go and who they ping, from its settings.
+// Falls back to the command's channel and @here. Nobody gets called out while the
+// consent gate is on and they haven't consented
+fn callout_target(
+    guild_id: u64,
+    fallback_channel: ChannelId,
+    username: &str,
+) -> Option<(ChannelId, String)> {
+    // With the consent gate on, only people who agreed get called out
+    if config::requires_consent(guild_id) && !user::has_consented(guild_id, username) {
+        return None;
+    }
 
-                    if crossed_threshold {
-                        let troll_msg =
-                            format!("@here 🚨 {} just crossed $300 in {}! 💸", username, game);
-                        msg.channel_id.say(&ctx.http, troll_msg).await.ok();
-                    }
-                }
-                Err(e) => {
-                    msg.channel_id
-                        .say(&ctx.http, format!("Error: {}", e))
-                        .await
-                        .ok();
-                }
-            }
-        }
+    let setting = |value: Result<Option<u64>, _>| {
+        value.unwrap_or_else(|e| {
+            warn!("Couldn't read the callout settings for server {guild_id}: {e}");
+            None
+        })
+    };
+    let channel = setting(config::get_shame_channel(guild_id))
+        .map(ChannelId::new)
+        .unwrap_or(fallback_channel);
+    let ping = match setting(config::get_shame_role(guild_id)) {
+        Some(role_id) => format!("<@&{}>", role_id),
+        None => "@here".to_string(),
+    };
 
-        // !removegame Q "Tekken 8"
-        if msg.content.starts_with("!removegame") {
-            let parts = parse_command_with_quotes(&msg.content);
+    Some((channel, ping))
+}
 
-            if parts.len() != 3 {
-                msg.channel_id
-                    .say(&ctx.http, "Usage: !removegame <username> \"<game name>\"")
-                    .await
-                    .ok();
-                return;
-            }
+// Break up anything in user-typed text that Discord would treat as a mention, like a game
+// called `@everyone`, by putting a zero-width space after each `@`
+fn escape_mentions(text: &str) -> String {
+    text.replace('@', "@\u{200B}")
+}
 
-            let username = &parts[1];
-            let game = &parts[2];
+// Callouts are the one place the bot pings on purpose: @here or the shame role.
+// Everything else is sent with no pings allowed at all
+fn callout_mentions() -> CreateAllowedMentions {
+    CreateAllowedMentions::new().everyone(true).all_roles(true)
+}
 
-            match user::remove_game(username, game) {
-                Ok(_) => {
-                    let mes = format!("Removed game '{}' from user {}", game, username);
-                    msg.channel_id.say(&ctx.http, mes).await.ok();
-                }
-                Err(e) => {
-                    msg.channel_id
-                        .say(&ctx.http, format!("Error: {}", e))
-                        .await
-                        .ok();
-                }
-            }
-        }
+// Call out a user whose updates crossed a shame threshold or who is spending fast.
+// Each update is a game with what was added, its new total and the threshold it crossed
+async fn send_spending_callouts(
+    ctx: &Context,
+    fallback_channel: ChannelId,
+    guild_id: u64,
+    username: &str,
+    updates: &[(String, i64, i64, user::ThresholdCrossed)],
+) {
+    let Some((callout_channel, ping)) = callout_target(guild_id, fallback_channel, username) else {
+        return;
+    };
+    let shown = user::get_display_name(guild_id, username);
 
-        // !deleteuser Q
-        if msg.content.starts_with("!deleteuser") {
-            let parts = parse_command_with_quotes(&msg.content);
+    for (game, _, new_total, crossed) in updates {
+        let threshold = match crossed {
+            user::ThresholdCrossed::SuperTroll => user::SUPER_TROLL_THRESHOLD,
+            _ => user::get_threshold(guild_id).unwrap_or(user::TROLL_THRESHOLD),
+        };
+        // The ping is allowed through, so names mustn't be able to add pings of their own
+        let callout = messages::shame_messages()
+            .render(
+                *crossed,
+                &escape_mentions(&shown),
+                &escape_mentions(game),
+                *new_total,
+                threshold,
+            )
+            .map(|text| format!("{} {}", ping, text));
 
-            if parts.len() != 2 {
-                msg.channel_id
-                    .say(&ctx.http, "Usage: !deleteuser <username>")
+        match (crossed, callout) {
+            (user::ThresholdCrossed::Troll, Some(troll_msg)) => {
+                let builder = CreateMessage::new()
+                    .content(troll_msg)
+                    .allowed_mentions(callout_mentions());
+                callout_channel
+                    .send_message(&ctx.http, builder)
                     .await
-                    .ok();
-                return;
+                    .log_send_error();
             }
-
-            let username = &parts[1];
-
-            match user::delete_user(username) {
-                Ok(_) => {
-                    let mes = format!("Deleted user {} and all their games", username);
-                    msg.channel_id.say(&ctx.http, mes).await.ok();
-                }
-                Err(e) => {
-                    msg.channel_id
-                        .say(&ctx.http, format!("Error: {}", e))
-                        .await
-                        .ok();
-                }
+            (user::ThresholdCrossed::SuperTroll, Some(super_troll_msg)) => {
+                let builder = super_troll_message(super_troll_msg)
+                    .await
+                    .allowed_mentions(callout_mentions());
+                callout_channel
+                    .send_message(&ctx.http, builder)
+                    .await
+                    .log_send_error();
             }
+            _ => {}
         }
+    }
 
-        // !usergames Q - show all games for a specific user
-        if msg.content.starts_with("!usergames") {
-            let parts = parse_command_with_quotes(&msg.content);
+    // Rapid spending gets called out whatever the totals are, once for the lot
+    let added = updates.iter().map(|(_, added, _, _)| added).sum();
+    let window = interval_from_env("SHAMEBOT_VELOCITY_WINDOW_SECS", 60 * 60);
+    if let Ok(velocity) = user::recent_velocity(guild_id, username, window.as_secs() as i64)
+        && crossed_velocity_limit(velocity, added, velocity_limit())
+    {
+        let mes = roast::slow_down_callout(&shown, velocity, window.as_secs() / 60);
+        callout_channel.say(&ctx.http, mes).await.log_send_error();
+    }
+}
 
-            if parts.len() != 2 {
-                msg.channel_id
-                    .say(&ctx.http, "Usage: !usergames <username>")
-                    .await
-                    .ok();
-                return;
-            }
+// Image attached to super-threshold callouts, set with SHAMEBOT_SHAME_GIF
+enum ShameAsset {
+    Url(String),
+    File(String),
+}
 
-            let username = &parts[1];
+// Decide what the shame GIF setting points at. Unset or blank means text-only
+fn shame_asset(setting: Option<&str>) -> Option<ShameAsset> {
+    let setting = setting?.trim();
 
-            match user::get_user_games(username) {
-                Ok(games) => {
-                    if games.is_empty() {
-                        msg.channel_id
-                            .say(&ctx.http, format!("User {} has no games", username))
-                            .await
-                            .ok();
-                    } else {
-                        let games_list: Vec<String> = games
-                            .iter()
-                            .map(|(game, total)| format!("• {}: ${}", game, total))
-                            .collect();
+    if setting.is_empty() {
+        None
+    } else if setting.starts_with("http://") || setting.starts_with("https://") {
+        Some(ShameAsset::Url(setting.to_string()))
+    } else {
+        Some(ShameAsset::File(setting.to_string()))
+    }
+}
 
-                        let mes = format!("**{}'s Games:**\n{}", username, games_list.join("\n"));
-                        msg.channel_id.say(&ctx.http, mes).await.ok();
-                    }
-                }
-                Err(e) => {
-                    msg.channel_id
-                        .say(&ctx.http, format!("Error: {}", e))
-                        .await
-                        .ok();
-                }
+// Build the super-threshold callout, with the shame GIF when one is configured and loadable
+async fn super_troll_message(text: String) -> CreateMessage {
+    let message = CreateMessage::new().content(text);
+    let setting = env::var("SHAMEBOT_SHAME_GIF").ok();
+
+    match shame_asset(setting.as_deref()) {
+        Some(ShameAsset::Url(url)) => message.embed(CreateEmbed::new().image(url)),
+        Some(ShameAsset::File(path)) => match CreateAttachment::path(&path).await {
+            Ok(attachment) => message.add_file(attachment),
+            Err(error) => {
+                warn!("Couldn't load shame GIF '{path}', sending text only: {error:?}");
+                message
             }
-        }
+        },
+        None => message,
+    }
+}
 
-        // !getusers - show all users (updated for new structure)
-        if msg.content == "!getusers" {
-            match user::get_users() {
-                Ok(user_list) => {
-                    if user_list.is_empty() {
-                        msg.channel_id.say(&ctx.http, "No users are currently added to the bot! Try the !adduser command.").await.ok();
-                        return;
-                    }
+// Hold tracking for someone new until they agree to it: store the request and DM
+// the mentioned person asking them to reply !consent yes
+async fn ask_for_consent(ctx: &Context, msg: &Message, guild_id: u64, game: &str, total: &str) {
+    let Some(person) = msg.mentions.first() else {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "This server asks people before tracking them, so mention who to add (like @Q)",
+            )
+            .await
+            .log_send_error();
+        return;
+    };
+    let Some(cents) = user::parse_money(total) else {
+        msg.channel_id
+            .say(&ctx.http, "Error: Invalid number for starting total")
+            .await
+            .log_send_error();
+        return;
+    };
 
-                    let user_strings: Vec<String> = user_list
-                        .iter()
-                        .map(|user| {
-                            let games_info: Vec<String> = user
-                                .games
-                                .iter()
-                                .map(|(game, total)| format!("  • {}: ${}", game, total))
-                                .collect();
-
-                            format!("**{}**\n{}", user.user, games_info.join("\n"))
-                        })
-                        .collect();
-
-                    let mes = format!("**All Users:**\n{}", user_strings.join("\n\n"));
-                    msg.channel_id.say(&ctx.http, mes).await.ok();
-                }
-                Err(e) => {
-                    msg.channel_id
-                        .say(&ctx.http, format!("Error: {}", e))
-                        .await
-                        .ok();
-                }
-            }
+    let request = user::PendingConsent {
+        discord_id: person.id.get(),
+        guild_id,
+        username: person.name.clone(),
+        game: game.to_string(),
+        total: total.to_string(),
+        requested_at: user::now(),
+    };
+    if let Err(e) = user::request_consent(request) {
+        msg.channel_id
+            .say(&ctx.http, format!("Error: {}", e))
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let ask = format!(
+        "{} wants ShameBot to track your spending on '{}', starting at {}. Reply `!consent yes` to agree or `!consent no` to decline within a day.",
+        msg.author.name,
+        game,
+        user::format_money(cents)
+    );
+    let mes = match person
+        .direct_message(&ctx.http, CreateMessage::new().content(ask))
+        .await
+    {
+        Ok(_) => format!(
+            "Asked {} for consent. They'll be tracked once they reply `!consent yes`",
+            person.name
+        ),
+        Err(error) => {
+            warn!("Error sending consent request: {error:?}");
+            format!(
+                "Couldn't DM {}, but they can reply `!consent yes` here to be tracked",
+                person.name
+            )
         }
+    };
+    msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+}
 
-        if msg.content.starts_with("!gametotal") {
-            let parts = parse_command_with_quotes(&msg.content);
+// Read a positive number of seconds from the environment, falling back to a default
+fn interval_from_env(name: &str, default_secs: u64) -> Duration {
+    interval_setting(env::var(name).ok().as_deref(), default_secs)
+}
 
-            if parts.len() != 3 {
-                msg.channel_id
-                    .say(&ctx.http, "Usage: !gametotal <username> \"<game>\"")
-                    .await
-                    .ok();
-                return;
-            }
+// A scheduler period from its setting. Unset, zero or not a number of seconds means
+// the default
+fn interval_setting(setting: Option<&str>, default_secs: u64) -> Duration {
+    let secs = setting
+        .and_then(|value| value.parse::<u64>().ok())
+        .filter(|&secs| secs > 0)
+        .unwrap_or(default_secs);
 
-            let username = &parts[1];
-            let game = &parts[2];
+    Duration::from_secs(secs)
+}
 
-            match user::get_game_total(username, game) {
-                Ok(total) => {
-                    let mes = format!("{}'s total for '{}': ${}", username, game, total);
-                    msg.channel_id.say(&ctx.http, mes).await.ok();
-                }
-                Err(e) => {
-                    msg.channel_id
-                        .say(&ctx.http, format!("Error: {}", e))
-                        .await
-                        .ok();
+// A spending burst is called out once the money added inside the velocity window
+// reaches SHAMEBOT_VELOCITY_LIMIT (default $300). Returns cents
+fn velocity_limit() -> i64 {
+    env::var("SHAMEBOT_VELOCITY_LIMIT")
+        .ok()
+        .and_then(|value| user::parse_money(&value))
+        .filter(|&limit| limit > 0)
+        .unwrap_or(300 * 100)
+}
+
+// Whether the update that just added `added` took the window's spending up past the
+// limit. Later updates in the same burst don't repeat the callout
+fn crossed_velocity_limit(velocity: i64, added: i64, limit: i64) -> bool {
+    added > 0 && velocity >= limit && velocity - added < limit
+}
+
+// Read a Discord channel id from the environment, if one is set
+fn channel_from_env(name: &str) -> Option<ChannelId> {
+    env::var(name)
+        .ok()
+        .and_then(|value| value.parse::<u64>().ok())
+        .filter(|&id| id != 0)
+        .map(ChannelId::new)
+}
+
+// The server a channel belongs to, or None for DMs and channels the bot can't see
+async fn channel_guild(http: &Http, channel_id: ChannelId) -> Option<u64> {
+    match http.get_channel(channel_id).await {
+        Ok(Channel::Guild(channel)) => Some(channel.guild_id.get()),
+        _ => None,
+    }
+}
+
+// Optional daily poke: roast the top spender in SHAMEBOT_POKE_CHANNEL_ID every
+// SHAMEBOT_POKE_INTERVAL_SECS (default one day). Users can opt out with !nopoke
+fn spawn_poke_scheduler(http: Arc<Http>) {
+    let Some(channel_id) = channel_from_env("SHAMEBOT_POKE_CHANNEL_ID") else {
+        return;
+    };
+    let period = interval_from_env("SHAMEBOT_POKE_INTERVAL_SECS", 24 * 60 * 60);
+
+    tokio::spawn(async move {
+        // Pokes roast whoever is on top in the channel's own server
+        let Some(guild_id) = channel_guild(&http, channel_id).await else {
+            warn!("SHAMEBOT_POKE_CHANNEL_ID isn't a server channel the bot can see, no daily poke");
+            return;
+        };
+
+        let mut interval = tokio::time::interval(period);
+        // The first tick fires immediately; don't poke anyone just for starting up
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+
+            let min_total = user::get_threshold(guild_id).unwrap_or(user::TROLL_THRESHOLD);
+            match user::pick_poke_target(guild_id, min_total) {
+                Ok(Some((username, total))) => {
+                    let mes =
+                        roast::poke_message(&user::get_display_name(guild_id, &username), total);
+                    if let Err(error) = channel_id.say(&http, mes).await {
+                        warn!("Error sending daily poke: {error:?}");
+                    }
                 }
+                Ok(None) => {}
+                Err(e) => error!("Error picking daily poke target: {}", e),
             }
         }
+    });
+}
 
-        if msg.content.starts_with("!usertotal") {
-            let parts = parse_command_with_quotes(&msg.content);
+// Which column the two-column !leaderboard is sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BoardSort {
+    AllTime,
+    ThisWeek,
+}
 
-            if parts.len() != 2 {
-                msg.channel_id
-                    .say(&ctx.http, "Usage: !usertotal <username>")
-                    .await
-                    .ok();
-                return;
-            }
+// Each user's all-time total next to what they've added this week, sorted by one of
+// them with the other and then the name breaking ties. Anyone who hasn't spent this
+// week shows zero for it
+fn combined_rows(
+    all_time: &[(String, i64)],
+    this_week: &[(String, i64)],
+    sort: BoardSort,
+) -> Vec<(String, i64, i64)> {
+    let mut rows: Vec<(String, i64, i64)> = all_time
+        .iter()
+        .map(|(username, total)| {
+            let week = this_week
+                .iter()
+                .find(|(name, _)| name == username)
+                .map_or(0, |&(_, spent)| spent);
+            (username.clone(), *total, week)
+        })
+        .collect();
 
-            let username = &parts[1];
+    rows.sort_by(|a, b| {
+        let (first, second) = match sort {
+            BoardSort::AllTime => (b.1.cmp(&a.1), b.2.cmp(&a.2)),
+            BoardSort::ThisWeek => (b.2.cmp(&a.2), b.1.cmp(&a.1)),
+        };
+        first.then(second).then_with(|| a.0.cmp(&b.0))
+    });
+    rows
+}
 
-            match user::get_user_total_all_games(username) {
-                Ok(total) => {
-                    let mes = format!(
-                        "{}'s total across all available games: ${}",
-                        username, total
-                    );
-                    msg.channel_id.say(&ctx.http, mes).await.ok();
-                }
-                Err(e) => {
-                    msg.channel_id
-                        .say(&ctx.http, format!("Error: {}", e))
-                        .await
-                        .ok();
-                }
-            }
+// The part of the week a !leaderboard keyword ranks spending in
+fn spend_window(keyword: &str) -> Option<user::SpendWindow> {
+    match keyword {
+        "weekend" => Some(user::SpendWindow::Weekend),
+        "weekdays" => Some(user::SpendWindow::Weekdays),
+        "nights" => Some(user::SpendWindow::Nights),
+        "weeknights" => Some(user::SpendWindow::Weeknights),
+        _ => None,
+    }
+}
+
+// Everyone on the leaderboard ranked by what they spent during one part of the week,
+// leaving out anyone who spent nothing in it
+fn window_leaderboard(
+    guild_id: u64,
+    window: user::SpendWindow,
+) -> Result<Vec<(String, i64)>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut leaderboard = Vec::new();
+    for (username, _) in user::get_leaderboard(guild_id, usize::MAX)? {
+        let spent = user::spend_in_window(guild_id, &username, window)?;
+        if spent > 0 {
+            leaderboard.push((username, spent));
         }
     }
 
-    async fn ready(&self, _: Context, ready: Ready) {
-        println!("{} is connected!", ready.user.name);
+    leaderboard.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(leaderboard)
+}
+
+// The top ten spenders as posted in the leaderboard thread
+fn thread_board_text(guild_id: u64) -> String {
+    match user::get_leaderboard(guild_id, 10) {
+        Ok(leaderboard) if leaderboard.is_empty() => {
+            "Nobody is being tracked yet! Try the !adduser command.".to_string()
+        }
+        Ok(leaderboard) => {
+            let lines: Vec<String> = leaderboard
+                .iter()
+                .enumerate()
+                .map(|(i, (username, total))| {
+                    roast::board_line(i + 1, &user::get_display_name(guild_id, username), *total)
+                })
+                .collect();
+            format!("**🏆 Leaderboard:**\n{}", lines.join("\n"))
+        }
+        Err(e) => format!("Error: {}", e),
     }
 }
 
-#[tokio::main]
-async fn main() {
-    //Load environment variables
-    dotenv().ok();
+// Users shown on each page of the paged !leaderboard
+const BOARD_PAGE_SIZE: usize = 10;
+// A paged leaderboard stops turning after this long without a reaction, in seconds
+const BOARD_PAGE_EXPIRY: i64 = 10 * 60;
+const PREVIOUS_PAGE: &str = "◀️";
+const NEXT_PAGE: &str = "▶️";
 
-    println!("🚀 Starting ShameBot...");
+// Where a paged leaderboard message is up to and who asked for it
+struct BoardPages {
+    page: usize, // from 0
+    invoker: u64,
+    last_used: i64, // Unix seconds
+}
 
-    // Load token environment variable
-    let token = env::var("DISCORD_TOKEN").expect("No token was found in the environment");
+// Paged leaderboard messages by message id
+static BOARD_PAGES: Mutex<Option<HashMap<u64, BoardPages>>> = Mutex::new(None);
 
-    // Set Intents for the bot
-    let intents = GatewayIntents::all();
+// How many pages a board of this many users needs. An empty board still has one
+fn page_count(users: usize) -> usize {
+    users.div_ceil(BOARD_PAGE_SIZE).max(1)
+}
 
-    //Create instance of the client, logging in the bot
-    let mut client = Client::builder(&token, intents)
-        .event_handler(Handler)
-        .await
-        .expect("There was an issue creating the client. Check bot setup");
+// One page (from 0) of the leaderboard, ranked from the top of the whole board
+fn leaderboard_page(guild_id: u64, leaderboard: &[(String, i64)], page: usize) -> String {
+    if leaderboard.is_empty() {
+        return "Nobody is being tracked yet! Try the !adduser command.".to_string();
+    }
 
-    // Listen for commands after client is started and bot is logged in
-    if let Err(error) = client.start().await {
-        println!("Client error: {error:?}");
+    let lines: Vec<String> = leaderboard
+        .iter()
+        .enumerate()
+        .skip(page * BOARD_PAGE_SIZE)
+        .take(BOARD_PAGE_SIZE)
+        .map(|(i, (username, total))| {
+            roast::board_line(i + 1, &user::get_display_name(guild_id, username), *total)
+        })
+        .collect();
+
+    format!(
+        "**🏆 Leaderboard** (page {} of {})\n{}",
+        page + 1,
+        page_count(leaderboard.len()),
+        lines.join("\n")
+    )
+}
+
+// Start tracking a freshly posted paged leaderboard, forgetting any that have expired
+fn track_board_pages(message_id: u64, invoker: u64, now: i64) {
+    let mut tracked = BOARD_PAGES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let tracked = tracked.get_or_insert_with(HashMap::new);
+
+    tracked.retain(|_, board| now - board.last_used < BOARD_PAGE_EXPIRY);
+    tracked.insert(
+        message_id,
+        BoardPages {
+            page: 0,
+            invoker,
+            last_used: now,
+        },
+    );
+}
+
+// The page a reaction turns a tracked leaderboard to. Nothing happens for untracked or
+// expired messages, for anyone but the invoker unless anyone may page, or past the
+// first or last page
+fn turn_board_page(
+    message_id: u64,
+    user_id: u64,
+    forward: bool,
+    pages: usize,
+    now: i64,
+    anyone: bool,
+) -> Option<usize> {
+    let mut tracked = BOARD_PAGES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let tracked = tracked.get_or_insert_with(HashMap::new);
+
+    let board = tracked.get_mut(&message_id)?;
+    if now - board.last_used >= BOARD_PAGE_EXPIRY {
+        tracked.remove(&message_id);
+        return None;
+    }
+    if !anyone && user_id != board.invoker {
+        return None;
+    }
+
+    let page = if forward {
+        board.page + 1
+    } else {
+        board.page.checked_sub(1)?
+    };
+    if page >= pages {
+        return None;
+    }
+
+    board.page = page;
+    board.last_used = now;
+    Some(page)
+}
+
+// Bring a server's leaderboard thread up to date, at most once per BOARD_EDIT_INTERVAL.
+// If its message has been deleted the thread stops being tracked
+async fn refresh_board_thread(http: &Http, guild_id: u64) {
+    let board = match config::claim_board_edit(guild_id, user::now()) {
+        Ok(Some(board)) => board,
+        Ok(None) => return,
+        Err(e) => {
+            error!("Error reading the leaderboard thread: {e}");
+            return;
+        }
+    };
+
+    let thread = ChannelId::new(board.thread_id);
+    let edit = EditMessage::new().content(thread_board_text(guild_id));
+    if let Err(error) = thread.edit_message(http, board.message_id, edit).await {
+        warn!("Error updating the leaderboard thread: {error:?}");
+        config::clear_board_thread(guild_id).ok();
+    }
+}
+
+// The plain leaderboard as an embed, medals for the top three
+fn leaderboard_embed(guild_id: u64, title: &str, leaderboard: &[(String, i64)]) -> CreateEmbed {
+    let lines: Vec<String> = leaderboard
+        .iter()
+        .enumerate()
+        .map(|(i, (username, total))| {
+            roast::podium_line(i + 1, &user::get_display_name(guild_id, username), *total)
+        })
+        .collect();
+
+    CreateEmbed::new()
+        .title(title)
+        .description(lines.join("\n"))
+        .color(0xffd700) // Gold color
+}
+
+// A week, in seconds
+const HALL_OF_SHAME_INTERVAL: u64 = 7 * 24 * 60 * 60;
+
+// Optional hall of shame: post the top 10 spenders in SHAMEBOT_SHAME_CHANNEL_ID every
+// SHAMEBOT_SHAME_INTERVAL_SECS (default one week), with nobody having to ask
+fn spawn_hall_of_shame_scheduler(http: Arc<Http>) {
+    let Some(channel_id) = channel_from_env("SHAMEBOT_SHAME_CHANNEL_ID") else {
+        return;
+    };
+    let period = interval_from_env("SHAMEBOT_SHAME_INTERVAL_SECS", HALL_OF_SHAME_INTERVAL);
+
+    tokio::spawn(async move {
+        // The hall covers the channel's own server
+        let Some(guild_id) = channel_guild(&http, channel_id).await else {
+            warn!(
+                "SHAMEBOT_SHAME_CHANNEL_ID isn't a server channel the bot can see, no hall of shame"
+            );
+            return;
+        };
+
+        let mut interval = tokio::time::interval(period);
+        // The first tick fires immediately; wait a full period before the first post
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+
+            match user::get_leaderboard(guild_id, 10) {
+                Ok(leaderboard) if leaderboard.is_empty() => {}
+                Ok(leaderboard) => {
+                    let embed = leaderboard_embed(guild_id, "🏛️ Hall of Shame", &leaderboard);
+                    let builder = CreateMessage::new().embed(embed);
+                    if let Err(error) = channel_id.send_message(&http, builder).await {
+                        warn!("Error posting hall of shame: {error:?}");
+                    }
+                }
+                Err(e) => error!("Error building hall of shame: {}", e),
+            }
+        }
+    });
+}
+
+// Read an on/off switch from the environment ("true"/"1"/"yes"/"on" mean on)
+fn flag_from_env(name: &str) -> bool {
+    env::var(name).is_ok_and(|value| {
+        matches!(
+            value.trim().to_lowercase().as_str(),
+            "true" | "1" | "yes" | "on"
+        )
+    })
+}
+
+// How long to leave the bot's own replies up, given SHAMEBOT_DELETE_REPLIES_AFTER_SECS.
+// Unset, zero or not a number of seconds means they stay up
+fn reply_delete_delay(setting: Option<&str>) -> Option<Duration> {
+    setting
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .filter(|&secs| secs > 0)
+        .map(Duration::from_secs)
+}
+
+// Replies are only tidied away if they're plain text. Anything that pings people
+// or carries an image or file (callouts, shame GIFs, backups) stays up
+fn is_plain_reply(msg: &Message) -> bool {
+    !msg.mention_everyone
+        && msg.mentions.is_empty()
+        && msg.mention_roles.is_empty()
+        && msg.embeds.is_empty()
+        && msg.attachments.is_empty()
+}
+
+// Deleting someone else's message needs Manage Messages in that channel
+fn bot_can_manage_messages(ctx: &Context, msg: &Message) -> bool {
+    let Some(guild_id) = msg.guild_id else {
+        return false;
+    };
+    let Some(guild) = ctx.cache.guild(guild_id) else {
+        return false;
+    };
+    let bot_id = ctx.cache.current_user().id;
+
+    match (
+        guild.channels.get(&msg.channel_id),
+        guild.members.get(&bot_id),
+    ) {
+        (Some(channel), Some(member)) => {
+            guild.user_permissions_in(channel, member).manage_messages()
+        }
+        _ => false,
+    }
+}
+
+// Delete a message once the delay is up, without holding up the caller
+fn delete_after(http: Arc<Http>, channel_id: ChannelId, message_id: MessageId, delay: Duration) {
+    tokio::spawn(async move {
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+
+        if let Err(error) = channel_id.delete_message(&http, message_id).await {
+            warn!("Error deleting message {message_id}: {error:?}");
+        }
+    });
+}
+
+// Whether the data changed since the last posted backup, remembering it if so
+fn backup_needed(contents: &str, last_hash: &mut Option<u64>) -> bool {
+    let mut hasher = DefaultHasher::new();
+    contents.hash(&mut hasher);
+    let hash = hasher.finish();
+
+    if *last_hash == Some(hash) {
+        return false;
+    }
+
+    *last_hash = Some(hash);
+    true
+}
+
+// Optional backups for operators without server access: post the data as JSON to
+// SHAMEBOT_BACKUP_CHANNEL_ID every SHAMEBOT_BACKUP_INTERVAL_SECS (default one day),
+// skipping runs where nothing changed
+fn spawn_backup_scheduler(http: Arc<Http>) {
+    let Some(channel_id) = channel_from_env("SHAMEBOT_BACKUP_CHANNEL_ID") else {
+        return;
+    };
+    let period = interval_from_env("SHAMEBOT_BACKUP_INTERVAL_SECS", 24 * 60 * 60);
+
+    tokio::spawn(async move {
+        // Backups cover the data of the channel's own server
+        let Some(guild_id) = channel_guild(&http, channel_id).await else {
+            warn!("SHAMEBOT_BACKUP_CHANNEL_ID isn't a server channel the bot can see, no backups");
+            return;
+        };
+
+        let mut interval = tokio::time::interval(period);
+        let mut last_hash = None;
+
+        loop {
+            interval.tick().await;
+
+            let contents = match user::read_data_file(guild_id) {
+                Ok(contents) => contents,
+                Err(e) => {
+                    error!("Error reading data for backup: {}", e);
+                    continue;
+                }
+            };
+
+            if !backup_needed(&contents, &mut last_hash) {
+                continue;
+            }
+
+            let builder = CreateMessage::new()
+                .content("🗄️ ShameBot data backup")
+                .add_file(CreateAttachment::bytes(contents, "users.json"));
+
+            if let Err(error) = channel_id.send_message(&http, builder).await {
+                warn!("Error posting backup: {error:?}");
+                // Try again next time rather than assuming this one landed
+                last_hash = None;
+            }
+        }
+    });
+}
+
+// Slash command option that names a user
+fn user_option() -> CreateCommandOption {
+    CreateCommandOption::new(CommandOptionType::String, "user", "The user's name").required(true)
+}
+
+// Slash command option that names a game
+fn game_option() -> CreateCommandOption {
+    CreateCommandOption::new(CommandOptionType::String, "game", "The game's name").required(true)
+}
+
+// Slash command option that names one of the user's existing games, with suggestions
+fn existing_game_option() -> CreateCommandOption {
+    game_option().set_autocomplete(true)
+}
+
+// Slash command option for an amount of money, parsed like prefix command amounts
+fn amount_option(name: &str, description: &str) -> CreateCommandOption {
+    CreateCommandOption::new(CommandOptionType::String, name, description).required(true)
+}
+
+// Slash versions of the core commands, registered when the bot connects. Prefix
+// commands keep working alongside them
+fn slash_commands() -> Vec<CreateCommand> {
+    vec![
+        CreateCommand::new("adduser")
+            .description("Create a new user with their first game")
+            .add_option(user_option())
+            .add_option(game_option())
+            .add_option(amount_option("total", "What they've spent so far")),
+        CreateCommand::new("addgame")
+            .description("Add a new game to an existing user")
+            .add_option(user_option())
+            .add_option(game_option())
+            .add_option(amount_option("total", "What they've spent so far")),
+        CreateCommand::new("updatetotal")
+            .description("Add money to a game total")
+            .add_option(user_option())
+            .add_option(existing_game_option())
+            .add_option(amount_option("amount", "How much more they spent"))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "confirm",
+                "Confirm a large amount you were asked about",
+            )),
+        CreateCommand::new("usergames")
+            .description("Show all games for a user")
+            .add_option(user_option()),
+        CreateCommand::new("gametotal")
+            .description("Show a user's total for one game")
+            .add_option(user_option())
+            .add_option(existing_game_option()),
+        CreateCommand::new("usertotal")
+            .description("Show a user's total across all games")
+            .add_option(user_option()),
+        CreateCommand::new("removegame")
+            .description("Remove a game from a user")
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .add_option(user_option())
+            .add_option(existing_game_option()),
+        CreateCommand::new("leaderboard")
+            .description("Show the biggest spenders across all games")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "count", "How many to show")
+                    .min_int_value(1)
+                    .max_int_value(25),
+            ),
+    ]
+}
+
+// A string option's value, or empty if it wasn't given
+fn string_option<'a>(options: &[ResolvedOption<'a>], name: &str) -> &'a str {
+    options
+        .iter()
+        .find_map(|option| match option.value {
+            ResolvedValue::String(value) if option.name == name => Some(value),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+// An integer option's value, if it was given
+fn integer_option(options: &[ResolvedOption], name: &str) -> Option<i64> {
+    options.iter().find_map(|option| match option.value {
+        ResolvedValue::Integer(value) if option.name == name => Some(value),
+        _ => None,
+    })
+}
+
+// A boolean option's value, or false if it wasn't given
+fn boolean_option(options: &[ResolvedOption], name: &str) -> bool {
+    options
+        .iter()
+        .any(|option| option.name == name && matches!(option.value, ResolvedValue::Boolean(true)))
+}
+
+// Answer a slash command with a message
+async fn respond(
+    ctx: &Context,
+    command: &CommandInteraction,
+    message: CreateInteractionResponseMessage,
+) {
+    // Interaction responses don't pick up the client's default, so no pings here either
+    let message = message.allowed_mentions(CreateAllowedMentions::new());
+    command
+        .create_response(&ctx.http, CreateInteractionResponse::Message(message))
+        .await
+        .log_send_error();
+}
+
+// Discord shows at most this many autocomplete choices, each at most this long
+const AUTOCOMPLETE_CHOICE_LIMIT: usize = 25;
+const AUTOCOMPLETE_CHOICE_LENGTH: usize = 100;
+
+// The suggestions Discord will accept: too-long names are dropped and the rest capped
+fn autocomplete_choices(games: Vec<String>) -> Vec<String> {
+    games
+        .into_iter()
+        .filter(|game| game.chars().count() <= AUTOCOMPLETE_CHOICE_LENGTH)
+        .take(AUTOCOMPLETE_CHOICE_LIMIT)
+        .collect()
+}
+
+// Suggest the chosen user's games while a game name is being typed
+async fn handle_autocomplete(ctx: Context, command: CommandInteraction) {
+    let options = command.data.options();
+    let typed = options.iter().find_map(|option| match option.value {
+        ResolvedValue::Autocomplete { value, .. } if option.name == "game" => Some(value),
+        _ => None,
+    });
+
+    // No user picked yet, or one that doesn't exist, just means no suggestions
+    let games = match (command.guild_id, typed) {
+        (Some(guild_id), Some(typed)) => {
+            user::suggest_games(guild_id.get(), string_option(&options, "user"), typed)
+                .unwrap_or_default()
+        }
+        _ => Vec::new(),
+    };
+
+    let response = autocomplete_choices(games)
+        .into_iter()
+        .fold(CreateAutocompleteResponse::new(), |response, game| {
+            response.add_string_choice(game.clone(), game)
+        });
+
+    command
+        .create_response(&ctx.http, CreateInteractionResponse::Autocomplete(response))
+        .await
+        .log_send_error();
+}
+
+// Runs a single slash command, replying the same way its prefix version does
+async fn handle_slash_command(ctx: Context, command: CommandInteraction) {
+    let Some(guild_id) = command.guild_id.map(|id| id.get()) else {
+        let message = CreateInteractionResponseMessage::new()
+            .content("Commands only work in servers. Try me in one of yours!");
+        respond(&ctx, &command, message).await;
+        return;
+    };
+
+    let options = command.data.options();
+    let username = string_option(&options, "user");
+    let shown = user::get_display_name(guild_id, username);
+    let game = &game_name(guild_id, string_option(&options, "game"));
+
+    let reply = match command.data.name.as_str() {
+        "adduser" => {
+            match user::add_user(guild_id, username, game, string_option(&options, "total")) {
+                Ok(total) => format!(
+                    "Added user {} with game '{}' and total {}",
+                    username,
+                    game,
+                    user::format_money(total)
+                ),
+                Err(e @ user::UserError::UserAlreadyExists(_)) => {
+                    format!("Error: {}! Use /addgame to add more games", e)
+                }
+                Err(e) => format!("Error: {}", e),
+            }
+        }
+        "addgame" => {
+            match user::add_game(guild_id, username, game, string_option(&options, "total")) {
+                Ok(total) => format!(
+                    "Added game '{}' with total {} to user {}",
+                    game,
+                    user::format_money(total),
+                    shown
+                ),
+                Err(e @ user::UserError::UserNotFound(_)) => {
+                    format!("Error: {}! Use /adduser first", e)
+                }
+                Err(e) => format!("Error: {}", e),
+            }
+        }
+        "updatetotal" => {
+            let amount = string_option(&options, "amount");
+
+            // Large amounts need running again with `confirm`, like !updatetotal
+            if let Some(cents) = user::parse_money(amount)
+                && let Some(limit) = user::needs_confirmation(cents)
+            {
+                let author_id = command.user.id.get();
+                let action = large_update_action(username, game, cents);
+
+                let prompt = if !boolean_option(&options, "confirm") {
+                    request_confirmation(guild_id, author_id, &action);
+                    Some(format!(
+                        "⚠️ That's more than {} in one go. Run `/updatetotal` again with `confirm: True` within {} seconds if it's right",
+                        user::format_money(limit),
+                        CONFIRMATION_WINDOW.as_secs()
+                    ))
+                } else if !take_confirmation(guild_id, author_id, &action) {
+                    Some(format!(
+                        "Nothing to confirm. Run the update without `confirm` first, then confirm within {} seconds",
+                        CONFIRMATION_WINDOW.as_secs()
+                    ))
+                } else {
+                    None
+                };
+
+                if let Some(prompt) = prompt {
+                    let message = CreateInteractionResponseMessage::new()
+                        .content(prompt)
+                        .ephemeral(true);
+                    respond(&ctx, &command, message).await;
+                    return;
+                }
+            }
+
+            match user::update_total(guild_id, username, game, amount, false) {
+                Ok((old_total, added, new_total, crossed)) => {
+                    let message = CreateInteractionResponseMessage::new().content(format!(
+                        "{}'s total for '{}': added {}, now {}{}",
+                        shown,
+                        game,
+                        user::format_money(added),
+                        user::format_money(new_total),
+                        goal_exceeded_note(guild_id, username, game, old_total, new_total)
+                    ));
+                    respond(&ctx, &command, message).await;
+
+                    let update = (game.to_string(), added, new_total, crossed);
+                    send_spending_callouts(&ctx, command.channel_id, guild_id, username, &[update])
+                        .await;
+                    return;
+                }
+                Err(e) => format!("Error: {}", e),
+            }
+        }
+        "usergames" => match user::get_user_games(guild_id, username) {
+            Ok(games) if games.is_empty() => format!("User {} has no games", shown),
+            Ok(games) => {
+                let games_list: Vec<String> = games
+                    .iter()
+                    .map(|(game, total)| format!("• {}: {}", game, user::format_money(*total)))
+                    .collect();
+                format!("**{}'s Games:**\n{}", shown, games_list.join("\n"))
+            }
+            Err(e) => format!("Error: {}", e),
+        },
+        "gametotal" => match user::get_game_record(guild_id, username, game) {
+            Ok(record) => format!(
+                "{}'s total for '{}': {}{}",
+                shown,
+                game,
+                total_against_goal(guild_id, username, game, record.total),
+                last_updated_note(&record)
+            ),
+            Err(e) => format!("Error: {}", e),
+        },
+        "usertotal" => match user::get_user_total_all_games(guild_id, username) {
+            Ok(total) => format!(
+                "{}'s total across all available games: {}",
+                shown,
+                user::format_money(total)
+            ),
+            Err(e) => format!("Error: {}", e),
+        },
+        "removegame" => {
+            // Discord hides this from non-admins, but servers can override that
+            let allowed = command
+                .member
+                .as_ref()
+                .and_then(|member| member.permissions)
+                .is_some_and(grants_admin);
+
+            if !allowed {
+                "You don't have permission to do that.".to_string()
+            } else {
+                match user::remove_game(guild_id, username, game) {
+                    Ok(_) => format!("Removed game '{}' from user {}", game, shown),
+                    Err(e) => format!("Error: {}", e),
+                }
+            }
+        }
+        "leaderboard" => {
+            let limit = integer_option(&options, "count").map_or(10, |count| count.max(1) as usize);
+            match user::get_leaderboard(guild_id, limit) {
+                Ok(leaderboard) if leaderboard.is_empty() => {
+                    "Nobody is being tracked yet! Try the /adduser command.".to_string()
+                }
+                Ok(leaderboard) => {
+                    let embed = leaderboard_embed(guild_id, "🏆 Leaderboard", &leaderboard);
+                    respond(
+                        &ctx,
+                        &command,
+                        CreateInteractionResponseMessage::new().embed(embed),
+                    )
+                    .await;
+                    return;
+                }
+                Err(e) => format!("Error: {}", e),
+            }
+        }
+        _ => return,
+    };
+
+    respond(
+        &ctx,
+        &command,
+        CreateInteractionResponseMessage::new().content(reply),
+    )
+    .await;
+}
+
+#[async_trait]
+impl EventHandler for Handler {
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        match interaction {
+            Interaction::Command(command) => {
+                let span = tracing::info_span!(
+                    "command",
+                    name = %format!("/{}", command.data.name),
+                    channel = %command.channel_id
+                );
+                handle_slash_command(ctx, command).instrument(span).await;
+            }
+            Interaction::Autocomplete(command) => handle_autocomplete(ctx, command).await,
+            _ => {}
+        }
+    }
+
+    async fn message(&self, ctx: Context, msg: Message) {
+        // The bot sees its own messages too; they only matter for tidying up
+        if msg.author.id == ctx.cache.current_user().id {
+            let setting = env::var("SHAMEBOT_DELETE_REPLIES_AFTER_SECS").ok();
+            if let Some(delay) = reply_delete_delay(setting.as_deref())
+                && is_plain_reply(&msg)
+            {
+                delete_after(ctx.http.clone(), msg.channel_id, msg.id, delay);
+            }
+            return;
+        }
+
+        let delete_command = msg.content.starts_with('!')
+            && flag_from_env("SHAMEBOT_DELETE_COMMANDS")
+            && bot_can_manage_messages(&ctx, &msg);
+
+        // Run the command on its own task so a panic inside one handler is
+        // contained there instead of taking the event handler down with it
+        let channel_id = msg.channel_id;
+        let message_id = msg.id;
+        let guild_id = msg.guild_id.map(|id| id.get());
+        let http = ctx.http.clone();
+        let is_command = msg.content.starts_with('!');
+        let command = msg
+            .content
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let span = tracing::info_span!("command", name = %command, channel = %channel_id);
+
+        if !run_supervised(&command, handle_command(ctx, msg).instrument(span)).await {
+            channel_id
+                .say(&http, "Something went wrong while running that command")
+                .await
+                .log_send_error();
+        }
+
+        // Commands may have changed the totals, so keep the thread's board current
+        if is_command && let Some(guild_id) = guild_id {
+            refresh_board_thread(&http, guild_id).await;
+        }
+
+        if delete_command {
+            delete_after(http, channel_id, message_id, Duration::ZERO);
+        }
+    }
+
+    // ◀️/▶️ on a paged !leaderboard turns its page
+    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        let forward = match &reaction.emoji {
+            ReactionType::Unicode(emoji) if emoji == NEXT_PAGE => true,
+            ReactionType::Unicode(emoji) if emoji == PREVIOUS_PAGE => false,
+            _ => return,
+        };
+        let Some(user_id) = reaction.user_id else {
+            return;
+        };
+        if user_id == ctx.cache.current_user().id {
+            return;
+        }
+        let Some(guild_id) = reaction.guild_id.map(|id| id.get()) else {
+            return;
+        };
+
+        let leaderboard = match user::get_leaderboard(guild_id, usize::MAX) {
+            Ok(leaderboard) => leaderboard,
+            Err(e) => {
+                error!("Error paging the leaderboard: {e}");
+                return;
+            }
+        };
+        let Some(page) = turn_board_page(
+            reaction.message_id.get(),
+            user_id.get(),
+            forward,
+            page_count(leaderboard.len()),
+            user::now(),
+            flag_from_env("SHAMEBOT_PAGE_ANYONE"),
+        ) else {
+            return;
+        };
+
+        let edit = EditMessage::new().content(leaderboard_page(guild_id, &leaderboard, page));
+        if let Err(error) = reaction
+            .channel_id
+            .edit_message(&ctx.http, reaction.message_id, edit)
+            .await
+        {
+            warn!("Error turning the leaderboard page: {error:?}");
+        }
+        // Take the reaction back off so the same arrow can be pressed again
+        reaction.delete(&ctx.http).await.log_send_error();
+    }
+
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        info!("{} is connected!", ready.user.name);
+
+        if let Err(error) = Command::set_global_commands(&ctx.http, slash_commands()).await {
+            error!("Couldn't register slash commands: {error:?}");
+        }
+    }
+}
+
+// Run a command on its own task, so a panic stays inside it. Returns false if it panicked
+async fn run_supervised(
+    command: &str,
+    handler: impl std::future::Future<Output = ()> + Send + 'static,
+) -> bool {
+    match tokio::spawn(handler).await {
+        Err(error) if error.is_panic() => {
+            error!("Command handler for {command} panicked: {error:?}");
+            false
+        }
+        _ => true,
+    }
+}
+
+// Runs a single chat command. Called from a supervised task in the message handler
+async fn handle_command(ctx: Context, msg: Message) {
+    let Some(name) = command_name(&msg.content) else {
+        if let Some(mes) = unknown_command_reply(&msg.content) {
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        return;
+    };
+
+    // Help, !version and !consent work anywhere, !consent mostly in DMs
+    match name {
+        "!help" | "!commands" => return help_command(&ctx, &msg).await,
+        "!quickhelp" => return quick_help_command(&ctx, &msg).await,
+        "!consent" => return consent_command(&ctx, &msg).await,
+        "!version" => return version_command(&ctx, &msg).await,
+        _ => {}
+    }
+
+    if let Err(e) = split_command(&msg.content) {
+        msg.channel_id
+            .say(&ctx.http, format!("Error: {}", e))
+            .await
+            .log_send_error();
+        return;
+    }
+
+    // Every server keeps its own users, so everything past here needs one
+    let Some(guild_id) = msg.guild_id.map(|id| id.get()) else {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Commands only work in servers. Try me in one of yours!",
+            )
+            .await
+            .log_send_error();
+        return;
+    };
+
+    // Mentions stand in for whoever they point at, so swap them for user names first
+    let name = name.to_string();
+    let mut msg = msg;
+    let mentioned = resolve_mentions(guild_id, &mut msg);
+
+    match name.as_str() {
+        "!adduser" => add_user_command(&ctx, &msg, guild_id).await,
+        "!addgame" => add_game_command(&ctx, &msg, guild_id).await,
+        "!updatetotal" => update_total_command(&ctx, &msg, guild_id).await,
+        "!preview" => preview_command(&ctx, &msg, guild_id).await,
+        "!addmultiple" => add_multiple_command(&ctx, &msg, guild_id).await,
+        "!removegame" => remove_game_command(&ctx, &msg, guild_id).await,
+        "!movegame" => move_game_command(&ctx, &msg, guild_id).await,
+        "!undo" | "!undouser" => undo_command(&ctx, &msg, guild_id).await,
+        "!deleteuser" => delete_user_command(&ctx, &msg, guild_id).await,
+        "!renameuser" => rename_user_command(&ctx, &msg, guild_id).await,
+        "!merge" => merge_command(&ctx, &msg, guild_id).await,
+        "!usergames" => user_games_command(&ctx, &msg, guild_id).await,
+        "!getusers" => get_users_command(&ctx, &msg, guild_id).await,
+        "!gametotal" => game_total_command(&ctx, &msg, guild_id).await,
+        "!search" => search_command(&ctx, &msg, guild_id).await,
+        "!stats" => stats_command(&ctx, &msg, guild_id).await,
+        "!refund" => refund_command(&ctx, &msg, guild_id).await,
+        "!history" => history_command(&ctx, &msg, guild_id).await,
+        "!usertotal" => user_total_command(&ctx, &msg, guild_id).await,
+        "!mystats" | "!whoami" => my_stats_command(&ctx, &msg, guild_id).await,
+        "!export" => export_command(&ctx, &msg, guild_id).await,
+        "!import" => import_command(&ctx, &msg, guild_id).await,
+        "!renamegame" => rename_game_command(&ctx, &msg, guild_id).await,
+        "!globalalias" => global_alias_command(&ctx, &msg, guild_id).await,
+        "!globalunalias" => global_unalias_command(&ctx, &msg, guild_id).await,
+        "!linkaccount" => link_account_command(&ctx, &msg, guild_id).await,
+        "!unlink" => unlink_command(&ctx, &msg, guild_id).await,
+        "!removegames" => remove_games_command(&ctx, &msg, guild_id).await,
+        "!whale" => whale_command(&ctx, &msg, guild_id).await,
+        "!debug" => debug_command(&ctx, &msg, guild_id).await,
+        "!cheapskates" => cheapskates_command(&ctx, &msg, guild_id).await,
+        "!topgame" => top_game_command(&ctx, &msg, guild_id).await,
+        "!leaderboard" => leaderboard_command(&ctx, &msg, guild_id).await,
+        "!snapshot" => snapshot_command(&ctx, &msg, guild_id).await,
+        "!leaderboarddiff" => leaderboard_diff_command(&ctx, &msg, guild_id).await,
+        "!reload" => reload_command(&ctx, &msg, guild_id).await,
+        "!top" => top_command(&ctx, &msg, guild_id).await,
+        "!rank" => rank_command(&ctx, &msg, guild_id).await,
+        "!compare" => compare_command(&ctx, &msg, guild_id).await,
+        "!setweight" => set_weight_command(&ctx, &msg, guild_id).await,
+        "!nopoke" | "!allowpoke" => poke_opt_out_command(&ctx, &msg, guild_id).await,
+        "!archive" | "!unarchive" => archive_command(&ctx, &msg, guild_id).await,
+        "!onthebrink" => on_the_brink_command(&ctx, &msg, guild_id).await,
+        "!inactive" => inactive_command(&ctx, &msg, guild_id).await,
+        "!setcap" => set_cap_command(&ctx, &msg, guild_id).await,
+        "!setgoal" => set_goal_command(&ctx, &msg, guild_id).await,
+        "!tag" | "!untag" => tag_command(&ctx, &msg, guild_id).await,
+        "!categorytotal" => category_total_command(&ctx, &msg, guild_id).await,
+        "!setdisplayname" => set_display_name_command(&ctx, &msg, guild_id).await,
+        "!setshamerole" => set_shame_role_command(&ctx, &msg, guild_id).await,
+        "!setshamechannel" => set_shame_channel_command(&ctx, &msg, guild_id).await,
+        "!requireconsent" => require_consent_command(&ctx, &msg, guild_id).await,
+        "!setthreshold" => set_threshold_command(&ctx, &msg, guild_id).await,
+        "!reset" => reset_command(&ctx, &msg, guild_id).await,
+        "!backup" => backup_command(&ctx, &msg, guild_id).await,
+        "!restore" => restore_command(&ctx, &msg, guild_id).await,
+        _ => {}
+    }
+
+    // Someone just added by mention gets their account tied to them straight away
+    for (username, discord_id) in mentioned {
+        if let Err(e) = user::link_discord_id(guild_id, &username, discord_id) {
+            warn!("Couldn't link Discord account {discord_id} to '{username}': {e}");
+        }
+    }
+}
+
+// The user id in a mention argument like `<@123>` or `<@!123>`
+fn parse_mention(arg: &str) -> Option<u64> {
+    let id = arg.strip_prefix("<@")?.strip_suffix('>')?;
+    let id = id.strip_prefix('!').unwrap_or(id);
+    id.parse().ok()
+}
+
+// Replace user mentions in a command with the names of the users they're linked to.
+// A mention of an account nobody is linked to yet becomes its Discord username, so
+// plain-name users of that name are found. Those are returned to be linked afterwards
+fn resolve_mentions(guild_id: u64, msg: &mut Message) -> Vec<(String, u64)> {
+    let mut unlinked = Vec::new();
+    let mut content = msg.content.clone();
+
+    for arg in msg.content.split_whitespace() {
+        let Some(discord_id) = parse_mention(arg) else {
+            continue;
+        };
+
+        let username = match user::username_for_discord_id(guild_id, discord_id) {
+            Ok(Some(username)) => username,
+            _ => match msg.mentions.iter().find(|user| user.id.get() == discord_id) {
+                Some(mentioned) => {
+                    unlinked.push((mentioned.name.clone(), discord_id));
+                    mentioned.name.clone()
+                }
+                None => continue,
+            },
+        };
+        content = content.replacen(arg, &username, 1);
+    }
+
+    msg.content = content;
+    unlinked
+}
+
+// The tracked user a Discord account belongs to. Linked accounts are found directly;
+// otherwise their display name, then username, is tried and linked if it's tracked
+fn author_username(guild_id: u64, author: &serenity::model::user::User) -> Option<String> {
+    if let Ok(Some(username)) = user::username_for_discord_id(guild_id, author.id.get()) {
+        return Some(username);
+    }
+
+    let candidates = author
+        .global_name
+        .iter()
+        .chain(std::iter::once(&author.name));
+    for candidate in candidates {
+        if user::get_user_games(guild_id, candidate).is_ok() {
+            if let Err(e) = user::link_discord_id(guild_id, candidate, author.id.get()) {
+                warn!(
+                    "Couldn't link Discord account {} to '{}': {}",
+                    author.id, candidate, e
+                );
+            }
+            return Some(candidate.clone());
+        }
+    }
+    None
+}
+
+// Discord caps each embed field at this many characters
+const EMBED_FIELD_LIMIT: usize = 1024;
+
+// Commands shown in the examples at the bottom of !help
+const HELP_EXAMPLES: &[&str] = &[
+    "!adduser",
+    "!addgame",
+    "!updatetotal",
+    "!usergames",
+    "!gametotal",
+    "!usertotal",
+    "!removegame",
+    "!deleteuser",
+];
+
+// One command's line in the full !help list
+fn help_line(command: &CommandInfo) -> String {
+    let mut line = format!("• `{}` - {}", command.usage, command.description);
+    for alias in command.aliases {
+        line.push_str(&format!(" (or `{}`)", alias));
+    }
+    if command.admin {
+        line.push_str(" (admin)");
+    }
+    line
+}
+
+// The command list as (title, text) embed fields, one section at a time. A section
+// too long for one field carries on in a "(cont.)" field
+fn help_fields() -> Vec<(String, String)> {
+    let mut fields = Vec::new();
+    for section in HelpSection::ALL {
+        let lines: Vec<String> = COMMANDS
+            .iter()
+            .filter(|command| command.section == section)
+            .map(help_line)
+            .collect();
+
+        for (i, chunk) in split_lines(&lines.join("\n"), EMBED_FIELD_LIMIT)
+            .into_iter()
+            .enumerate()
+        {
+            let title = if i == 0 {
+                section.title().to_string()
+            } else {
+                format!("{} (cont.)", section.title())
+            };
+            fields.push((title, chunk));
+        }
+    }
+    fields
+}
+
+// Help message. `!help <command>` explains just that command
+async fn help_command(ctx: &Context, msg: &Message) {
+    let parts = parse_command_with_quotes(&msg.content);
+    if let Some(name) = parts.get(1) {
+        return command_help_command(ctx, msg, name).await;
+    }
+
+    let mut help_embed = CreateEmbed::new()
+        .title("🎮 ShameBot - Command List")
+        .description("Track your gaming totals across different games! When the totals get high, it puts you on blast for your spending! Use `!help <command>` for the details of one command.")
+        .color(0x00ff00); // Green color
+    let mut fallback_text = String::from("**🎮 ShameBot Commands:**");
+
+    for (title, text) in help_fields() {
+        fallback_text.push_str(&format!("\n\n**{}:**\n{}", title, text));
+        help_embed = help_embed.field(title, text, false);
+    }
+
+    let examples: Vec<&str> = HELP_EXAMPLES
+        .iter()
+        .filter_map(|name| find_command(name))
+        .map(|command| command.example)
+        .collect();
+    help_embed = help_embed
+        .field(
+            "💡 Command Examples",
+            format!("```\n{}```", examples.join("\n")),
+            false,
+        )
+        .field(
+            "⚠️ Important Notes",
+            "• Use quotes around game names with spaces (and `\\\"` for a quote inside one)\n• Game and user names aren't case-sensitive\n• Amounts must be valid numbers\n• User names cannot contain spaces\n• You can @mention someone instead of typing their user name\n• The core commands also work as slash commands, like `/updatetotal`",
+            false,
+        );
+
+    let builder = CreateMessage::new().embed(help_embed);
+
+    if let Err(error) = msg.channel_id.send_message(&ctx.http, builder).await {
+        warn!("Error sending help message: {error:?}");
+        // Fallback to simple text if embed fails
+        for chunk in split_lines(&fallback_text, DISCORD_MESSAGE_LIMIT) {
+            msg.channel_id.say(&ctx.http, chunk).await.log_send_error();
+        }
+    }
+}
+
+// !help updatetotal
+async fn command_help_command(ctx: &Context, msg: &Message, name: &str) {
+    let Some(command) = find_command(name) else {
+        let typed = format!("!{}", name.strip_prefix('!').unwrap_or(name));
+        let mes = match suggest_command(&typed, &command_names()) {
+            Some(suggestion) => format!(
+                "There's no command called `{}`. Did you mean `{}`? Try `!help` for the full list.",
+                typed, suggestion
+            ),
+            None => format!(
+                "There's no command called `{}`. Try `!help` for the full list.",
+                typed
+            ),
+        };
+        msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        return;
+    };
+
+    let mut description = command.description.to_string();
+    if command.admin {
+        description.push_str("\nOnly server managers can run it");
+    }
+    if !command.aliases.is_empty() {
+        description.push_str(&format!(
+            "\nAlso works as `{}`",
+            command.aliases.join("`, `")
+        ));
+    }
+
+    let mut embed = CreateEmbed::new()
+        .title(format!("`{}`", command.usage))
+        .description(description)
+        .color(0x00ff00); // Green color
+
+    if !command.arguments.is_empty() {
+        let arguments: Vec<String> = command
+            .arguments
+            .iter()
+            .map(|(argument, meaning)| format!("• `{}` - {}", argument, meaning))
+            .collect();
+        embed = embed.field("Arguments", arguments.join("\n"), false);
+    }
+    embed = embed.field("Example", format!("```\n{}```", command.example), false);
+
+    let builder = CreateMessage::new().embed(embed);
+    msg.channel_id
+        .send_message(&ctx.http, builder)
+        .await
+        .log_send_error();
+}
+
+// The build time build.rs recorded, in seconds since the epoch, as a date
+fn format_build_time(secs: &str) -> String {
+    secs.parse::<i64>()
+        .ok()
+        .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+        .map_or_else(
+            || "unknown".to_string(),
+            |time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        )
+}
+
+// !version - which build is running, from what build.rs recorded
+async fn version_command(ctx: &Context, msg: &Message) {
+    let built_at = format_build_time(env!("SHAMEBOT_BUILT_AT"));
+
+    let embed = CreateEmbed::new()
+        .title("🤖 ShameBot Version")
+        .field("Version", env!("CARGO_PKG_VERSION"), true)
+        .field("Commit", format!("`{}`", env!("SHAMEBOT_GIT_COMMIT")), true)
+        .field("Built", built_at, false)
+        .field("Compiler", env!("SHAMEBOT_RUSTC_VERSION"), false)
+        .color(0x5865f2); // Blurple
+
+    let builder = CreateMessage::new().embed(embed);
+    msg.channel_id
+        .send_message(&ctx.http, builder)
+        .await
+        .log_send_error();
+}
+
+async fn quick_help_command(ctx: &Context, msg: &Message) {
+    let quick_help = "**Quick Commands:** `!adduser`, `!addgame`, `!updatetotal`, `!getusers`, `!usergames`, `!deleteuser`, `!removegame` | Use `!help` for details";
+    msg.channel_id
+        .say(&ctx.http, quick_help)
+        .await
+        .log_send_error();
+}
+
+// !consent yes - answer a request to be tracked (works in DMs)
+async fn consent_command(ctx: &Context, msg: &Message) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    let accept = match parts.get(1).map(String::as_str) {
+        Some("yes") if parts.len() == 2 => true,
+        Some("no") if parts.len() == 2 => false,
+        _ => {
+            msg.channel_id
+                .say(&ctx.http, "Usage: !consent <yes|no>")
+                .await
+                .log_send_error();
+            return;
+        }
+    };
+
+    let mes = match user::answer_consent(msg.author.id.get(), accept, user::now()) {
+        Ok(user::ConsentAnswer::Accepted(request)) => format!(
+            "Thanks! You're now tracked as {} with '{}' at {}",
+            request.username,
+            request.game,
+            user::parse_money(&request.total).map_or(request.total.clone(), user::format_money)
+        ),
+        Ok(user::ConsentAnswer::Declined(_)) => {
+            "No problem, nothing about you was saved".to_string()
+        }
+        Ok(user::ConsentAnswer::Expired) => {
+            "That request expired, so nothing was saved. Ask to be added again".to_string()
+        }
+        Ok(user::ConsentAnswer::NotAsked) => "Nobody has asked to track you right now".to_string(),
+        Err(e) => format!("Error: {}", e),
+    };
+    msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+}
+
+// !adduser Q "Tekken 8" 200
+async fn add_user_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 4 {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Usage: !adduser <username> \"<game name>\" <total>",
+            )
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let game = &game_name(guild_id, &parts[2]);
+    let total = &parts[3];
+
+    if config::requires_consent(guild_id) {
+        ask_for_consent(ctx, msg, guild_id, game, total).await;
+        return;
+    }
+
+    match user::add_user(guild_id, username, game, total) {
+        Ok(total) => {
+            let mes = format!(
+                "Added user {} with game '{}' and total {}",
+                username,
+                game,
+                user::format_money(total)
+            );
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e @ user::UserError::UserAlreadyExists(_)) => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!("Error: {}! Use !addgame to add more games", e),
+                )
+                .await
+                .log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !addgame Q "Street Fighter 6" 150
+async fn add_game_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 4 {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Usage: !addgame <username> \"<game name>\" <starting_total>",
+            )
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+    let game = &game_name(guild_id, &parts[2]);
+    let total = &parts[3];
+
+    // Someone who isn't tracked yet gets asked first when consent is required
+    if config::requires_consent(guild_id) && user::get_user_games(guild_id, username).is_err() {
+        ask_for_consent(ctx, msg, guild_id, game, total).await;
+        return;
+    }
+
+    match user::add_game(guild_id, username, game, total) {
+        Ok(total) => {
+            let mes = format!(
+                "Added game '{}' with total {} to user {}",
+                game,
+                user::format_money(total),
+                shown
+            );
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e @ user::UserError::UserNotFound(_)) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}! Use !adduser first", e))
+                .await
+                .log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !updatetotal Q "Tekken 8" 50
+async fn update_total_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    // Admins can push past a spending cap with a trailing `override`, and a large
+    // update goes ahead once it's repeated with a trailing `confirm`
+    let flags = parts.get(4..).unwrap_or_default();
+    let override_cap = flags.iter().any(|flag| flag == "override");
+    let confirmed = flags.iter().any(|flag| flag == "confirm");
+    if parts.len() < 4 || flags.len() != usize::from(override_cap) + usize::from(confirmed) {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Usage: !updatetotal <username> \"<game name>\" <additional_amount> [override] [confirm]",
+            )
+            .await
+            .log_send_error();
+        return;
+    }
+
+    if override_cap && !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+    let game = &game_name(guild_id, &parts[2]);
+    let total = &parts[3];
+
+    if let Some(amount) = user::parse_money(total)
+        && let Some(limit) = user::needs_confirmation(amount)
+    {
+        let author_id = msg.author.id.get();
+        let action = large_update_action(username, game, amount);
+
+        if !confirmed {
+            request_confirmation(guild_id, author_id, &action);
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!(
+                        "⚠️ That's more than {} in one go. Run `!updatetotal {} \"{}\" {}{} confirm` within {} seconds if it's right",
+                        user::format_money(limit),
+                        username,
+                        game,
+                        total,
+                        if override_cap { " override" } else { "" },
+                        CONFIRMATION_WINDOW.as_secs()
+                    ),
+                )
+                .await
+                .log_send_error();
+            return;
+        }
+
+        if !take_confirmation(guild_id, author_id, &action) {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!(
+                        "Nothing to confirm. Run the update without `confirm` first, then confirm within {} seconds",
+                        CONFIRMATION_WINDOW.as_secs()
+                    ),
+                )
+                .await
+                .log_send_error();
+            return;
+        }
+    }
+
+    match user::update_total(guild_id, username, game, total, override_cap) {
+        Ok((old_total, added, new_total, crossed)) => {
+            let mes = format!(
+                "{}'s total for '{}': added {}, now {}{}",
+                shown,
+                game,
+                user::format_money(added),
+                user::format_money(new_total),
+                goal_exceeded_note(guild_id, username, game, old_total, new_total)
+            );
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+
+            let update = (game.to_string(), added, new_total, crossed);
+            send_spending_callouts(ctx, msg.channel_id, guild_id, username, &[update]).await;
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !addmultiple Q "Tekken 8" 50 "Street Fighter 6" 30 - several updates in one go
+async fn add_multiple_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() < 4 || !parts.len().is_multiple_of(2) {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Usage: !addmultiple <username> \"<game>\" <amount> [\"<game>\" <amount> ...]",
+            )
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+    let updates: Vec<(String, String)> = parts[2..]
+        .chunks(2)
+        .map(|pair| (game_name(guild_id, &pair[0]), pair[1].clone()))
+        .collect();
+
+    match user::update_totals(guild_id, username, &updates) {
+        Ok(results) => {
+            let lines: Vec<String> = results
+                .iter()
+                .map(|(game, _, new_total, _)| {
+                    format!("• {}: now {}", game, user::format_money(*new_total))
+                })
+                .collect();
+            let mes = format!("**Updated {}'s games:**\n{}", shown, lines.join("\n"));
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+
+            send_spending_callouts(ctx, msg.channel_id, guild_id, username, &results).await;
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}. Nothing was updated.", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !preview Q "Tekken 8" 50 - what !updatetotal would do, without doing it
+async fn preview_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 4 {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Usage: !preview <username> \"<game name>\" <additional_amount>",
+            )
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+    let game = &game_name(guild_id, &parts[2]);
+    let total = &parts[3];
+
+    match user::preview_update(guild_id, username, game, total) {
+        Ok((new_total, crossed)) => {
+            let callout = match crossed {
+                user::ThresholdCrossed::SuperTroll => format!(
+                    " and cross {}, the big callout",
+                    user::format_money(user::SUPER_TROLL_THRESHOLD)
+                ),
+                user::ThresholdCrossed::Troll => format!(
+                    " and cross {}, setting off a callout",
+                    user::format_money(
+                        user::get_threshold(guild_id).unwrap_or(user::TROLL_THRESHOLD)
+                    )
+                ),
+                user::ThresholdCrossed::None => String::new(),
+            };
+            let mes = format!(
+                "Preview: {}'s total for '{}' would be {}{}. Nothing has been changed.",
+                shown,
+                game,
+                user::format_money(new_total),
+                callout
+            );
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !removegame Q "Tekken 8"
+async fn remove_game_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 3 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !removegame <username> \"<game name>\"")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+    let game = &game_name(guild_id, &parts[2]);
+
+    match user::remove_game(guild_id, username, game) {
+        Ok(_) => {
+            let mes = format!("Removed game '{}' from user {}", game, shown);
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !movegame Q Alice "Tekken 8" history
+async fn move_game_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    // A trailing `history` moves the game's logged changes along with it
+    let move_history = parts.len() == 5 && parts[4] == "history";
+    if parts.len() != 4 && !move_history {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Usage: !movegame <from> <to> \"<game name>\" [history]",
+            )
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let (from, to) = (&parts[1], &parts[2]);
+    // Looked up first, since `from` may not be around afterwards
+    let from_shown = user::get_display_name(guild_id, from);
+    let to_shown = user::get_display_name(guild_id, to);
+    let game = &game_name(guild_id, &parts[3]);
+
+    match user::move_game(guild_id, from, to, game, move_history) {
+        Ok(emptied) => {
+            let mut mes = format!("Moved '{}' from {} to {}", game, from_shown, to_shown);
+            if move_history {
+                mes.push_str(", along with its history");
+            }
+            if emptied {
+                mes.push_str(&format!(
+                    "\n{} had no games left and was removed",
+                    from_shown
+                ));
+            }
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !undo Q - revert Q's last update (!undouser Q does the same)
+async fn undo_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 2 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !undo <username>")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+
+    match user::undo_last_for(guild_id, username) {
+        Ok((game, total)) => {
+            let mes = format!(
+                "↩️ Undid {}'s last update. '{}' is back to {}",
+                shown,
+                game,
+                user::format_money(total)
+            );
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !deleteuser Q
+async fn delete_user_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 2 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !deleteuser <username>")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+
+    match user::delete_user(guild_id, username) {
+        Ok(_) => {
+            let mes = format!("Deleted user {} and all their games", shown);
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !renameuser Q Quinn (admin only) - rename a user, keeping all their games
+async fn rename_user_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 3 {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Usage: !renameuser <old username> <new username>",
+            )
+            .await
+            .log_send_error();
+        return;
+    }
+
+    match user::rename_user(guild_id, &parts[1], &parts[2]) {
+        Ok(_) => {
+            let mes = format!("Renamed user {} to {}", parts[1], parts[2]);
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !merge Q2 Q - fold a duplicate account into the real one (`preview` to only see
+// what it would do)
+async fn merge_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+
+    let preview = parts.len() == 4 && parts[3] == "preview";
+    if parts.len() != 3 && !preview {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !merge <from> <into> [preview]")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let into_shown = user::get_display_name(guild_id, &parts[2]);
+
+    match user::merge_users(guild_id, &parts[1], &parts[2], preview) {
+        Ok(moved) if preview => {
+            let mes = format!(
+                "🔍 Merging {} into {} would move {} games over. Nothing was changed",
+                parts[1], into_shown, moved
+            );
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Ok(moved) => {
+            let mes = format!(
+                "🔀 Merged {} into {}: {} games moved over",
+                parts[1], into_shown, moved
+            );
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !usergames Q - show all games for a specific user
+async fn user_games_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 2 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !usergames <username>")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+
+    match user::get_user_games(guild_id, username) {
+        Ok(games) => {
+            if games.is_empty() {
+                msg.channel_id
+                    .say(&ctx.http, format!("User {} has no games", shown))
+                    .await
+                    .log_send_error();
+            } else {
+                let tags = user::get_user_tags(guild_id, username).unwrap_or_default();
+                let games_list: Vec<String> = games
+                    .iter()
+                    .map(|(game, total)| match tags.get(game) {
+                        Some(tags) => format!(
+                            "• {}: {} [{}]",
+                            game,
+                            user::format_money(*total),
+                            tags.join(", ")
+                        ),
+                        None => format!("• {}: {}", game, user::format_money(*total)),
+                    })
+                    .collect();
+
+                let mes = format!("**{}'s Games:**\n{}", shown, games_list.join("\n"));
+                for chunk in split_lines(&mes, DISCORD_MESSAGE_LIMIT) {
+                    msg.channel_id.say(&ctx.http, chunk).await.log_send_error();
+                }
+            }
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// Room left at the top of each !getusers message for its header and page counter
+const USER_LIST_HEADER_RESERVE: usize = 50;
+
+// Render the !getusers list as messages that each fit in Discord's limit.
+// Users are kept whole on one page unless a single user is too big for one
+fn render_user_list(user_list: &[user::User]) -> Vec<String> {
+    let budget = DISCORD_MESSAGE_LIMIT - USER_LIST_HEADER_RESERVE;
+    let mut pages: Vec<String> = Vec::new();
+    let mut current = String::new();
+
+    for user in user_list {
+        let games_info: Vec<String> = user
+            .games
+            .iter()
+            .map(|(game, record)| format!("  • {}: {}", game, user::format_money(record.total)))
+            .collect();
+        let games_info = if games_info.is_empty() {
+            "  (no games)".to_string()
+        } else {
+            games_info.join("\n")
+        };
+
+        // Show the lookup key too when it differs, since commands need it
+        let name = if user.display_name() == user.user {
+            format!("**{}**", user.user)
+        } else {
+            format!("**{}** ({})", user.display_name(), user.user)
+        };
+
+        let name = if user.archived {
+            format!("{} (archived)", name)
+        } else {
+            name
+        };
+
+        let section = match &user.alias_of {
+            Some(canonical) => format!("{} (linked to {})\n{}", name, canonical, games_info),
+            None => format!("{}\n{}", name, games_info),
+        };
+
+        for piece in split_lines(&section, budget) {
+            let needed = if current.is_empty() { 0 } else { 2 } + piece.chars().count();
+            if current.chars().count() + needed > budget {
+                pages.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push_str("\n\n");
+            }
+            current.push_str(&piece);
+        }
+    }
+    if !current.is_empty() {
+        pages.push(current);
+    }
+
+    let count = pages.len();
+    pages
+        .into_iter()
+        .enumerate()
+        .map(|(i, page)| {
+            if count == 1 {
+                format!("**All Users:**\n{}", page)
+            } else {
+                format!("**All Users (page {}/{}):**\n{}", i + 1, count, page)
+            }
+        })
+        .collect()
+}
+
+// !getusers - show all users (updated for new structure)
+async fn get_users_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+    let include_archived = match parts.get(1).map(String::as_str) {
+        None => false,
+        Some("--all") if parts.len() == 2 => true,
+        _ => {
+            msg.channel_id
+                .say(&ctx.http, "Usage: !getusers [--all]")
+                .await
+                .log_send_error();
+            return;
+        }
+    };
+
+    match user::get_users(guild_id, include_archived) {
+        Ok(user_list) => {
+            if user_list.is_empty() {
+                msg.channel_id
+                    .say(
+                        &ctx.http,
+                        "No users are currently added to the bot! Try the !adduser command.",
+                    )
+                    .await
+                    .log_send_error();
+                return;
+            }
+
+            for page in render_user_list(&user_list) {
+                msg.channel_id.say(&ctx.http, page).await.log_send_error();
+            }
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !stats - summary numbers for the server
+async fn stats_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    match user::get_stats(guild_id) {
+        Ok(stats) => {
+            let biggest = match &stats.biggest {
+                Some((username, game, total)) => format!(
+                    "{} on '{}': {}",
+                    user::get_display_name(guild_id, username),
+                    game,
+                    user::format_money(*total)
+                ),
+                None => "Nobody yet".to_string(),
+            };
+
+            let embed = CreateEmbed::new()
+                .title("📈 Server Stats")
+                .color(0x3498db) // Blue color
+                .field("Users", stats.total_users.to_string(), true)
+                .field("Games", stats.distinct_games.to_string(), true)
+                .field("Total Spent", user::format_money(stats.grand_total), true)
+                .field(
+                    "Average Per User",
+                    user::format_money(stats.average_per_user),
+                    true,
+                )
+                .field("Biggest Single Game", biggest, false);
+
+            let builder = CreateMessage::new().embed(embed);
+            msg.channel_id
+                .send_message(&ctx.http, builder)
+                .await
+                .log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !search tekken - find games by part of their name, across every user
+async fn search_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() < 2 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !search <part of a game name>")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let query = parts[1..].join(" ");
+
+    match user::search_games(guild_id, &query) {
+        Ok(found) => {
+            if found.is_empty() {
+                msg.channel_id
+                    .say(&ctx.http, format!("No games matching '{}'", query))
+                    .await
+                    .log_send_error();
+                return;
+            }
+
+            // Results come sorted by user, so each user's games are next to each other
+            let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+            for (username, game, total) in &found {
+                let line = format!("  • {}: {}", game, user::format_money(*total));
+                match groups.last_mut() {
+                    Some((last, lines)) if last == username => lines.push(line),
+                    _ => groups.push((username.clone(), vec![line])),
+                }
+            }
+
+            let sections: Vec<String> = groups
+                .iter()
+                .map(|(username, lines)| {
+                    format!(
+                        "**{}**\n{}",
+                        user::get_display_name(guild_id, username),
+                        lines.join("\n")
+                    )
+                })
+                .collect();
+
+            let mes = format!(
+                "**🔎 Games matching '{}':**\n{}",
+                query,
+                sections.join("\n")
+            );
+            for chunk in split_lines(&mes, DISCORD_MESSAGE_LIMIT) {
+                msg.channel_id.say(&ctx.http, chunk).await.log_send_error();
+            }
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+async fn game_total_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 3 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !gametotal <username> \"<game>\"")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+    let game = &game_name(guild_id, &parts[2]);
+
+    match user::get_game_record(guild_id, username, game) {
+        Ok(record) => {
+            let everyone = user::get_cached_stats(guild_id)
+                .ok()
+                .and_then(|stats| stats.game_totals.get(game).copied())
+                .unwrap_or(record.total);
+            let mes = format!(
+                "{}'s total for '{}': {} (of {} across everyone){}",
+                shown,
+                game,
+                total_against_goal(guild_id, username, game, record.total),
+                user::format_money(everyone),
+                last_updated_note(&record)
+            );
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// How long ago a game last changed, like ` (last updated 3 days ago)`. Empty for
+// games from before this was recorded
+fn last_updated_note(record: &user::GameRecord) -> String {
+    if record.last_updated == 0 {
+        return String::new();
+    }
+    format!(
+        " (last updated {})",
+        time_ago(record.last_updated, user::now())
+    )
+}
+
+// A past Unix timestamp as `just now`, `5 minutes ago`, `3 days ago` and so on, as of `now`
+fn time_ago(timestamp: i64, now: i64) -> String {
+    const MINUTE: i64 = 60;
+    const HOUR: i64 = 60 * MINUTE;
+    const DAY: i64 = 24 * HOUR;
+    const MONTH: i64 = 30 * DAY;
+    const YEAR: i64 = 365 * DAY;
+
+    let elapsed = (now - timestamp).max(0);
+    let (count, unit) = match elapsed {
+        _ if elapsed < MINUTE => return "just now".to_string(),
+        _ if elapsed < HOUR => (elapsed / MINUTE, "minute"),
+        _ if elapsed < DAY => (elapsed / HOUR, "hour"),
+        _ if elapsed < MONTH => (elapsed / DAY, "day"),
+        _ if elapsed < YEAR => (elapsed / MONTH, "month"),
+        _ => (elapsed / YEAR, "year"),
+    };
+
+    format!(
+        "{} {}{} ago",
+        count,
+        unit,
+        if count == 1 { "" } else { "s" }
+    )
+}
+
+// A total against its goal, like `▰▰▰▰▰▰▰▱▱▱ $150.00 / $200.00 goal — 75%`
+fn goal_progress(total: i64, goal: i64) -> String {
+    const BAR_LENGTH: usize = 10;
+    let percent = user::goal_percent(total, goal);
+    let filled = (percent.min(100) as usize * BAR_LENGTH) / 100;
+
+    format!(
+        "{}{} {} / {} goal — {}%",
+        "▰".repeat(filled),
+        "▱".repeat(BAR_LENGTH - filled),
+        user::format_money(total),
+        user::format_money(goal),
+        percent
+    )
+}
+
+// A game's total, with its progress bar if it has a goal
+fn total_against_goal(guild_id: u64, username: &str, game: &str, total: i64) -> String {
+    match user::get_goal(guild_id, username, game) {
+        Ok(Some(goal)) => goal_progress(total, goal),
+        _ => user::format_money(total),
+    }
+}
+
+// A line to add to an update's reply when it took a game past its goal
+fn goal_exceeded_note(
+    guild_id: u64,
+    username: &str,
+    game: &str,
+    old_total: i64,
+    new_total: i64,
+) -> String {
+    match user::get_goal(guild_id, username, game) {
+        Ok(Some(goal)) if old_total <= goal && new_total > goal => format!(
+            "\n🎯 Goal exceeded: that's past the {} goal for '{}'",
+            user::format_money(goal),
+            game
+        ),
+        _ => String::new(),
+    }
+}
+
+// !refund Q "Tekken 8" 20 - take money back off a game total
+async fn refund_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 4 {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Usage: !refund <username> \"<game name>\" <amount>",
+            )
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+    let game = &game_name(guild_id, &parts[2]);
+
+    match user::refund(guild_id, username, game, &parts[3]) {
+        Ok((_, refunded, new_total, _)) => {
+            let mes = format!(
+                "💰 Refunded {} on {}'s '{}', now {}",
+                user::format_money(-refunded),
+                shown,
+                game,
+                user::format_money(new_total)
+            );
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !history Q "Tekken 8" - the last 10 changes to a user's totals
+async fn history_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 2 && parts.len() != 3 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !history <username> [\"<game>\"]")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+    let game = parts.get(2).map(|game| game_name(guild_id, game));
+
+    match user::get_history(guild_id, username, game.as_deref()) {
+        Ok(history) => {
+            if history.is_empty() {
+                msg.channel_id
+                    .say(&ctx.http, format!("No updates recorded for {} yet", shown))
+                    .await
+                    .log_send_error();
+                return;
+            }
+
+            let lines: Vec<String> = history
+                .iter()
+                .take(10)
+                .map(|entry| {
+                    let sign = if entry.delta < 0 { "" } else { "+" };
+                    let undo = if entry.undoes.is_some() {
+                        " (undo)"
+                    } else {
+                        ""
+                    };
+                    format!(
+                        "• {} - {}: {}{}{} (now {})",
+                        entry.when(),
+                        entry.game,
+                        sign,
+                        user::format_money(entry.delta),
+                        undo,
+                        user::format_money(entry.total)
+                    )
+                })
+                .collect();
+
+            let mes = format!("**📜 {}'s History:**\n{}", shown, lines.join("\n"));
+            for chunk in split_lines(&mes, DISCORD_MESSAGE_LIMIT) {
+                msg.channel_id.say(&ctx.http, chunk).await.log_send_error();
+            }
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+async fn user_total_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 2 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !usertotal <username>")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+
+    match user::get_user_total_all_games(guild_id, username) {
+        Ok(total) => {
+            let mut mes = format!(
+                "{}'s total across all available games: {}",
+                shown,
+                user::format_money(total)
+            );
+
+            // Where that puts them, from the stats cache rather than a rescan
+            if let Ok(stats) = user::get_cached_stats(guild_id)
+                && stats.grand_total > 0
+            {
+                let ranking = stats.ranking();
+                if let Some(place) = ranking.iter().position(|(name, _)| name == username) {
+                    mes.push_str(&format!(
+                        " (#{} of {}, {}% of the {} tracked)",
+                        place + 1,
+                        ranking.len(),
+                        total * 100 / stats.grand_total,
+                        user::format_money(stats.grand_total)
+                    ));
+                }
+            }
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !mystats, for whoever sent it
+async fn my_stats_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let Some(username) = author_username(guild_id, &msg.author) else {
+        msg.channel_id
+            .say(&ctx.http, "You're not being tracked yet — use !adduser.")
+            .await
+            .log_send_error();
+        return;
+    };
+    let shown = user::get_display_name(guild_id, &username);
+
+    let stats = user::get_user_games(guild_id, &username).and_then(|games| {
+        user::get_user_total_all_games(guild_id, &username).map(|total| (games, total))
+    });
+
+    match stats {
+        Ok((games, total)) => {
+            let mut games: Vec<(String, i64)> = games.into_iter().collect();
+            games.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+            let games_list: Vec<String> = games
+                .iter()
+                .map(|(game, total)| format!("• {}: {}", game, user::format_money(*total)))
+                .collect();
+
+            let mes = if games_list.is_empty() {
+                format!("**{}** has no games yet", shown)
+            } else {
+                format!(
+                    "**{}'s Games:**\n{}\n**Total:** {}",
+                    shown,
+                    games_list.join("\n"),
+                    user::format_money(total)
+                )
+            };
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !globalalias sf6 "Street Fighter 6"
+async fn global_alias_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 3 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !globalalias <short> \"<game name>\"")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let short = &parts[1];
+    let game = &parts[2];
+
+    match config::set_global_alias(guild_id, short, game) {
+        Ok(_) => {
+            let mes = format!("'{}' now means '{}' for everyone", short, game);
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !globalunalias sf6
+async fn global_unalias_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 2 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !globalunalias <short>")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let short = &parts[1];
+
+    match config::remove_global_alias(guild_id, short) {
+        Ok(_) => {
+            let mes = format!("Removed global alias '{}'", short);
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !linkaccount Q2 Q - Q2's commands now act on Q (admin only)
+async fn link_account_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 3 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !linkaccount <alias> <canonical_user>")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let alias = &parts[1];
+    let canonical = &parts[2];
+
+    match user::link_account(guild_id, alias, canonical) {
+        Ok(_) => {
+            let mes = format!(
+                "Linked {} to {}. Their totals now roll up to {}",
+                alias,
+                canonical,
+                user::get_display_name(guild_id, canonical)
+            );
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !unlink Q2 (admin only)
+async fn unlink_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 2 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !unlink <alias>")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let alias = &parts[1];
+
+    match user::unlink_account(guild_id, alias) {
+        Ok(_) => {
+            let mes = format!("Unlinked {}", alias);
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !removegames Q "Tekken*" confirm
+async fn remove_games_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+
+    let confirmed = parts.len() == 4 && parts[3] == "confirm";
+    if parts.len() != 3 && !confirmed {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Usage: !removegames <username> \"<pattern>\" [confirm]",
+            )
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+    let pattern = &parts[2];
+
+    // Without the confirm flag only show what would be removed
+    let result = if confirmed {
+        user::remove_games_matching(guild_id, username, pattern)
+    } else {
+        user::find_games_matching(guild_id, username, pattern)
+    };
+
+    match result {
+        Ok(games) if games.is_empty() => {
+            let mes = format!("{} has no games matching '{}'", shown, pattern);
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Ok(games) => {
+            let games_list: Vec<String> = games.iter().map(|game| format!("• {}", game)).collect();
+
+            let mes = if confirmed {
+                format!(
+                    "Removed {} games from {}:\n{}",
+                    games.len(),
+                    shown,
+                    games_list.join("\n")
+                )
+            } else {
+                format!(
+                    "This would remove {} games from {}:\n{}\nRun `!removegames {} \"{}\" confirm` to remove them",
+                    games.len(),
+                    shown,
+                    games_list.join("\n"),
+                    username,
+                    pattern
+                )
+            };
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !whale - spotlight the biggest spender on the server
+async fn whale_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let top = match user::get_leaderboard(guild_id, 1) {
+        Ok(leaderboard) => leaderboard.into_iter().next(),
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+            return;
+        }
+    };
+
+    let Some((username, total)) = top else {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "No whales here yet. Nobody is being tracked! Try the !adduser command.",
+            )
+            .await
+            .log_send_error();
+        return;
+    };
+
+    match user::get_user_games(guild_id, &username) {
+        Ok(games) => {
+            let (top_game, top_game_total) = games
+                .into_iter()
+                .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(&a.0)))
+                .unwrap_or_default();
+
+            let mes = roast::whale_callout(
+                &user::get_display_name(guild_id, &username),
+                total,
+                &top_game,
+                top_game_total,
+            );
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !debug Q - dump the raw stored record (admin only)
+async fn debug_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 2 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !debug <username>")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    match user::debug_dump(guild_id, &parts[1]) {
+        Ok(dump) => {
+            msg.channel_id.say(&ctx.http, dump).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !export Q csv - send a user's data as a file, JSON unless CSV is asked for
+async fn export_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    let format = match parts.get(2).map(|format| format.to_lowercase()).as_deref() {
+        None | Some("json") => Some(user::ExportFormat::Json),
+        Some("csv") => Some(user::ExportFormat::Csv),
+        Some(_) => None,
+    };
+
+    let (2..=3, Some(format)) = (parts.len(), format) else {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !export <username> [json|csv]")
+            .await
+            .log_send_error();
+        return;
+    };
+
+    match user::export_user(guild_id, &parts[1], format) {
+        Ok(contents) => {
+            let filename = format!("{}.{}", parts[1], format.extension());
+            let builder = CreateMessage::new()
+                .content(format!("📦 Export for {}", parts[1]))
+                .add_file(CreateAttachment::bytes(contents, filename));
+            msg.channel_id
+                .send_message(&ctx.http, builder)
+                .await
+                .log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// Largest CSV file !import will download
+const IMPORT_FILE_LIMIT: u32 = 256 * 1024;
+
+// How many bad rows !import lists before summing up the rest
+const IMPORT_PROBLEMS_SHOWN: usize = 10;
+
+// !import Q with a CSV attached - set a user's games from `game,total` lines (admin only,
+// `preview` to only see what it would do)
+async fn import_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+
+    let preview = parts.len() == 3 && parts[2] == "preview";
+    let (true, Some(attachment)) = (parts.len() == 2 || preview, msg.attachments.first()) else {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Usage: !import <username> [preview] with a CSV file of `game,total` lines attached",
+            )
+            .await
+            .log_send_error();
+        return;
+    };
+
+    if attachment.size > IMPORT_FILE_LIMIT {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!(
+                    "Error: That file is too big to import (limit {} KB)",
+                    IMPORT_FILE_LIMIT / 1024
+                ),
+            )
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let csv = match attachment.download().await {
+        Ok(bytes) => match String::from_utf8(bytes) {
+            Ok(csv) => csv,
+            Err(_) => {
+                msg.channel_id
+                    .say(&ctx.http, "Error: That file isn't readable text")
+                    .await
+                    .log_send_error();
+                return;
+            }
+        },
+        Err(e) => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!("Error: Couldn't download the file: {}", e),
+                )
+                .await
+                .log_send_error();
+            return;
+        }
+    };
+
+    match user::import_games(guild_id, &parts[1], &csv, preview) {
+        Ok((imported, problems)) => {
+            let mut mes = if preview {
+                format!(
+                    "🔍 Would import {} games for {}. Nothing was changed",
+                    imported, parts[1]
+                )
+            } else {
+                format!("📥 Imported {} games for {}", imported, parts[1])
+            };
+            if !problems.is_empty() {
+                mes.push_str(&format!("\nSkipped {} rows:", problems.len()));
+                for problem in problems.iter().take(IMPORT_PROBLEMS_SHOWN) {
+                    mes.push_str(&format!("\n• {}", problem));
+                }
+                if problems.len() > IMPORT_PROBLEMS_SHOWN {
+                    mes.push_str(&format!(
+                        "\n…and {} more",
+                        problems.len() - IMPORT_PROBLEMS_SHOWN
+                    ));
+                }
+            }
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e @ user::UserError::UserNotFound(_)) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}! Use !adduser first", e))
+                .await
+                .log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !renamegame "Tekken8" "Tekken 8" (admin only) - rename a game for everyone who has it
+async fn rename_game_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+
+    let preview = parts.len() == 4 && parts[3] == "preview";
+    if parts.len() != 3 && !preview {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Usage: !renamegame \"<old game name>\" \"<new game name>\" [preview]",
+            )
+            .await
+            .log_send_error();
+        return;
+    }
+
+    match user::rename_game_global(guild_id, &parts[1], &parts[2], preview) {
+        Ok(renamed) => {
+            let names: Vec<String> = renamed
+                .iter()
+                .map(|username| user::get_display_name(guild_id, username))
+                .collect();
+            let mes = if preview {
+                format!(
+                    "🔍 Renaming '{}' to '{}' would change {} users: {}. Nothing was changed",
+                    parts[1],
+                    parts[2],
+                    names.len(),
+                    names.join(", ")
+                )
+            } else {
+                format!(
+                    "✏️ Renamed '{}' to '{}' for {} users: {}",
+                    parts[1],
+                    parts[2],
+                    names.len(),
+                    names.join(", ")
+                )
+            };
+            for chunk in split_lines(&mes, DISCORD_MESSAGE_LIMIT) {
+                msg.channel_id.say(&ctx.http, chunk).await.log_send_error();
+            }
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !cheapskates 5 - the smallest spenders, cheapest first
+async fn cheapskates_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    let limit = match parts.len() {
+        1 => Some(10),
+        2 => parts[1].parse::<usize>().ok().filter(|&count| count > 0),
+        _ => None,
+    };
+
+    let Some(limit) = limit else {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !cheapskates [count]")
+            .await
+            .log_send_error();
+        return;
+    };
+
+    match user::get_cheapskates(guild_id, limit) {
+        Ok(cheapskates) => {
+            if cheapskates.is_empty() {
+                msg.channel_id
+                    .say(
+                        &ctx.http,
+                        "Nobody is being tracked yet! Try the !adduser command.",
+                    )
+                    .await
+                    .log_send_error();
+                return;
+            }
+
+            let lines: Vec<String> = cheapskates
+                .iter()
+                .enumerate()
+                .map(|(i, (username, total))| {
+                    roast::board_line(i + 1, &user::get_display_name(guild_id, username), *total)
+                })
+                .collect();
+
+            let mes = format!("**🪙 Cheapskates:**\n{}", lines.join("\n"));
+            for chunk in split_lines(&mes, DISCORD_MESSAGE_LIMIT) {
+                msg.channel_id.say(&ctx.http, chunk).await.log_send_error();
+            }
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !topgame "Tekken 8" - the biggest spenders on one game
+async fn top_game_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 2 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !topgame \"<game name>\"")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let game = &game_name(guild_id, &parts[1]);
+
+    match user::get_game_leaderboard(guild_id, game) {
+        Ok(leaderboard) => {
+            let lines: Vec<String> = leaderboard
+                .iter()
+                .enumerate()
+                .map(|(i, (username, total))| {
+                    roast::podium_line(i + 1, &user::get_display_name(guild_id, username), *total)
+                })
+                .collect();
+
+            let embed = CreateEmbed::new()
+                .title(format!("🎮 Top Spenders: {}", game))
+                .description(lines.join("\n"))
+                .color(0xffd700); // Gold color
+
+            let builder = CreateMessage::new().embed(embed);
+            msg.channel_id
+                .send_message(&ctx.http, builder)
+                .await
+                .log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !leaderboard 5 - the biggest spenders across all games
+// !leaderboard weighted 5 - rank by totals with per-game weights applied
+// !leaderboard both week 5 - all-time totals and this week's spending side by side
+// !leaderboard weekend 5 - rank by spending logged during one part of the week
+// !leaderboard thread - post a board into its own thread and keep it updated (admin only)
+async fn leaderboard_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if let Some(window) = parts.get(1).and_then(|keyword| spend_window(keyword)) {
+        let limit = match parts.len() {
+            2 => Some(10),
+            3 => parts[2].parse::<usize>().ok().filter(|&count| count > 0),
+            _ => None,
+        };
+        let Some(limit) = limit else {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!("Usage: !leaderboard {} [count]", parts[1]),
+                )
+                .await
+                .log_send_error();
+            return;
+        };
+
+        match window_leaderboard(guild_id, window) {
+            Ok(leaderboard) if leaderboard.is_empty() => {
+                msg.channel_id
+                    .say(&ctx.http, "Nobody has spent anything in that time!")
+                    .await
+                    .log_send_error();
+            }
+            Ok(leaderboard) => {
+                let lines: Vec<String> = leaderboard
+                    .iter()
+                    .take(limit)
+                    .enumerate()
+                    .map(|(i, (username, spent))| {
+                        roast::board_line(
+                            i + 1,
+                            &user::get_display_name(guild_id, username),
+                            *spent,
+                        )
+                    })
+                    .collect();
+                let title = match window {
+                    user::SpendWindow::Weekend => "🏆 Top Weekend Spenders",
+                    user::SpendWindow::Weekdays => "🏆 Top Weekday Spenders",
+                    user::SpendWindow::Nights => "🏆 Top Late-Night Spenders",
+                    user::SpendWindow::Weeknights => "🏆 Top Weeknight Spenders",
+                };
+
+                let mes = format!("**{}**\n{}", title, lines.join("\n"));
+                msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+            }
+            Err(e) => {
+                msg.channel_id
+                    .say(&ctx.http, format!("Error: {}", e))
+                    .await
+                    .log_send_error();
+            }
+        }
+        return;
+    }
+
+    if parts.len() == 2 && parts[1] == "thread" {
+        if !is_admin(ctx, msg).await {
+            msg.channel_id
+                .say(&ctx.http, "You don't have permission to do that.")
+                .await
+                .log_send_error();
+            return;
+        }
+
+        let text = thread_board_text(guild_id);
+        let thread = CreateThread::new("🏆 Leaderboard").kind(ChannelType::PublicThread);
+        let posted = match msg.channel_id.create_thread(&ctx.http, thread).await {
+            Ok(thread) => thread
+                .id
+                .say(&ctx.http, &text)
+                .await
+                .map(|board| (thread.id, board)),
+            Err(error) => Err(error),
+        };
+
+        match posted {
+            Ok((thread_id, board)) => {
+                if let Err(e) =
+                    config::set_board_thread(guild_id, thread_id.get(), board.id.get(), user::now())
+                {
+                    error!("Error saving the leaderboard thread: {e}");
+                }
+                msg.channel_id
+                    .say(
+                        &ctx.http,
+                        format!(
+                            "Posted the leaderboard in <#{}>, and it will stay updated",
+                            thread_id
+                        ),
+                    )
+                    .await
+                    .log_send_error();
+            }
+            Err(error) => {
+                // No threads here (e.g. a DM), so post it as a normal message instead
+                warn!("Error creating the leaderboard thread: {error:?}");
+                msg.channel_id.say(&ctx.http, text).await.log_send_error();
+            }
+        }
+        return;
+    }
+
+    if parts.get(1).map(String::as_str) == Some("both") {
+        let mut args = &parts[2..];
+        let sort = match args.first().map(String::as_str) {
+            Some("week") => Some(BoardSort::ThisWeek),
+            Some("all") => Some(BoardSort::AllTime),
+            _ => None,
+        };
+        if sort.is_some() {
+            args = &args[1..];
+        }
+        let limit = match args {
+            [] => Some(10),
+            [count] => count.parse::<usize>().ok().filter(|&count| count > 0),
+            _ => None,
+        };
+        let Some(limit) = limit else {
+            msg.channel_id
+                .say(&ctx.http, "Usage: !leaderboard both [all|week] [count]")
+                .await
+                .log_send_error();
+            return;
+        };
+        let sort = sort.unwrap_or(BoardSort::AllTime);
+
+        let week_ago = user::now() - 7 * 24 * 60 * 60;
+        let boards = user::get_leaderboard(guild_id, usize::MAX).and_then(|all_time| {
+            user::spend_since(guild_id, week_ago).map(|this_week| (all_time, this_week))
+        });
+
+        match boards {
+            Ok((all_time, _)) if all_time.is_empty() => {
+                msg.channel_id
+                    .say(
+                        &ctx.http,
+                        "Nobody is being tracked yet! Try the !adduser command.",
+                    )
+                    .await
+                    .log_send_error();
+            }
+            Ok((all_time, this_week)) => {
+                let lines: Vec<String> = combined_rows(&all_time, &this_week, sort)
+                    .iter()
+                    .take(limit)
+                    .enumerate()
+                    .map(|(i, (username, total, week))| {
+                        format!(
+                            "{} · {} this week",
+                            roast::board_line(
+                                i + 1,
+                                &user::get_display_name(guild_id, username),
+                                *total
+                            ),
+                            user::format_money(*week)
+                        )
+                    })
+                    .collect();
+                let title = match sort {
+                    BoardSort::AllTime => "🏆 Leaderboard: All Time and This Week",
+                    BoardSort::ThisWeek => "🏆 Leaderboard: This Week and All Time",
+                };
+
+                let mes = format!("**{}**\n{}", title, lines.join("\n"));
+                msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+            }
+            Err(e) => {
+                msg.channel_id
+                    .say(&ctx.http, format!("Error: {}", e))
+                    .await
+                    .log_send_error();
+            }
+        }
+        return;
+    }
+
+    // Just !leaderboard pages through everyone with reactions
+    if parts.len() == 1 {
+        let leaderboard = match user::get_leaderboard(guild_id, usize::MAX) {
+            Ok(leaderboard) => leaderboard,
+            Err(e) => {
+                msg.channel_id
+                    .say(&ctx.http, format!("Error: {}", e))
+                    .await
+                    .log_send_error();
+                return;
+            }
+        };
+
+        let Ok(board) = msg
+            .channel_id
+            .say(&ctx.http, leaderboard_page(guild_id, &leaderboard, 0))
+            .await
+        else {
+            return;
+        };
+        if page_count(leaderboard.len()) > 1 {
+            track_board_pages(board.id.get(), msg.author.id.get(), user::now());
+            for arrow in [PREVIOUS_PAGE, NEXT_PAGE] {
+                board
+                    .react(&ctx.http, ReactionType::Unicode(arrow.to_string()))
+                    .await
+                    .log_send_error();
+            }
+        }
+        return;
+    }
+
+    // !leaderboard 5 - just the biggest spenders, with medals for the top three
+    if parts.len() == 2
+        && let Ok(limit) = parts[1].parse::<usize>()
+        && limit > 0
+    {
+        match user::get_leaderboard(guild_id, limit) {
+            Ok(leaderboard) => {
+                if leaderboard.is_empty() {
+                    msg.channel_id
+                        .say(
+                            &ctx.http,
+                            "Nobody is being tracked yet! Try the !adduser command.",
+                        )
+                        .await
+                        .log_send_error();
+                    return;
+                }
+
+                let embed = leaderboard_embed(guild_id, "🏆 Leaderboard", &leaderboard);
+                let builder = CreateMessage::new().embed(embed);
+                msg.channel_id
+                    .send_message(&ctx.http, builder)
+                    .await
+                    .log_send_error();
+            }
+            Err(e) => {
+                msg.channel_id
+                    .say(&ctx.http, format!("Error: {}", e))
+                    .await
+                    .log_send_error();
+            }
+        }
+        return;
+    }
+
+    let limit = match (parts.get(1).map(String::as_str), parts.len()) {
+        (Some("weighted"), 2) => Some(10),
+        (Some("weighted"), 3) => parts[2].parse::<usize>().ok().filter(|&count| count > 0),
+        _ => None,
+    };
+
+    let Some(limit) = limit else {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Usage: !leaderboard [count] or !leaderboard weighted [count]",
+            )
+            .await
+            .log_send_error();
+        return;
+    };
+
+    let weights = match config::get_game_weights(guild_id) {
+        Ok(weights) => weights,
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+            return;
+        }
+    };
+
+    match user::get_weighted_leaderboard(guild_id, &weights, limit) {
+        Ok(leaderboard) => {
+            if leaderboard.is_empty() {
+                msg.channel_id
+                    .say(
+                        &ctx.http,
+                        "Nobody is being tracked yet! Try the !adduser command.",
+                    )
+                    .await
+                    .log_send_error();
+                return;
+            }
+
+            let lines: Vec<String> = leaderboard
+                .iter()
+                .enumerate()
+                .map(|(i, (username, total))| {
+                    format!(
+                        "{}. {} - {} shame points",
+                        i + 1,
+                        user::get_display_name(guild_id, username),
+                        total
+                    )
+                })
+                .collect();
+
+            let mes = format!("**⚖️ Weighted Leaderboard:**\n{}", lines.join("\n"));
+            for chunk in split_lines(&mes, DISCORD_MESSAGE_LIMIT) {
+                msg.channel_id.say(&ctx.http, chunk).await.log_send_error();
+            }
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !snapshot monday (admin only) - save the leaderboard to compare against later
+async fn snapshot_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+    if parts.len() != 2 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !snapshot <name>")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    match user::save_snapshot(guild_id, &parts[1]) {
+        Ok(count) => {
+            let mes = format!("📸 Saved snapshot '{}' of {} users", parts[1], count);
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !leaderboarddiff monday now
+async fn leaderboard_diff_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+    if parts.len() != 3 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !leaderboarddiff <snapshot> <snapshot>")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    match user::leaderboard_diff(guild_id, &parts[1], &parts[2]) {
+        Ok(changes) if changes.is_empty() => {
+            msg.channel_id
+                .say(&ctx.http, "Both leaderboards are empty")
+                .await
+                .log_send_error();
+        }
+        Ok(changes) => {
+            let lines: Vec<String> = changes
+                .iter()
+                .map(|change| {
+                    let shown = user::get_display_name(guild_id, &change.username);
+                    match (change.before, change.after) {
+                        (None, Some(after)) => {
+                            format!("🆕 {} is new at #{}", shown, after)
+                        }
+                        (Some(before), None) => {
+                            format!("👋 {} is gone (was #{})", shown, before)
+                        }
+                        (Some(before), Some(after)) if after < before => {
+                            format!("⬆️ {}: #{} → #{}", shown, before, after)
+                        }
+                        (Some(before), Some(after)) if after > before => {
+                            format!("⬇️ {}: #{} → #{}", shown, before, after)
+                        }
+                        (_, after) => {
+                            format!("➖ {}: still #{}", shown, after.unwrap_or_default())
+                        }
+                    }
+                })
+                .collect();
+
+            let mes = format!(
+                "**📈 Leaderboard changes from '{}' to '{}':**\n{}",
+                parts[1],
+                parts[2],
+                lines.join("\n")
+            );
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !reload (admin only) - pick up changes made to the database while the bot was running
+async fn reload_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    match user::reload_from_disk(guild_id) {
+        Ok(count) => {
+            msg.channel_id
+                .say(&ctx.http, format!("🔄 Reloaded {} users", count))
+                .await
+                .log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !rank Q
+async fn rank_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 2 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !rank <username>")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+
+    match user::get_rank(guild_id, username) {
+        Ok((rank, ranked, total)) => {
+            let mes = format!(
+                "{} is ranked #{} of {} with {}",
+                shown,
+                rank,
+                ranked,
+                user::format_money(total)
+            );
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !compare Q Alice
+async fn compare_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 3 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !compare <username> <username>")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    match user::compare(guild_id, &parts[1], &parts[2]) {
+        Ok(comparison) => {
+            let first = user::get_display_name(guild_id, &comparison.first);
+            let second = user::get_display_name(guild_id, &comparison.second);
+
+            let verdict = match &comparison.leader {
+                Some(leader) => format!(
+                    "**{}** spends {} more overall",
+                    user::get_display_name(guild_id, leader),
+                    user::format_money((comparison.first_total - comparison.second_total).abs())
+                ),
+                None => "Dead even overall".to_string(),
+            };
+
+            let shared = if comparison.shared.is_empty() {
+                "No games in common".to_string()
+            } else {
+                let lines: Vec<String> = comparison
+                    .shared
+                    .iter()
+                    .map(|(game, first_total, second_total)| {
+                        format!(
+                            "• {}: {} vs {}",
+                            game,
+                            user::format_money(*first_total),
+                            user::format_money(*second_total)
+                        )
+                    })
+                    .collect();
+                // Only the first page fits in one embed field
+                split_lines(&lines.join("\n"), EMBED_FIELD_LIMIT).swap_remove(0)
+            };
+
+            let embed = CreateEmbed::new()
+                .title(format!("⚔️ {} vs {}", first, second))
+                .description(verdict)
+                .color(0xe74c3c) // Red color
+                .field(first, user::format_money(comparison.first_total), true)
+                .field(second, user::format_money(comparison.second_total), true)
+                .field("Shared Games", shared, false);
+
+            let builder = CreateMessage::new().embed(embed);
+            msg.channel_id
+                .send_message(&ctx.http, builder)
+                .await
+                .log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// How far back each !top window reaches; `all` has no limit
+fn top_window(keyword: &str) -> Option<Option<chrono::Duration>> {
+    match keyword {
+        "week" => Some(Some(chrono::Duration::weeks(1))),
+        "month" => Some(Some(chrono::Duration::days(30))),
+        "all" => Some(None),
+        _ => None,
+    }
+}
+
+// !top week - the biggest spenders over the last week, month, or all time
+async fn top_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    let keyword = parts.get(1).map_or("week", String::as_str);
+    let window = match parts.len() {
+        1 | 2 => top_window(keyword),
+        _ => None,
+    };
+    let Some(window) = window else {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !top [week|month|all]")
+            .await
+            .log_send_error();
+        return;
+    };
+
+    let result = match window {
+        Some(window) => user::get_leaderboard_since(guild_id, chrono::Utc::now() - window),
+        None => user::get_leaderboard(guild_id, usize::MAX),
+    };
+
+    match result {
+        Ok(mut leaderboard) => {
+            if leaderboard.is_empty() {
+                msg.channel_id
+                    .say(&ctx.http, "Nobody has spent anything in that time!")
+                    .await
+                    .log_send_error();
+                return;
+            }
+
+            leaderboard.truncate(10);
+            let title = match keyword {
+                "week" => "🏆 Top Spenders This Week",
+                "month" => "🏆 Top Spenders This Month",
+                _ => "🏆 Top Spenders Of All Time",
+            };
+            let embed = leaderboard_embed(guild_id, title, &leaderboard);
+            let builder = CreateMessage::new().embed(embed);
+            msg.channel_id
+                .send_message(&ctx.http, builder)
+                .await
+                .log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !setweight "Genshin Impact" 2.5 (admin only)
+async fn set_weight_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 3 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !setweight \"<game name>\" <weight>")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let game = &game_name(guild_id, &parts[1]);
+    let Ok(weight) = parts[2].parse::<f64>() else {
+        msg.channel_id
+            .say(&ctx.http, "Error: Invalid number for weight")
+            .await
+            .log_send_error();
+        return;
+    };
+
+    match config::set_game_weight(guild_id, game, weight) {
+        Ok(_) => {
+            let mes = format!(
+                "Each {} spent on '{}' now counts as {} shame points",
+                user::format_money(100),
+                game,
+                weight
+            );
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !nopoke Q / !allowpoke Q - opt out of or back into the daily poke
+async fn poke_opt_out_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+    let opt_out = parts[0] == "!nopoke";
+
+    if parts.len() != 2 {
+        msg.channel_id
+            .say(&ctx.http, format!("Usage: {} <username>", parts[0]))
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+
+    match user::set_poke_opt_out(guild_id, username, opt_out) {
+        Ok(_) => {
+            let mes = if opt_out {
+                format!("{} won't get the daily poke anymore", shown)
+            } else {
+                format!("{} is back in the running for the daily poke", shown)
+            };
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !archive Bob / !unarchive Bob
+async fn archive_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+    let archive = parts[0] == "!archive";
+
+    if parts.len() != 2 {
+        msg.channel_id
+            .say(&ctx.http, format!("Usage: {} <username>", parts[0]))
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+
+    let result = if archive {
+        user::archive_user(guild_id, username)
+    } else {
+        user::unarchive_user(guild_id, username)
+    };
+
+    match result {
+        Ok(_) => {
+            let mes = if archive {
+                format!(
+                    "Archived {}. Their totals are kept, but they're hidden until `!unarchive`",
+                    shown
+                )
+            } else {
+                format!("{} is back on the lists", shown)
+            };
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !onthebrink 25 - who's about to cross a threshold
+async fn on_the_brink_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    let margin = match parts.len() {
+        1 => Some(50 * 100),
+        2 => user::parse_money(&parts[1]).filter(|&margin| margin > 0),
+        _ => None,
+    };
+
+    let Some(margin) = margin else {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !onthebrink [margin]")
+            .await
+            .log_send_error();
+        return;
+    };
+
+    match user::near_threshold(guild_id, margin) {
+        Ok(near) => {
+            if near.is_empty() {
+                let mes = format!(
+                    "Nobody is within {} of a shame threshold. For now...",
+                    user::format_money(margin)
+                );
+                msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+                return;
+            }
+
+            let lines: Vec<String> = near
+                .iter()
+                .map(|(username, game, total, threshold)| {
+                    format!(
+                        "• {} - {}: {} ({} away from {})",
+                        user::get_display_name(guild_id, username),
+                        game,
+                        user::format_money(*total),
+                        user::format_money(threshold - total),
+                        user::format_money(*threshold)
+                    )
+                })
+                .collect();
+
+            let mes = format!("**😬 On the Brink:**\n{}", lines.join("\n"));
+            for chunk in split_lines(&mes, DISCORD_MESSAGE_LIMIT) {
+                msg.channel_id.say(&ctx.http, chunk).await.log_send_error();
+            }
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !inactive 30 - games that haven't been touched in a month
+async fn inactive_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    let days = match parts.len() {
+        2 => parts[1].parse::<u32>().ok().filter(|&days| days > 0),
+        _ => None,
+    };
+
+    let Some(days) = days else {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !inactive <days>")
+            .await
+            .log_send_error();
+        return;
+    };
+
+    match user::inactive_since(guild_id, days) {
+        Ok(inactive) => {
+            if inactive.is_empty() {
+                let mes = format!(
+                    "Every game has been updated in the last {} day{}",
+                    days,
+                    if days == 1 { "" } else { "s" }
+                );
+                msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+                return;
+            }
+
+            let lines: Vec<String> = inactive
+                .iter()
+                .map(|(username, game, last_updated)| {
+                    let when = if *last_updated == 0 {
+                        "no update on record".to_string()
+                    } else {
+                        format!("last updated {}", time_ago(*last_updated, user::now()))
+                    };
+                    format!(
+                        "• {} - {}: {}",
+                        user::get_display_name(guild_id, username),
+                        game,
+                        when
+                    )
+                })
+                .collect();
+
+            let mes = format!("**💤 Inactive for {}+ days:**\n{}", days, lines.join("\n"));
+            for chunk in split_lines(&mes, DISCORD_MESSAGE_LIMIT) {
+                msg.channel_id.say(&ctx.http, chunk).await.log_send_error();
+            }
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !setcap Q "Tekken 8" 400 (or `none` to lift it, admin only)
+async fn set_cap_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 4 {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Usage: !setcap <username> \"<game name>\" <amount|none>",
+            )
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+    let game = &game_name(guild_id, &parts[2]);
+    let cap = (parts[3] != "none").then_some(parts[3].as_str());
+
+    match user::set_cap(guild_id, username, game, cap) {
+        Ok(cap) => {
+            let mes = match cap {
+                Some(cap) => format!(
+                    "{}'s '{}' is now capped at {}",
+                    shown,
+                    game,
+                    user::format_money(cap)
+                ),
+                None => format!("Removed the cap on {}'s '{}'", shown, game),
+            };
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !setgoal Q "Tekken 8" 200 (or `none` to clear it)
+async fn set_goal_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 4 {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Usage: !setgoal <username> \"<game name>\" <amount|none>",
+            )
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+    let game = &game_name(guild_id, &parts[2]);
+    let goal = (parts[3] != "none").then_some(parts[3].as_str());
+
+    match user::set_goal(guild_id, username, game, goal) {
+        Ok(goal) => {
+            let mes = match goal {
+                Some(goal) => format!(
+                    "{}'s goal for '{}' is now {}",
+                    shown,
+                    game,
+                    user::format_money(goal)
+                ),
+                None => format!("Removed the goal on {}'s '{}'", shown, game),
+            };
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !tag Q "Genshin Impact" gacha / !untag Q "Genshin Impact" gacha
+async fn tag_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+    let add = parts[0] == "!tag";
+
+    if parts.len() != 4 {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!("Usage: {} <username> \"<game name>\" <tag>", parts[0]),
+            )
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let shown = user::get_display_name(guild_id, username);
+    let game = &game_name(guild_id, &parts[2]);
+    let tag = &parts[3];
+
+    let result = if add {
+        user::tag_game(guild_id, username, game, tag)
+    } else {
+        user::untag_game(guild_id, username, game, tag)
+    };
+
+    match result {
+        Ok(game) => {
+            let tag = tag.to_lowercase();
+            let mes = if add {
+                format!("Tagged {}'s '{}' as {}", shown, game, tag)
+            } else {
+                format!("{}'s '{}' is no longer tagged {}", shown, game, tag)
+            };
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !categorytotal gacha
+async fn category_total_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 2 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !categorytotal <tag>")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    match user::get_category_total(guild_id, &parts[1]) {
+        Ok(total) => {
+            let mes = format!(
+                "Everyone's total on {} games: {}",
+                parts[1].to_lowercase(),
+                user::format_money(total)
+            );
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !setdisplayname Q "Quentin the Whale"
+async fn set_display_name_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 3 {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                "Usage: !setdisplayname <username> \"<display name>\" (or none to reset)",
+            )
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let username = &parts[1];
+    let display_name = (parts[2] != "none").then_some(parts[2].as_str());
+
+    match user::set_display_name(guild_id, username, display_name) {
+        Ok(_) => {
+            let mes = match display_name {
+                Some(name) => format!("{} will now be shown as {}", username, name),
+                None => format!("{} will be shown by their username again", username),
+            };
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !setshamerole @Whales (admin only)
+async fn set_shame_role_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 2 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !setshamerole <@role|none>")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let role_id = if parts[1] == "none" {
+        None
+    } else {
+        let role = serenity::utils::parse_role_mention(&parts[1]).filter(|role_id| {
+            msg.guild_id
+                .and_then(|guild_id| ctx.cache.guild(guild_id))
+                .is_some_and(|guild| guild.roles.contains_key(role_id))
+        });
+
+        let Some(role) = role else {
+            msg.channel_id
+                .say(&ctx.http, "Error: That isn't a role in this server")
+                .await
+                .log_send_error();
+            return;
+        };
+        Some(role.get())
+    };
+
+    match config::set_shame_role(guild_id, role_id) {
+        Ok(_) => {
+            let mes = match role_id {
+                // Echoing the mention back would ping the whole role
+                Some(_) => "Threshold callouts will now ping that role".to_string(),
+                None => "Threshold callouts will ping @here again".to_string(),
+            };
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !setshamechannel #hall-of-shame (admin only)
+async fn set_shame_channel_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 2 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !setshamechannel <#channel|none>")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let channel_id = if parts[1] == "none" {
+        None
+    } else {
+        let channel = serenity::utils::parse_channel_mention(&parts[1]).filter(|channel_id| {
+            msg.guild_id
+                .and_then(|guild_id| ctx.cache.guild(guild_id))
+                .is_some_and(|guild| guild.channels.contains_key(channel_id))
+        });
+
+        let Some(channel) = channel else {
+            msg.channel_id
+                .say(&ctx.http, "Error: That isn't a channel in this server")
+                .await
+                .log_send_error();
+            return;
+        };
+        Some(channel.get())
+    };
+
+    match config::set_shame_channel(guild_id, channel_id) {
+        Ok(_) => {
+            let mes = match channel_id {
+                Some(_) => format!("Threshold callouts will now be posted in {}", parts[1]),
+                None => {
+                    "Threshold callouts will be posted where the update happens again".to_string()
+                }
+            };
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !requireconsent on (admin only) - ask new people before tracking them
+async fn require_consent_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+
+    let on = match parts.get(1).map(String::as_str) {
+        Some("on") if parts.len() == 2 => true,
+        Some("off") if parts.len() == 2 => false,
+        _ => {
+            msg.channel_id
+                .say(&ctx.http, "Usage: !requireconsent <on|off>")
+                .await
+                .log_send_error();
+            return;
+        }
+    };
+
+    match config::set_require_consent(guild_id, on) {
+        Ok(_) => {
+            let mes = if on {
+                "New people will be asked before they're tracked"
+            } else {
+                "New people will be tracked right away again"
+            };
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !setthreshold 150 - change when this server's first callout fires (`none` for the default)
+async fn set_threshold_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+
+    if parts.len() != 2 {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !setthreshold <amount|none>")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    match user::set_threshold(guild_id, &parts[1]) {
+        Ok(threshold) => {
+            let mes = format!(
+                "Callouts will now fire when a game total reaches {}",
+                user::format_money(threshold)
+            );
+            msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !reset, then !reset confirm within 30 seconds - wipe the server's data
+async fn reset_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+    let author_id = msg.author.id.get();
+
+    match parts.get(1).map(String::as_str) {
+        None if parts.len() == 1 => {
+            request_confirmation(guild_id, author_id, "reset");
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!(
+                        "⚠️ This deletes every user, game and history entry on this server. Run `!reset confirm` within {} seconds to go ahead",
+                        CONFIRMATION_WINDOW.as_secs()
+                    ),
+                )
+                .await
+                .log_send_error();
+        }
+        Some("confirm") if parts.len() == 2 => {
+            if !take_confirmation(guild_id, author_id, "reset") {
+                msg.channel_id
+                    .say(
+                        &ctx.http,
+                        format!(
+                            "Nothing to confirm. Run `!reset` first, then confirm within {} seconds",
+                            CONFIRMATION_WINDOW.as_secs()
+                        ),
+                    )
+                    .await
+                    .log_send_error();
+                return;
+            }
+
+            match user::reset_all(guild_id) {
+                Ok(()) => {
+                    msg.channel_id
+                        .say(
+                            &ctx.http,
+                            "🧹 All users and games have been wiped. Fresh season!",
+                        )
+                        .await
+                        .log_send_error();
+                }
+                Err(e) => {
+                    msg.channel_id
+                        .say(&ctx.http, format!("Error: {}", e))
+                        .await
+                        .log_send_error();
+                }
+            }
+        }
+        _ => {
+            msg.channel_id
+                .say(&ctx.http, "Usage: !reset, then !reset confirm")
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// !backup - snapshot the server's data before doing anything risky
+async fn backup_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    match user::backup(guild_id) {
+        Ok(path) => {
+            let name = path.rsplit('/').next().unwrap_or(&path);
+            msg.channel_id
+                .say(&ctx.http, format!("🗄️ Backed up to `{}`", name))
+                .await
+                .log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+// How many backups !restore lists
+const BACKUPS_SHOWN: usize = 10;
+
+// !restore lists backups; !restore <name>, then !restore <name> confirm within
+// 30 seconds, puts one back over the current data
+async fn restore_command(ctx: &Context, msg: &Message, guild_id: u64) {
+    if !is_admin(ctx, msg).await {
+        msg.channel_id
+            .say(&ctx.http, "You don't have permission to do that.")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let parts = parse_command_with_quotes(&msg.content);
+    let author_id = msg.author.id.get();
+
+    if parts.len() == 1 {
+        let mes = match user::list_backups(guild_id) {
+            Ok(backups) if backups.is_empty() => {
+                "No backups yet. Make one with !backup".to_string()
+            }
+            Ok(backups) => {
+                let lines: Vec<String> = backups
+                    .iter()
+                    .take(BACKUPS_SHOWN)
+                    .map(|name| format!("• `{}`", name))
+                    .collect();
+                format!("**🗄️ Backups, newest first:**\n{}", lines.join("\n"))
+            }
+            Err(e) => format!("Error: {}", e),
+        };
+        msg.channel_id.say(&ctx.http, mes).await.log_send_error();
+        return;
+    }
+
+    let confirmed = parts.len() == 3 && parts[2] == "confirm";
+    if parts.len() != 2 && !confirmed {
+        msg.channel_id
+            .say(&ctx.http, "Usage: !restore [backup name] [confirm]")
+            .await
+            .log_send_error();
+        return;
+    }
+
+    let name = &parts[1];
+    let action = format!("restore {}", name);
+
+    if !confirmed {
+        if !user::list_backups(guild_id).is_ok_and(|backups| backups.contains(name)) {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!(
+                        "Error: No backup named '{}'. Use !restore to list them",
+                        name
+                    ),
+                )
+                .await
+                .log_send_error();
+            return;
+        }
+
+        request_confirmation(guild_id, author_id, &action);
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!(
+                    "⚠️ This replaces every user and game on this server with `{}`. Run `!restore {} confirm` within {} seconds to go ahead",
+                    name,
+                    name,
+                    CONFIRMATION_WINDOW.as_secs()
+                ),
+            )
+            .await
+            .log_send_error();
+        return;
+    }
+
+    if !take_confirmation(guild_id, author_id, &action) {
+        msg.channel_id
+            .say(
+                &ctx.http,
+                format!(
+                    "Nothing to confirm. Run `!restore {}` first, then confirm within {} seconds",
+                    name,
+                    CONFIRMATION_WINDOW.as_secs()
+                ),
+            )
+            .await
+            .log_send_error();
+        return;
+    }
+
+    match user::restore(guild_id, name) {
+        Ok(count) => {
+            msg.channel_id
+                .say(
+                    &ctx.http,
+                    format!("♻️ Restored {} users from `{}`", count, name),
+                )
+                .await
+                .log_send_error();
+        }
+        Err(e) => {
+            msg.channel_id
+                .say(&ctx.http, format!("Error: {}", e))
+                .await
+                .log_send_error();
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    //Load environment variables
+    dotenv().ok();
+
+    // Logs go to stdout
+    let setting = env::var("RUST_LOG").ok();
+    tracing_subscriber::fmt()
+        .with_env_filter(log_filter(setting.as_deref()))
+        .init();
+
+    // Offline maintenance runs instead of the bot when a subcommand is given
+    if let Some(command) = cli::Cli::parse().command {
+        if let Err(e) = cli::run(command) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    info!("🚀 Starting ShameBot...");
+    messages::shame_messages();
+
+    // Load token environment variable
+    let token = env::var("DISCORD_TOKEN").expect("No token was found in the environment");
+
+    // Set Intents for the bot
+    let intents = GatewayIntents::all();
+
+    // Names and games are typed by users and echoed back, so by default nothing the bot
+    // sends is allowed to ping anyone. Callouts opt back in for their own ping
+    let http = HttpBuilder::new(&token)
+        .default_allowed_mentions(CreateAllowedMentions::new())
+        .build();
+
+    //Create instance of the client, logging in the bot
+    let mut client = serenity::client::ClientBuilder::new_with_http(http, intents)
+        .event_handler(Handler)
+        .await
+        .expect("There was an issue creating the client. Check bot setup");
+
+    spawn_poke_scheduler(client.http.clone());
+    spawn_backup_scheduler(client.http.clone());
+    spawn_hall_of_shame_scheduler(client.http.clone());
+    spawn_shutdown_handler(client.shard_manager.clone());
+
+    // Listen for commands after client is started and bot is logged in
+    if let Err(error) = client.start().await {
+        error!("Client error: {error:?}");
+    }
+}
+
+// On Ctrl+C, save what's loaded and disconnect, which lets client.start() return
+fn spawn_shutdown_handler(shard_manager: Arc<serenity::gateway::ShardManager>) {
+    tokio::spawn(async move {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Couldn't listen for Ctrl+C: {}", e);
+            return;
+        }
+
+        info!("Shutting down...");
+        match user::flush() {
+            Ok(count) => info!("Saved data for {} servers", count),
+            Err(e) => error!("Couldn't save data on shutdown: {}", e),
+        }
+
+        shard_manager.shutdown_all().await;
+        info!("👋 ShameBot shut down cleanly");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shame_gif_setting_is_a_url_or_a_file() {
+        assert!(shame_asset(None).is_none());
+        assert!(shame_asset(Some("  ")).is_none());
+        assert!(matches!(
+            shame_asset(Some("https://example.com/a.gif")),
+            Some(ShameAsset::Url(_))
+        ));
+        assert!(matches!(
+            shame_asset(Some("shame.gif")),
+            Some(ShameAsset::File(_))
+        ));
+    }
+
+    #[test]
+    fn only_server_managers_pass_the_admin_check() {
+        assert!(!grants_admin(Permissions::empty()));
+        assert!(!grants_admin(
+            Permissions::SEND_MESSAGES | Permissions::MANAGE_MESSAGES | Permissions::KICK_MEMBERS
+        ));
+        assert!(grants_admin(Permissions::MANAGE_GUILD));
+        assert!(grants_admin(
+            Permissions::MANAGE_GUILD | Permissions::SEND_MESSAGES
+        ));
+        assert!(grants_admin(Permissions::ADMINISTRATOR));
+    }
+
+    #[test]
+    fn a_confirmation_only_counts_for_whoever_asked() {
+        request_confirmation(7, 100, "reset");
+
+        assert!(!take_confirmation(7, 200, "reset"));
+        assert!(!take_confirmation(8, 100, "reset"));
+        assert!(!take_confirmation(7, 100, "restore"));
+        assert!(take_confirmation(7, 100, "reset"));
+        // Each request confirms once
+        assert!(!take_confirmation(7, 100, "reset"));
+    }
+
+    #[test]
+    fn a_large_update_is_confirmed_for_the_same_amount_only() {
+        // Asked with !updatetotal, confirmed with /updatetotal, under any capitalization
+        request_confirmation(3, 10, &large_update_action("Q", "Tekken 8", 50000));
+        assert!(!take_confirmation(
+            3,
+            10,
+            &large_update_action("q", "Tekken 8", 50001)
+        ));
+        assert!(take_confirmation(
+            3,
+            10,
+            &large_update_action("q", "Tekken 8", 50000)
+        ));
+    }
+
+    #[test]
+    fn only_removegame_is_kept_to_server_managers_as_a_slash_command() {
+        let commands: Vec<serde_json::Value> = slash_commands()
+            .iter()
+            .map(|command| serde_json::to_value(command).unwrap())
+            .collect();
+
+        let names: Vec<&str> = commands
+            .iter()
+            .map(|command| command["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "adduser",
+                "addgame",
+                "updatetotal",
+                "usergames",
+                "gametotal",
+                "usertotal",
+                "removegame",
+                "leaderboard"
+            ]
+        );
+
+        for command in &commands {
+            let restricted = &command["default_member_permissions"];
+            if command["name"] == "removegame" {
+                let manage_server = Permissions::MANAGE_GUILD.bits().to_string();
+                assert_eq!(restricted.as_str(), Some(manage_server.as_str()));
+            } else {
+                assert!(restricted.is_null(), "{} is restricted", command["name"]);
+            }
+        }
+    }
+
+    #[test]
+    fn autocomplete_offers_at_most_25_names_discord_can_show() {
+        let mut games = vec!["x".repeat(AUTOCOMPLETE_CHOICE_LENGTH + 1)];
+        games.extend((0..30).map(|n| format!("Game {n}")));
+
+        let choices = autocomplete_choices(games);
+        assert_eq!(choices.len(), AUTOCOMPLETE_CHOICE_LIMIT);
+        assert_eq!(choices[0], "Game 0");
+        assert!(
+            choices
+                .iter()
+                .all(|game| game.len() <= AUTOCOMPLETE_CHOICE_LENGTH)
+        );
+    }
+
+    #[test]
+    fn mentions_are_read_with_or_without_the_nickname_marker() {
+        assert_eq!(parse_mention("<@123>"), Some(123));
+        assert_eq!(parse_mention("<@!123>"), Some(123));
+        assert_eq!(parse_mention("<@&123>"), None);
+        assert_eq!(parse_mention("<#123>"), None);
+        assert_eq!(parse_mention("Q"), None);
+    }
+
+    #[test]
+    fn the_author_is_found_by_account_then_display_name_then_username() {
+        let _store = user::fresh_store();
+        user::add_user(user::TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        user::add_user(user::TEST_GUILD, "kev_99", "Tekken 8", "10").unwrap();
+
+        let mut author = serenity::model::user::User::default();
+        author.id = serenity::model::id::UserId::new(42);
+        author.name = "q_discord".to_string();
+        author.global_name = Some("Q".to_string());
+        assert_eq!(
+            author_username(user::TEST_GUILD, &author).as_deref(),
+            Some("Q")
+        );
+
+        // The display name linked the account, so a rename doesn't lose them
+        author.global_name = Some("Someone Else".to_string());
+        assert_eq!(
+            author_username(user::TEST_GUILD, &author).as_deref(),
+            Some("Q")
+        );
+
+        let mut kev = serenity::model::user::User::default();
+        kev.id = serenity::model::id::UserId::new(7);
+        kev.name = "kev_99".to_string();
+        assert_eq!(
+            author_username(user::TEST_GUILD, &kev).as_deref(),
+            Some("kev_99")
+        );
+
+        let mut stranger = serenity::model::user::User::default();
+        stranger.id = serenity::model::id::UserId::new(8);
+        stranger.name = "stranger".to_string();
+        assert_eq!(author_username(user::TEST_GUILD, &stranger), None);
+    }
+
+    #[test]
+    fn goal_progress_fills_a_bar_and_flags_the_update_that_passes_it() {
+        assert_eq!(
+            goal_progress(15000, 20000),
+            "▰▰▰▰▰▰▰▱▱▱ $150.00 / $200.00 goal — 75%"
+        );
+        assert_eq!(
+            goal_progress(30000, 20000),
+            "▰▰▰▰▰▰▰▰▰▰ $300.00 / $200.00 goal — 150%"
+        );
+
+        let _store = user::fresh_store();
+        user::add_user(user::TEST_GUILD, "Q", "Tekken 8", "150").unwrap();
+        user::set_goal(user::TEST_GUILD, "Q", "Tekken 8", Some("200")).unwrap();
+        let note = |old, new| goal_exceeded_note(user::TEST_GUILD, "Q", "Tekken 8", old, new);
+        assert!(note(15000, 20500).contains("Goal exceeded"));
+        assert!(note(15000, 20000).is_empty());
+        assert!(note(20500, 21000).is_empty());
+    }
+
+    #[test]
+    fn names_in_a_callout_cant_ping_anyone_themselves() {
+        let game = escape_mentions("@everyone");
+        assert_ne!(game, "@everyone");
+        assert_eq!(game.replace('\u{200B}', ""), "@everyone");
+        assert_eq!(escape_mentions("Tekken 8"), "Tekken 8");
+
+        let callout = messages::shame_messages()
+            .render(
+                user::ThresholdCrossed::Troll,
+                &escape_mentions("@here"),
+                &game,
+                25000,
+                user::TROLL_THRESHOLD,
+            )
+            .unwrap();
+        assert!(!callout.contains("@everyone") && !callout.contains("@here"));
+
+        // Only the callout's own ping is let through: everyone/here and roles, not users
+        let allowed = serde_json::to_value(callout_mentions()).unwrap();
+        let parse = allowed["parse"].as_array().unwrap();
+        assert!(parse.contains(&"everyone".into()) && parse.contains(&"roles".into()));
+        assert!(!parse.contains(&"users".into()));
+    }
+
+    #[test]
+    fn help_looks_commands_up_by_name_or_alias_with_or_without_the_bang() {
+        assert_eq!(
+            find_command("updatetotal").map(|command| command.name),
+            Some("!updatetotal")
+        );
+        assert_eq!(
+            find_command("!UpdateTotal").map(|command| command.name),
+            Some("!updatetotal")
+        );
+        assert_eq!(
+            find_command("whoami").map(|command| command.name),
+            Some("!mystats")
+        );
+        assert_eq!(
+            find_command("!undouser").map(|command| command.name),
+            Some("!undo")
+        );
+        assert!(find_command("!nosuchcommand").is_none());
+
+        let names = command_names();
+        let unique: std::collections::BTreeSet<_> = names.iter().collect();
+        assert_eq!(unique.len(), names.len());
+    }
+
+    #[test]
+    fn destructive_commands_are_flagged_admin_and_say_so_in_help() {
+        for name in [
+            "!removegame",
+            "!removegames",
+            "!deleteuser",
+            "!merge",
+            "!reset",
+            "!restore",
+            "!renameuser",
+            "!linkaccount",
+            "!unlink",
+            "!setcap",
+            "!import",
+            "!renamegame",
+        ] {
+            let command = find_command(name).unwrap();
+            assert!(command.admin, "{name} should be admin only");
+            assert!(help_line(command).ends_with(" (admin)"));
+        }
+
+        let everyday = find_command("!updatetotal").unwrap();
+        assert!(!everyday.admin);
+        assert!(!help_line(everyday).contains("(admin)"));
+    }
+
+    #[test]
+    fn help_fits_in_embed_fields_and_lists_every_command() {
+        let fields = help_fields();
+        assert!(
+            fields
+                .iter()
+                .all(|(_, text)| text.chars().count() <= EMBED_FIELD_LIMIT)
+        );
+
+        let all_text: String = fields.iter().map(|(_, text)| text.as_str()).collect();
+        for command in COMMANDS {
+            assert!(all_text.contains(&format!("`{}`", command.usage)));
+        }
+    }
+
+    #[test]
+    fn the_build_time_is_shown_as_a_date() {
+        assert_eq!(format_build_time("1700000000"), "2023-11-14 22:13:20 UTC");
+        assert_eq!(format_build_time("not a time"), "unknown");
+        assert_ne!(format_build_time(env!("SHAMEBOT_BUILT_AT")), "unknown");
+        assert!(!env!("SHAMEBOT_GIT_COMMIT").is_empty());
+    }
+
+    #[test]
+    fn time_since_an_update_is_rounded_down_to_its_biggest_unit() {
+        let now = 1_000_000_000;
+        assert_eq!(time_ago(now - 30, now), "just now");
+        assert_eq!(time_ago(now - 60, now), "1 minute ago");
+        assert_eq!(time_ago(now - 3 * 3600 - 59, now), "3 hours ago");
+        assert_eq!(time_ago(now - 3 * 86400, now), "3 days ago");
+        assert_eq!(time_ago(now - 400 * 86400, now), "1 year ago");
+        // A clock that's slightly off doesn't make it sound like the future
+        assert_eq!(time_ago(now + 10, now), "just now");
+    }
+
+    #[tokio::test]
+    async fn a_panicking_command_is_contained() {
+        assert!(!run_supervised("!boom", async { panic!("deliberate") }).await);
+        assert!(run_supervised("!fine", async {}).await);
+    }
+
+    #[test]
+    fn logs_default_to_our_info_messages_unless_rust_log_says_otherwise() {
+        use tracing::level_filters::LevelFilter;
+
+        assert_eq!(log_filter(None).max_level_hint(), Some(LevelFilter::INFO));
+        assert_eq!(
+            log_filter(Some("debug")).max_level_hint(),
+            Some(LevelFilter::DEBUG)
+        );
+        // A setting that doesn't parse falls back to the default
+        assert_eq!(
+            log_filter(Some("shame_bot=loud")).to_string(),
+            log_filter(None).to_string()
+        );
+
+        // A failed send is logged, not passed on
+        Err::<(), _>(serenity::Error::Other("rate limited")).log_send_error();
+    }
+
+    #[test]
+    fn commands_are_matched_by_their_exact_name() {
+        assert_eq!(command_name("!usertotal Q"), Some("!usertotal"));
+        assert_eq!(command_name("!removegames Q \"*\""), Some("!removegames"));
+        assert_eq!(command_name("!removegame Q Halo"), Some("!removegame"));
+        assert_eq!(
+            command_name("!leaderboarddiff a b"),
+            Some("!leaderboarddiff")
+        );
+        assert_eq!(command_name("!usertotalxyz Q"), None);
+        assert_eq!(command_name("!addgamemode Q"), None);
+        assert_eq!(command_name("usertotal Q"), None);
+        assert_eq!(command_name(""), None);
+    }
+
+    #[test]
+    fn unknown_commands_get_a_suggestion_and_chat_gets_nothing() {
+        assert_eq!(
+            unknown_command_reply("!addgaem Q \"Tekken 8\" 50").as_deref(),
+            Some("Unknown command `!addgaem`. Did you mean `!addgame`? Try `!help`.")
+        );
+        assert_eq!(
+            unknown_command_reply("!xyzzyplugh").as_deref(),
+            Some("Unknown command `!xyzzyplugh`. Try `!help`.")
+        );
+        assert_eq!(unknown_command_reply("!addgame Q Halo 5"), None);
+        assert_eq!(unknown_command_reply("good game everyone!"), None);
+        assert_eq!(unknown_command_reply("!"), None);
+    }
+
+    #[test]
+    fn smart_quotes_group_words_like_straight_ones() {
+        assert_eq!(
+            parse_command_with_quotes("!gametotal Q \u{201C}Tekken 8\u{201D}"),
+            ["!gametotal", "Q", "Tekken 8"]
+        );
+        assert_eq!(
+            parse_command_with_quotes("!gametotal Q \u{201E}Tekken 8\u{201F}"),
+            parse_command_with_quotes("!gametotal Q \"Tekken 8\"")
+        );
+    }
+
+    #[test]
+    fn quotes_must_be_balanced_and_can_be_escaped() {
+        assert_eq!(
+            split_command("!adduser Q \"Tekken 8\" 200").unwrap(),
+            ["!adduser", "Q", "Tekken 8", "200"]
+        );
+        assert_eq!(
+            split_command("!adduser Q \"Tekken 8 200").unwrap_err(),
+            "Unmatched quote in command"
+        );
+        assert_eq!(
+            split_command(r#"!addgame Q "The \"Best\" Game" 5"#).unwrap(),
+            ["!addgame", "Q", "The \"Best\" Game", "5"]
+        );
+        assert!(parse_command_with_quotes("!adduser Q \"Tekken").is_empty());
+    }
+
+    #[test]
+    fn long_replies_are_split_between_lines() {
+        let line = "y".repeat(150);
+        let text = vec![line.as_str(); 30].join("\n");
+        let messages = split_lines(&text, DISCORD_MESSAGE_LIMIT);
+
+        assert_eq!(messages.len(), 3);
+        assert!(
+            messages
+                .iter()
+                .all(|message| message.chars().count() <= DISCORD_MESSAGE_LIMIT)
+        );
+        assert_eq!(messages.join("\n"), text);
+
+        // A single line longer than a message is cut up on its own
+        let huge = "z".repeat(DISCORD_MESSAGE_LIMIT + 10);
+        assert_eq!(split_lines(&huge, DISCORD_MESSAGE_LIMIT).len(), 2);
+        assert!(split_lines("", DISCORD_MESSAGE_LIMIT).is_empty());
+    }
+
+    #[test]
+    fn a_hundred_users_are_listed_in_pages_under_the_limit() {
+        let users: Vec<user::User> = (0..100)
+            .map(|i| {
+                serde_json::from_value(serde_json::json!({
+                    "user": format!("player{:03}", i),
+                    "games": {"Tekken 8": 1000, "Street Fighter 6": 2500},
+                }))
+                .unwrap()
+            })
+            .collect();
+
+        let pages = render_user_list(&users);
+        assert!(pages.len() > 1);
+        assert!(
+            pages
+                .iter()
+                .all(|page| page.chars().count() <= DISCORD_MESSAGE_LIMIT)
+        );
+        assert!(pages[0].starts_with(&format!("**All Users (page 1/{}):**", pages.len())));
+        // Every user is listed once, never split across pages
+        for i in 0..100 {
+            let name = format!("**player{:03}**", i);
+            assert_eq!(pages.iter().filter(|page| page.contains(&name)).count(), 1);
+        }
+
+        assert_eq!(render_user_list(&users[..1]).len(), 1);
+        assert!(render_user_list(&users[..1])[0].starts_with("**All Users:**\n"));
+    }
+
+    #[test]
+    fn combined_rows_pair_each_total_with_this_weeks_spending() {
+        let all_time = vec![
+            ("Q".to_string(), 500),
+            ("Bob".to_string(), 300),
+            ("Amy".to_string(), 100),
+        ];
+        let this_week = vec![("Amy".to_string(), 80), ("Bob".to_string(), 5)];
+
+        assert_eq!(
+            combined_rows(&all_time, &this_week, BoardSort::AllTime),
+            vec![
+                ("Q".to_string(), 500, 0),
+                ("Bob".to_string(), 300, 5),
+                ("Amy".to_string(), 100, 80),
+            ]
+        );
+        assert_eq!(
+            combined_rows(&all_time, &this_week, BoardSort::ThisWeek),
+            vec![
+                ("Amy".to_string(), 100, 80),
+                ("Bob".to_string(), 300, 5),
+                ("Q".to_string(), 500, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn typos_suggest_the_intended_command() {
+        assert_eq!(
+            suggest_command("!updatetotl", &command_names()).as_deref(),
+            Some("!updatetotal")
+        );
+        assert_eq!(
+            suggest_command("!uptotal", &command_names()).as_deref(),
+            Some("!updatetotal")
+        );
+        assert_eq!(
+            suggest_command("!adgame", &command_names()).as_deref(),
+            Some("!addgame")
+        );
+        assert_eq!(suggest_command("!xyzzyplugh", &command_names()), None);
+        assert_eq!(suggest_command("!zq", &command_names()), None);
+    }
+
+    #[test]
+    fn backups_only_post_when_the_data_changed() {
+        let mut last_hash = None;
+        assert!(backup_needed("one", &mut last_hash));
+        assert!(!backup_needed("one", &mut last_hash));
+        assert!(backup_needed("two", &mut last_hash));
+        assert!(backup_needed("one", &mut last_hash));
+    }
+
+    #[test]
+    fn hall_of_shame_posts_weekly_unless_given_a_positive_interval() {
+        let week = Duration::from_secs(HALL_OF_SHAME_INTERVAL);
+        assert_eq!(interval_setting(None, HALL_OF_SHAME_INTERVAL), week);
+        assert_eq!(interval_setting(Some("0"), HALL_OF_SHAME_INTERVAL), week);
+        assert_eq!(
+            interval_setting(Some("daily"), HALL_OF_SHAME_INTERVAL),
+            week
+        );
+        assert_eq!(interval_setting(Some("-60"), HALL_OF_SHAME_INTERVAL), week);
+        assert_eq!(
+            interval_setting(Some("3600"), HALL_OF_SHAME_INTERVAL),
+            Duration::from_secs(3600)
+        );
+    }
+
+    #[test]
+    fn reply_delete_delay_needs_a_positive_number_of_seconds() {
+        assert_eq!(reply_delete_delay(None), None);
+        assert_eq!(reply_delete_delay(Some("0")), None);
+        assert_eq!(reply_delete_delay(Some("soon")), None);
+        assert_eq!(reply_delete_delay(Some("-5")), None);
+        assert_eq!(
+            reply_delete_delay(Some(" 30 ")),
+            Some(Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn leaderboard_pages_stop_at_the_first_and_last_page() {
+        let (message, invoker, now) = (233_001, 7, 1000);
+        let pages = page_count(25);
+        assert_eq!(pages, 3);
+        track_board_pages(message, invoker, now);
+
+        assert_eq!(
+            turn_board_page(message, invoker, false, pages, now, false),
+            None
+        );
+        assert_eq!(
+            turn_board_page(message, invoker, true, pages, now, false),
+            Some(1)
+        );
+        assert_eq!(
+            turn_board_page(message, invoker, true, pages, now, false),
+            Some(2)
+        );
+        assert_eq!(
+            turn_board_page(message, invoker, true, pages, now, false),
+            None
+        );
+        assert_eq!(
+            turn_board_page(message, invoker, false, pages, now, false),
+            Some(1)
+        );
+
+        // Only the invoker can page unless anyone is allowed to
+        assert_eq!(turn_board_page(message, 8, true, pages, now, false), None);
+        assert_eq!(turn_board_page(message, 8, true, pages, now, true), Some(2));
+
+        // And the message stops turning once it has sat idle long enough
+        let later = now + BOARD_PAGE_EXPIRY;
+        assert_eq!(
+            turn_board_page(message, invoker, false, pages, later, false),
+            None
+        );
+        assert_eq!(
+            turn_board_page(233_002, invoker, true, pages, now, false),
+            None
+        );
+    }
+
+    #[test]
+    fn leaderboard_pages_keep_overall_ranks() {
+        let leaderboard: Vec<(String, i64)> = (0..12)
+            .map(|i| (format!("user{i}"), 1200 - i * 100))
+            .collect();
+        assert_eq!(page_count(0), 1);
+        assert_eq!(page_count(BOARD_PAGE_SIZE), 1);
+
+        let second = leaderboard_page(user::TEST_GUILD, &leaderboard, 1);
+        assert!(second.starts_with("**🏆 Leaderboard** (page 2 of 2)"));
+        assert_eq!(second.lines().count(), 3);
+        assert!(second.contains(&roast::board_line(11, "user10", 200)));
+    }
+
+    #[test]
+    fn velocity_callouts_fire_once_per_burst() {
+        assert!(crossed_velocity_limit(300, 300, 300));
+        assert!(crossed_velocity_limit(350, 100, 300));
+        // Already past the limit before this update
+        assert!(!crossed_velocity_limit(450, 100, 300));
+        assert!(!crossed_velocity_limit(299, 299, 300));
+        assert!(!crossed_velocity_limit(300, -20, 300));
+    }
+
+    #[test]
+    fn callouts_follow_each_servers_shame_role_and_channel() {
+        let _store = user::fresh_store();
+        let here = ChannelId::new(1);
+        let other = user::TEST_GUILD + 1;
+        assert_eq!(
+            callout_target(user::TEST_GUILD, here, "Q"),
+            Some((here, "@here".to_string()))
+        );
+
+        config::set_shame_role(user::TEST_GUILD, Some(42)).unwrap();
+        config::set_shame_channel(user::TEST_GUILD, Some(7)).unwrap();
+        assert_eq!(
+            callout_target(user::TEST_GUILD, here, "Q"),
+            Some((ChannelId::new(7), "<@&42>".to_string()))
+        );
+        // Another server's callouts are left as they were
+        assert_eq!(
+            callout_target(other, here, "Q"),
+            Some((here, "@here".to_string()))
+        );
+
+        config::set_shame_role(user::TEST_GUILD, None).unwrap();
+        config::set_shame_channel(user::TEST_GUILD, None).unwrap();
+        assert_eq!(
+            callout_target(user::TEST_GUILD, here, "Q").unwrap().1,
+            "@here"
+        );
+    }
+
+    #[test]
+    fn leaderboard_windows_are_recognised() {
+        assert_eq!(spend_window("weekend"), Some(user::SpendWindow::Weekend));
+        assert_eq!(spend_window("nights"), Some(user::SpendWindow::Nights));
+        assert_eq!(spend_window("weighted"), None);
+        assert_eq!(spend_window("5"), None);
+    }
+
+    #[test]
+    fn window_leaderboards_rank_by_spending_in_the_window() {
+        let _store = user::fresh_store();
+        for username in ["Q", "Bob", "Amy"] {
+            user::add_user(user::TEST_GUILD, username, "Tekken 8", "10").unwrap();
+        }
+        // Saturday noon, Sunday 1am and Thursday noon, all UTC
+        for (username, timestamp, delta) in [
+            ("Q", 1_792_238_400, 50),
+            ("Bob", 1_792_285_200, 30),
+            ("Amy", 1_792_065_600, 90),
+        ] {
+            user::record_transaction(
+                user::TEST_GUILD,
+                user::Transaction {
+                    timestamp,
+                    username: username.to_string(),
+                    game: "Tekken 8".to_string(),
+                    delta,
+                    total: 0,
+                    undoes: None,
+                },
+            )
+            .unwrap();
+        }
+
+        assert_eq!(
+            window_leaderboard(user::TEST_GUILD, user::SpendWindow::Weekend).unwrap(),
+            vec![("Q".to_string(), 50), ("Bob".to_string(), 30)]
+        );
+        assert_eq!(
+            window_leaderboard(user::TEST_GUILD, user::SpendWindow::Nights).unwrap(),
+            vec![("Bob".to_string(), 30)]
+        );
+    }
+
+    #[test]
+    fn callouts_with_the_consent_gate_only_reach_opted_in_users() {
+        let _store = user::fresh_store();
+        let here = ChannelId::new(1);
+        user::add_user(user::TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        user::request_consent(user::PendingConsent {
+            discord_id: 5,
+            guild_id: user::TEST_GUILD,
+            username: "Amy".to_string(),
+            game: "Tekken 8".to_string(),
+            total: "10".to_string(),
+            requested_at: user::now(),
+        })
+        .unwrap();
+        user::answer_consent(5, true, user::now()).unwrap();
+
+        config::set_require_consent(user::TEST_GUILD, true).unwrap();
+        assert_eq!(callout_target(user::TEST_GUILD, here, "Q"), None);
+        assert!(!config::requires_consent(user::TEST_GUILD + 1));
+        assert_eq!(
+            callout_target(user::TEST_GUILD, here, "Amy"),
+            Some((here, "@here".to_string()))
+        );
+
+        // With the gate off everyone is called out, as before
+        config::set_require_consent(user::TEST_GUILD, false).unwrap();
+        assert!(callout_target(user::TEST_GUILD, here, "Q").is_some());
     }
 }
@@ -0,0 +1,150 @@
+use serde::Deserialize;
+use std::sync::OnceLock;
+use tracing::{info, warn};
+
+use crate::user::{ThresholdCrossed, format_money};
+
+// Where the callout wording is read from, relative to the working directory
+// unless SHAMEBOT_MESSAGES_PATH says otherwise
+const DEFAULT_MESSAGES_FILE: &str = "messages.json";
+
+fn messages_file() -> String {
+    std::env::var("SHAMEBOT_MESSAGES_PATH").unwrap_or_else(|_| DEFAULT_MESSAGES_FILE.to_string())
+}
+
+// The threshold callouts, with {user}, {game}, {total} and {threshold} filled in when sent.
+// Anything missing from the file keeps its default
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ShameMessages {
+    pub troll: String,
+    pub super_troll: String,
+}
+
+impl Default for ShameMessages {
+    fn default() -> Self {
+        ShameMessages {
+            troll: "🚨 {user} just crossed {threshold} in {game}! 💸".to_string(),
+            super_troll:
+                "🚨🚨🚨 {user} just blew past {threshold} in {game}! Somebody take their card away 🐳"
+                    .to_string(),
+        }
+    }
+}
+
+impl ShameMessages {
+    // Read the messages file, falling back to the defaults if it's missing or unreadable
+    fn load() -> Self {
+        let path = messages_file();
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(_) => return ShameMessages::default(),
+        };
+
+        match serde_json::from_str(&contents) {
+            Ok(messages) => {
+                info!("Loaded shame messages from '{}'", path);
+                messages
+            }
+            Err(e) => {
+                warn!(
+                    "Couldn't parse '{}', using the default shame messages: {}",
+                    path, e
+                );
+                ShameMessages::default()
+            }
+        }
+    }
+
+    // The callout for a crossed threshold, or None when nothing was crossed
+    pub fn render(
+        &self,
+        crossed: ThresholdCrossed,
+        user: &str,
+        game: &str,
+        total: i64,
+        threshold: i64,
+    ) -> Option<String> {
+        let template = match crossed {
+            ThresholdCrossed::Troll => &self.troll,
+            ThresholdCrossed::SuperTroll => &self.super_troll,
+            ThresholdCrossed::None => return None,
+        };
+
+        let values = [
+            ("{user}", user.to_string()),
+            ("{game}", game.to_string()),
+            ("{total}", format_money(total)),
+            ("{threshold}", format_money(threshold)),
+        ];
+
+        // One pass over the template, so a name containing `{game}` isn't filled in again
+        let mut rendered = String::new();
+        let mut rest = template.as_str();
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            match values.iter().find(|(key, _)| rest.starts_with(key)) {
+                Some((key, value)) => {
+                    rendered.push_str(value);
+                    rest = &rest[key.len()..];
+                }
+                None => {
+                    rendered.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        rendered.push_str(rest);
+
+        Some(rendered)
+    }
+}
+
+static SHAME_MESSAGES: OnceLock<ShameMessages> = OnceLock::new();
+
+// The configured callouts, read from disk the first time they're needed
+pub fn shame_messages() -> &'static ShameMessages {
+    SHAME_MESSAGES.get_or_init(ShameMessages::load)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn callouts_fill_in_every_placeholder_once() {
+        let messages = ShameMessages {
+            troll: "{user} hit {threshold} on {game} ({total}) {nope}".to_string(),
+            ..ShameMessages::default()
+        };
+
+        assert_eq!(
+            messages
+                .render(ThresholdCrossed::Troll, "Q", "Tekken 8", 12345, 20000)
+                .unwrap(),
+            "Q hit $200.00 on Tekken 8 ($123.45) {nope}"
+        );
+        // A name that looks like a placeholder is left as it is
+        assert_eq!(
+            messages
+                .render(ThresholdCrossed::Troll, "{game}", "Halo", 0, 20000)
+                .unwrap(),
+            "{game} hit $200.00 on Halo ($0.00) {nope}"
+        );
+        assert!(
+            messages
+                .render(ThresholdCrossed::None, "Q", "Halo", 0, 20000)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn missing_fields_keep_the_default_wording() {
+        let messages: ShameMessages =
+            serde_json::from_str(r#"{"troll": "{user} again?"}"#).unwrap();
+        assert_eq!(messages.troll, "{user} again?");
+        assert_eq!(messages.super_troll, ShameMessages::default().super_troll);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::user::{SUPER_TROLL_THRESHOLD, TROLL_THRESHOLD, format_money};
+
+// How hard the bot goes in on someone, based on what they've spent
+pub struct RoastTier {
+    pub min_total: i64,
+    pub name: &'static str,
+    pub emoji: &'static str,
+    pub roast: &'static str,
+}
+
+// Ordered from the biggest spenders down so the first match wins
+pub const ROAST_TIERS: [RoastTier; 3] = [
+    RoastTier {
+        min_total: SUPER_TROLL_THRESHOLD,
+        name: "whale",
+        emoji: "🐳",
+        roast: "At this point the devs should be sending YOU a thank-you card.",
+    },
+    RoastTier {
+        min_total: TROLL_THRESHOLD,
+        name: "spender",
+        emoji: "🐬",
+        roast: "That's not a hobby anymore, that's a subscription to disappointment.",
+    },
+    RoastTier {
+        min_total: i64::MIN,
+        name: "casual",
+        emoji: "🐟",
+        roast: "Barely a nibble. The battle pass is still waiting for you.",
+    },
+];
+
+// Find the tier for a total
+pub fn roast_tier(total: i64) -> &'static RoastTier {
+    ROAST_TIERS
+        .iter()
+        .find(|tier| total >= tier.min_total)
+        .unwrap_or(&ROAST_TIERS[ROAST_TIERS.len() - 1])
+}
+
+// Tier emoji shown next to a user on ranking boards
+pub fn tier_badge(total: i64) -> &'static str {
+    roast_tier(total).emoji
+}
+
+// One numbered line of a ranking board, badged by the user's grand total
+pub fn board_line(rank: usize, username: &str, total: i64) -> String {
+    format!(
+        "{}. {} {} - {}",
+        rank,
+        tier_badge(total),
+        username,
+        format_money(total)
+    )
+}
+
+// A ranking line with medals in place of numbers for the top three
+pub fn podium_line(rank: usize, username: &str, total: i64) -> String {
+    let medal = match rank {
+        1 => "🥇",
+        2 => "🥈",
+        3 => "🥉",
+        _ => return board_line(rank, username, total),
+    };
+
+    format!(
+        "{} {} {} - {}",
+        medal,
+        tier_badge(total),
+        username,
+        format_money(total)
+    )
+}
+
+// Build the !whale spotlight message for the biggest spender
+pub fn whale_callout(username: &str, total: i64, top_game: &str, top_game_total: i64) -> String {
+    let tier = roast_tier(total);
+
+    format!(
+        "🚨🐋 **WHALE ALERT** 🐋🚨\n**{}** sits atop the server with {} spent, {} of it on '{}'!\n{} Tier: {}. {}",
+        username,
+        format_money(total),
+        format_money(top_game_total),
+        top_game,
+        tier.emoji,
+        tier.name,
+        tier.roast
+    )
+}
+
+// Build the scheduled daily poke for whoever is on top
+pub fn poke_message(username: &str, total: i64) -> String {
+    let tier = roast_tier(total);
+
+    format!(
+        "👉 Daily poke for **{}**: {} and counting. {} {}",
+        username,
+        format_money(total),
+        tier.emoji,
+        tier.roast
+    )
+}
+
+// Build the "slow down" callout for a burst of spending in a short window
+pub fn slow_down_callout(username: &str, spent: i64, window_minutes: u64) -> String {
+    format!(
+        "🐢 Slow down, **{}**! That's {} in the last {} minutes. Your wallet needs a breather",
+        username,
+        format_money(spent),
+        window_minutes
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiers_start_at_the_thresholds() {
+        assert_eq!(roast_tier(SUPER_TROLL_THRESHOLD).name, "whale");
+        assert_eq!(roast_tier(SUPER_TROLL_THRESHOLD - 1).name, "spender");
+        assert_eq!(roast_tier(TROLL_THRESHOLD).name, "spender");
+        assert_eq!(roast_tier(TROLL_THRESHOLD - 1).name, "casual");
+        assert_eq!(roast_tier(-50).name, "casual");
+    }
+
+    #[test]
+    fn whale_callout_names_the_user_and_their_top_game() {
+        let callout = whale_callout("Q", 60000, "Tekken 8", 45050);
+        assert!(callout.contains("**Q**"));
+        assert!(callout.contains("$600.00 spent, $450.50 of it on 'Tekken 8'"));
+        assert!(callout.contains("Tier: whale"));
+    }
+
+    #[test]
+    fn poke_message_uses_the_tier_roast() {
+        let poke = poke_message("Q", 25000);
+        assert!(poke.contains("**Q**: $250.00"));
+        assert!(poke.contains(ROAST_TIERS[1].roast));
+    }
+
+    #[test]
+    fn board_lines_carry_the_tier_badge() {
+        assert_eq!(tier_badge(0), "🐟");
+        assert_eq!(tier_badge(TROLL_THRESHOLD), "🐬");
+        assert_eq!(tier_badge(SUPER_TROLL_THRESHOLD + 100), "🐳");
+        assert_eq!(board_line(2, "Q", 25000), "2. 🐬 Q - $250.00");
+    }
+
+    #[test]
+    fn podium_lines_give_the_top_three_medals() {
+        assert_eq!(podium_line(1, "Q", 60000), "🥇 🐳 Q - $600.00");
+        assert_eq!(podium_line(3, "Q", 0), "🥉 🐟 Q - $0.00");
+        assert_eq!(podium_line(4, "Q", 0), board_line(4, "Q", 0));
+    }
+}
@@ -1,224 +1,6126 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard, OnceLock, RwLock};
+use tracing::info;
 
-pub const TROLL_THRESHOLD: i32 = 200; // Start pinging at 200 dollars
-pub const SUPER_TROLL_THRESHOLD: i32 = 500; // Lay into the user at this point
+use crate::db;
+use crate::fuzzy;
+
+// All money is stored in cents
+pub const TROLL_THRESHOLD: i64 = 200 * 100; // Start pinging at 200 dollars by default
+pub const SUPER_TROLL_THRESHOLD: i64 = 500 * 100; // Lay into the user at this point
 
 // New structure: User has multiple games
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct User {
     pub user: String,
-    pub games: HashMap<String, i32>, // game_name -> total
+    pub games: HashMap<String, GameRecord>, // game_name -> total and timestamps
+    #[serde(default)]
+    pub alias_of: Option<String>, // shared account: commands redirect to this user
+    #[serde(default)]
+    pub poke_opt_out: bool, // skip this user in the scheduled daily poke
+    #[serde(default)]
+    pub caps: HashMap<String, i64>, // game_name -> hard spending cap in cents
+    #[serde(default)]
+    pub goals: HashMap<String, i64>, // game_name -> spending budget in cents, warned past
+    #[serde(default)]
+    pub tags: HashMap<String, Vec<String>>, // game_name -> lowercase categories like `gacha`
+    #[serde(default)]
+    pub display_name: Option<String>, // shown in messages; `user` stays the lookup key
+    #[serde(default)]
+    pub consented: bool, // agreed to be tracked with !consent yes
+    #[serde(default)]
+    pub in_cents: bool, // false for records saved back when totals were whole dollars
+    #[serde(default)]
+    pub discord_id: Option<u64>, // the Discord account mentions of this user resolve to
+    #[serde(default)]
+    pub archived: bool, // kept for the record but left out of listings and rankings
 }
 
-type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+// One of a user's games: what's been spent on it and when
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(from = "StoredGame")]
+pub struct GameRecord {
+    pub total: i64,        // in cents
+    pub created_at: i64,   // Unix seconds, UTC; 0 when unknown
+    pub last_updated: i64, // Unix seconds, UTC; 0 when unknown
+}
 
-// Helper functions
-fn load_user_file() -> Vec<User> {
-    match std::fs::read_to_string("../users.json") {
-        Ok(contents) => {
-            if contents.is_empty() {
-                Vec::new()
-            } else {
-                serde_json::from_str(&contents).unwrap_or_else(|_| Vec::new())
-            }
+impl GameRecord {
+    // A game being tracked as of now
+    pub fn new(total: i64) -> Self {
+        let now = now();
+        GameRecord {
+            total,
+            created_at: now,
+            last_updated: now,
         }
-        Err(_) => Vec::new(),
+    }
+
+    // Set a new total, marking the game as changed just now
+    fn set_total(&mut self, total: i64) {
+        self.total = total;
+        self.last_updated = now();
     }
 }
 
-fn save_users_to_file(users: &Vec<User>) -> Result<()> {
-    let json = serde_json::to_string_pretty(users)?;
-    std::fs::write("../users.json", json)?;
-    Ok(())
+// A game as it reads back from JSON. Files from before GameRecord hold just the total
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredGame {
+    Total(i64),
+    Record {
+        total: i64,
+        #[serde(default)]
+        created_at: i64,
+        #[serde(default)]
+        last_updated: i64,
+    },
 }
 
-// Function to add a new game to an existing user
-pub fn add_game(username: &str, game: &str, starting_total: &str) -> Result<()> {
-    let mut users = load_user_file();
-    let total: i32 = starting_total
-        .parse()
-        .map_err(|_| "Invalid number for starting total")?;
+impl From<StoredGame> for GameRecord {
+    fn from(stored: StoredGame) -> Self {
+        match stored {
+            // No way to know when these were made, so leave the timestamps unknown
+            StoredGame::Total(total) => GameRecord {
+                total,
+                ..GameRecord::default()
+            },
+            StoredGame::Record {
+                total,
+                created_at,
+                last_updated,
+            } => GameRecord {
+                total,
+                created_at,
+                last_updated,
+            },
+        }
+    }
+}
 
-    // Find the user
-    let user_found = users.iter_mut().find(|user| user.user == username);
+// Which shame threshold an update pushed a game total past, if any
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThresholdCrossed {
+    None,
+    Troll,
+    SuperTroll, // wins when one update jumps past both
+}
 
-    match user_found {
-        Some(user) => {
-            // User exists - check if game already exists
-            if user.games.contains_key(game) {
-                return Err(format!("User {} already has game '{}'", username, game).into());
+impl User {
+    // Name to show in messages, falling back to the lookup key
+    pub fn display_name(&self) -> &str {
+        self.display_name.as_deref().unwrap_or(&self.user)
+    }
+}
+
+// One change to a game total, as recorded by update_total
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Transaction {
+    pub timestamp: i64, // Unix seconds, UTC
+    pub username: String,
+    pub game: String,
+    pub delta: i64,
+    pub total: i64, // the game's total after the change
+    #[serde(default)]
+    pub undoes: Option<i64>, // the logged change this one reverted, for !undo
+}
+
+// How !export writes out a user's data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json, // the user's stored record
+    Csv,  // `game,total` rows, totals in dollars
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Csv => "csv",
+        }
+    }
+}
+
+impl Transaction {
+    // When the change happened, e.g. `2025-08-14 21:07 UTC`
+    pub fn when(&self) -> String {
+        match chrono::DateTime::from_timestamp(self.timestamp, 0) {
+            Some(time) => time.format("%Y-%m-%d %H:%M UTC").to_string(),
+            None => "unknown time".to_string(),
+        }
+    }
+}
+
+// How one user's place on the leaderboard differs between two snapshots.
+// A rank of None means they weren't on that board
+#[derive(Clone, Debug, PartialEq)]
+pub struct RankChange {
+    pub username: String,
+    pub before: Option<usize>,
+    pub after: Option<usize>,
+}
+
+// Parts of the week !leaderboard can rank spending in, going by the server's local time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpendWindow {
+    Weekend,    // Saturday and Sunday
+    Weekdays,   // Monday to Friday
+    Nights,     // from NIGHT_STARTS_AT until NIGHT_ENDS_AT
+    Weeknights, // nights on Monday to Friday
+}
+
+// Night runs from 10pm until 6am, by the day the change was made on
+const NIGHT_STARTS_AT: u32 = 22;
+const NIGHT_ENDS_AT: u32 = 6;
+
+impl SpendWindow {
+    // Whether a moment in local time falls in this window
+    pub fn contains(&self, time: chrono::DateTime<chrono::FixedOffset>) -> bool {
+        use chrono::{Datelike, Timelike};
+
+        let weekend = matches!(time.weekday(), chrono::Weekday::Sat | chrono::Weekday::Sun);
+        let night = time.hour() >= NIGHT_STARTS_AT || time.hour() < NIGHT_ENDS_AT;
+        match self {
+            SpendWindow::Weekend => weekend,
+            SpendWindow::Weekdays => !weekend,
+            SpendWindow::Nights => night,
+            SpendWindow::Weeknights => night && !weekend,
+        }
+    }
+}
+
+// Everything that can go wrong in here, so callers can tell the failures apart
+#[derive(Debug)]
+pub enum UserError {
+    UserNotFound(String),
+    UserAlreadyExists(String),
+    GameNotFound {
+        user: String,
+        game: String,
+        suggestion: Option<String>, // one of the user's games that looks like a typo of it
+    },
+    GameAlreadyExists {
+        user: String,
+        game: String,
+    },
+    NoGames(String),       // the user has no games at all
+    InvalidAmount(String), // what was wrong with it
+    InvalidName(String),   // what was wrong with it
+    NotFound(String),      // something other than a user or game wasn't there
+    Rejected(String),      // the change would break a rule, like a cap or going below $0
+    Io {
+        context: String,
+        source: std::io::Error,
+    },
+    Serde {
+        context: String,
+        source: serde_json::Error,
+    },
+    Database(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl std::fmt::Display for UserError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UserError::UserNotFound(user) => write!(f, "User '{}' not found", user),
+            UserError::UserAlreadyExists(user) => write!(f, "User '{}' already exists", user),
+            UserError::GameNotFound {
+                user,
+                game,
+                suggestion,
+            } => {
+                write!(f, "User '{}' doesn't have game '{}'", user, game)?;
+                match suggestion {
+                    Some(suggestion) => write!(f, ". Did you mean '{}'?", suggestion),
+                    None => Ok(()),
+                }
             }
+            UserError::GameAlreadyExists { user, game } => {
+                write!(f, "User {} already has game '{}'", user, game)
+            }
+            UserError::NoGames(user) => write!(f, "User '{}' has no games", user),
+            UserError::InvalidAmount(message)
+            | UserError::InvalidName(message)
+            | UserError::NotFound(message)
+            | UserError::Rejected(message) => write!(f, "{}", message),
+            UserError::Io { context, source } => write!(f, "{}: {}", context, source),
+            UserError::Serde { context, source } => write!(f, "{}: {}", context, source),
+            UserError::Database(e) => write!(f, "{}", e),
+        }
+    }
+}
 
-            // Add new game to existing user
-            user.games.insert(game.to_string(), total);
-            println!(
-                "Added game '{}' with total {} to user '{}'",
-                game, total, username
-            );
+impl std::error::Error for UserError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            UserError::Io { source, .. } => Some(source),
+            UserError::Serde { source, .. } => Some(source),
+            UserError::Database(e) => Some(e.as_ref()),
+            _ => None,
         }
-        None => {
-            return Err(format!("User '{}' not found! Use !adduser first", username).into());
+    }
+}
+
+// The database layer still reports plain boxed errors
+impl From<Box<dyn std::error::Error + Send + Sync>> for UserError {
+    fn from(e: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        UserError::Database(e)
+    }
+}
+
+impl From<serde_json::Error> for UserError {
+    fn from(source: serde_json::Error) -> Self {
+        UserError::Serde {
+            context: "Couldn't convert data to JSON".to_string(),
+            source,
         }
     }
+}
 
-    save_users_to_file(&users)?;
-    Ok(())
+type Result<T> = std::result::Result<T, UserError>;
+
+// Attach what was being done to a file error
+fn io_error(context: String) -> impl FnOnce(std::io::Error) -> UserError {
+    move |source| UserError::Io { context, source }
 }
 
-// Function to add a completely new user with their first game
-pub fn add_user(username: &str, game: &str, starting_total: &str) -> Result<()> {
-    let mut users = load_user_file();
-    let total: i32 = starting_total
-        .parse()
-        .map_err(|_| "Invalid number for starting total")?;
+// Attach what was being read or written to a JSON error
+fn serde_error(context: String) -> impl FnOnce(serde_json::Error) -> UserError {
+    move |source| UserError::Serde { context, source }
+}
 
-    // Check if user already exists
-    if users.iter().any(|user| user.user == username) {
-        return Err(format!(
-            "User '{}' already exists! Use !addgame to add more games",
-            username
-        )
-        .into());
+// Every read-modify-write of the stored data holds this, so two commands landing
+// at once can't both load the old data and have one update overwrite the other.
+// Plain reads don't need it: the database and the files alike are replaced all at
+// once, so a read sees either the old data or the new
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+pub(crate) fn lock_store() -> MutexGuard<'static, ()> {
+    // A panic mid-command doesn't leave a save half-done, so carry on
+    STORE_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// Helper functions
+// Where users and their games were kept before the database, relative to the working
+// directory unless SHAMEBOT_DATA_PATH says otherwise. The first server to use the bot
+// imports it
+#[cfg(not(test))]
+const DEFAULT_USERS_FILE: &str = "users.json";
+
+#[cfg(not(test))]
+fn users_file() -> String {
+    std::env::var("SHAMEBOT_DATA_PATH").unwrap_or_else(|_| DEFAULT_USERS_FILE.to_string())
+}
+
+// Tests never touch real data: each run gets a users file of its own under the temp dir
+#[cfg(test)]
+fn users_file() -> String {
+    format!(
+        "{}/shamebot-test-{}/users.json",
+        std::env::temp_dir().display(),
+        std::process::id()
+    )
+}
+
+// The old change log and snapshots, and pending consent requests, live next to the users file
+fn beside_users_file(name: &str) -> String {
+    let users_file = users_file();
+    match std::path::Path::new(&users_file).parent() {
+        Some(dir) => dir.join(name).to_string_lossy().into_owned(),
+        None => name.to_string(),
     }
+}
 
-    // Create new user with first game
-    let mut games = HashMap::new();
-    games.insert(game.to_string(), total);
+fn history_file() -> String {
+    beside_users_file("history.json")
+}
 
-    let new_user = User {
-        user: username.to_string(),
-        games,
-    };
+// Snapshots from before servers were kept apart
+fn legacy_snapshots_file() -> String {
+    beside_users_file("snapshots.json")
+}
 
-    users.push(new_user);
-    save_users_to_file(&users)?;
+// Each server's snapshots sit next to its database
+fn snapshots_file(guild_id: u64) -> String {
+    format!("{}/{}-snapshots.json", db::data_dir(), guild_id)
+}
 
-    println!(
-        "Added new user '{}' with game '{}' and total {}",
-        username, game, total
-    );
-    Ok(())
+// Consent is answered in DMs, so pending requests are kept for every server together
+fn consent_file() -> String {
+    beside_users_file("consent.json")
 }
 
-// Updated function to update totals (now needs to specify which game)
-pub fn update_total(username: &str, game: &str, additional_total: &str) -> Result<(i32, bool)> {
-    let mut users = load_user_file();
-    let additional: i32 = additional_total
-        .parse()
-        .map_err(|_| "Invalid number for additional total")?;
+// Snapshots taken with !backup, one directory per server. Relative to the working
+// directory unless SHAMEBOT_BACKUP_DIR says otherwise
+#[cfg(not(test))]
+const DEFAULT_BACKUP_DIR: &str = "backups";
 
-    // Find the user
-    let user_found = users.iter_mut().find(|user| user.user == username);
+#[cfg(not(test))]
+fn backup_dir(guild_id: u64) -> String {
+    let root =
+        std::env::var("SHAMEBOT_BACKUP_DIR").unwrap_or_else(|_| DEFAULT_BACKUP_DIR.to_string());
+    format!("{}/{}", root, guild_id)
+}
 
-    let mut new_total = 0;
-    let mut crossed_threshold = false;
+#[cfg(test)]
+fn backup_dir(guild_id: u64) -> String {
+    beside_users_file(&format!("backups/{}", guild_id))
+}
 
-    match user_found {
-        Some(user) => {
-            // Check if user has this game
-            if let Some(current_total) = user.games.get_mut(game) {
-                let old_total = *current_total;
-                *current_total += additional;
-                new_total = *current_total;
-                crossed_threshold = old_total < 300 && new_total >= 300;
-                println!("Updated {}'s {} total to {}", username, game, new_total);
-            } else {
-                return Err(format!("User '{}' doesn't have game '{}'", username, game).into());
+// The server tests keep their users in
+#[cfg(test)]
+pub const TEST_GUILD: u64 = 1;
+
+// Every test shares that one file, so they take turns and each starts with no users
+#[cfg(test)]
+pub fn fresh_store() -> std::sync::MutexGuard<'static, ()> {
+    static STORE: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    let guard = STORE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let file = users_file();
+    let _ = std::fs::remove_file(&file);
+    let _ = std::fs::remove_file(format!("{}.bak", file));
+    let _ = std::fs::remove_file(format!("{}.imported", file));
+    let _ = std::fs::remove_file(history_file());
+    let _ = std::fs::remove_file(format!("{}.imported", history_file()));
+    let _ = std::fs::remove_file(legacy_snapshots_file());
+    let _ = std::fs::remove_file(consent_file());
+    let _ = std::fs::remove_file(db::legacy_db_path());
+    let _ = std::fs::remove_dir_all(db::data_dir());
+    let _ = std::fs::remove_dir_all(beside_users_file("backups"));
+    lock_user_cache().clear();
+    lock_stats().clear();
+    if let Some(dir) = std::path::Path::new(&file).parent() {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+    guard
+}
+
+// A file of its own next to the test users file, for tests that work on other files
+#[cfg(test)]
+pub fn scratch_file(name: &str) -> String {
+    let path = users_file().replace("users.json", name);
+    std::fs::create_dir_all(std::path::Path::new(&path).parent().unwrap()).unwrap();
+    path
+}
+
+// The first server to use the bot takes over the data from before servers were kept
+// apart: the old single database, or failing that the old users.json and history.json.
+// Either is moved aside afterwards so a second server doesn't get a copy too, and so do
+// the old snapshots
+fn import_legacy_files(guild_id: u64) -> Result<()> {
+    if db::exists(guild_id) {
+        return Ok(());
+    }
+
+    if db::claim_legacy(guild_id)? {
+        info!("Moved the old database over to server {}", guild_id);
+    } else {
+        let path = users_file();
+        let users = load_legacy_file(&path)?;
+        let history = load_legacy_history();
+        db::save_users(guild_id, &users)?;
+        for change in &history {
+            db::record_transaction(guild_id, change)?;
+        }
+
+        if !users.is_empty() {
+            std::fs::rename(&path, format!("{}.imported", path))
+                .map_err(io_error(format!("Couldn't move '{}' aside", path)))?;
+            if !history.is_empty() {
+                let history_path = history_file();
+                std::fs::rename(&history_path, format!("{}.imported", history_path))
+                    .map_err(io_error(format!("Couldn't move '{}' aside", history_path)))?;
             }
+            info!(
+                "Imported {} users and {} logged changes from '{}' into server {}",
+                users.len(),
+                history.len(),
+                path,
+                guild_id
+            );
         }
-        None => {
-            return Err(format!("User '{}' not found", username).into());
+    }
+
+    let snapshots = legacy_snapshots_file();
+    if std::path::Path::new(&snapshots).exists() {
+        std::fs::rename(&snapshots, snapshots_file(guild_id))
+            .map_err(io_error(format!("Couldn't move '{}' aside", snapshots)))?;
+    }
+    Ok(())
+}
+
+// Each server's users as last read or saved, so commands don't go back to the database
+// for what the bot already has. Reads share it and only saves write to it. Like the
+// stats, changes made to the database outside the bot aren't seen until !reload
+static USER_CACHE: RwLock<BTreeMap<u64, Vec<User>>> = RwLock::new(BTreeMap::new());
+
+fn cached_users(guild_id: u64) -> Option<Vec<User>> {
+    USER_CACHE
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .get(&guild_id)
+        .cloned()
+}
+
+fn lock_user_cache() -> std::sync::RwLockWriteGuard<'static, BTreeMap<u64, Vec<User>>> {
+    USER_CACHE
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn load_users(guild_id: u64) -> Result<Vec<User>> {
+    if let Some(users) = cached_users(guild_id) {
+        return Ok(users);
+    }
+
+    import_legacy_files(guild_id)?;
+    let users = db::load_users(guild_id)?;
+    // A save that landed while this was reading has already cached something newer
+    lock_user_cache()
+        .entry(guild_id)
+        .or_insert_with(|| users.clone());
+    Ok(users)
+}
+
+// Every save drops the server's cached stats, so they're worked out again from what was
+// saved. The cached users only change once the database has, so a failed save leaves
+// both as they were
+fn save_users(guild_id: u64, users: &[User]) -> Result<()> {
+    db::save_users(guild_id, users)?;
+    lock_user_cache().insert(guild_id, users.to_vec());
+    lock_stats().remove(&guild_id);
+    Ok(())
+}
+
+// A save after a change the cached stats can follow, so they're kept
+fn save_users_and_stats(
+    guild_id: u64,
+    users: &[User],
+    change: impl FnOnce(&mut StatsCache),
+) -> Result<()> {
+    db::save_users(guild_id, users)?;
+    lock_user_cache().insert(guild_id, users.to_vec());
+    if let Some(stats) = lock_stats().get_mut(&guild_id) {
+        change(stats);
+    }
+    Ok(())
+}
+
+// Function to write every server the bot has loaded back to its database, for a clean
+// shutdown. Saves already go straight through, so this is a last safety net; holding
+// the store lock also waits out any command that's partway through one. Returns how
+// many servers were written
+pub fn flush() -> Result<usize> {
+    let _store = lock_store();
+    let cached: Vec<(u64, Vec<User>)> = USER_CACHE
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .iter()
+        .map(|(&guild_id, users)| (guild_id, users.clone()))
+        .collect();
+
+    for (guild_id, users) in &cached {
+        db::save_users(*guild_id, users)?;
+    }
+    Ok(cached.len())
+}
+
+// A missing file means there's nothing to import. Any other read failure, or a file
+// that doesn't parse, is an error so a bad file is never mistaken for no users.
+// A corrupt file is copied to users.json.bak first. A file saved back when totals
+// were whole dollars is converted to cents, along with the logs from then
+fn load_legacy_file(path: &str) -> Result<Vec<User>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(io_error(format!("Couldn't read '{}'", path))(e)),
+    };
+
+    if contents.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut users: Vec<User> = match serde_json::from_str(&contents) {
+        Ok(users) => users,
+        Err(e) => {
+            // Keep a copy of the bad file before anything gets a chance to overwrite it
+            let backup_path = format!("{}.bak", path);
+            let context = match std::fs::copy(path, &backup_path) {
+                Ok(_) => format!(
+                    "'{}' is corrupt and wasn't loaded (backed up to '{}')",
+                    path, backup_path
+                ),
+                Err(copy_error) => format!(
+                    "'{}' is corrupt and wasn't loaded, and backing it up failed ({})",
+                    path, copy_error
+                ),
+            };
+            return Err(serde_error(context)(e));
+        }
+    };
+
+    if migrate_to_cents(&mut users) {
+        migrate_logs_to_cents()?;
+    }
+    Ok(users)
+}
+
+// Records from before totals were stored in cents hold whole dollars.
+// Scale those up and mark them, returning whether anything changed
+fn migrate_to_cents(users: &mut [User]) -> bool {
+    let mut migrated = false;
+
+    for user in users.iter_mut().filter(|user| !user.in_cents) {
+        let totals = user.games.values_mut().map(|record| &mut record.total);
+        for amount in totals.chain(user.caps.values_mut()) {
+            *amount *= 100;
         }
+        user.in_cents = true;
+        migrated = true;
+    }
+
+    migrated
+}
+
+// The change log and saved snapshots from the whole-dollar days, scaled to cents
+// along with the users they belong to
+fn migrate_logs_to_cents() -> Result<()> {
+    let mut history = load_legacy_history();
+    for change in &mut history {
+        change.delta *= 100;
+        change.total *= 100;
+    }
+    if !history.is_empty() {
+        write_json_atomically(&history_file(), &history)?;
     }
 
-    save_users_to_file(&users)?;
-    Ok((new_total, crossed_threshold))
+    let mut snapshots = read_snapshots(&legacy_snapshots_file());
+    for (_, total) in snapshots.values_mut().flatten() {
+        *total *= 100;
+    }
+    if !snapshots.is_empty() {
+        write_json_atomically(&legacy_snapshots_file(), &snapshots)?;
+    }
+    Ok(())
 }
 
-// Function to get all users and their games (for listing)
-pub fn get_users() -> Result<Vec<User>> {
-    Ok(load_user_file())
+// Take the commas out of a number grouped like `1,234,567`, so amounts can be typed
+// the way they're shown. Commas anywhere but between groups of three mean it isn't one
+fn ungroup_thousands(digits: &str) -> Option<String> {
+    if !digits.contains(',') {
+        return Some(digits.to_string());
+    }
+
+    let groups: Vec<&str> = digits.split(',').collect();
+    let first_ok = (1..=3).contains(&groups[0].len());
+    let rest_ok = groups[1..].iter().all(|group| group.len() == 3);
+    (first_ok && rest_ok).then(|| groups.concat())
 }
 
-// Function to get current total
-pub fn get_game_total(username: &str, game: &str) -> Result<i32> {
-    let users = load_user_file();
+// Write a whole number with a comma between each group of three digits
+fn group_thousands(value: u64) -> String {
+    let digits = value.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
 
-    match users.iter().find(|user| user.user == username) {
-        Some(user) => match user.games.get(game) {
-            Some(&total) => Ok(total),
-            None => Err(format!("User '{}' doesn't have a game", username).into()),
-        },
-        None => Err(format!("User '{}' not found", username).into()),
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
     }
+    grouped
 }
 
-// Function to get total across ALL games for a user
-pub fn get_user_total_all_games(username: &str) -> Result<i32> {
-    let users = load_user_file();
+// How amounts are written. SHAMEBOT_CURRENCY_SYMBOL swaps the `$` for another symbol, and
+// SHAMEBOT_CURRENCY_POSITION=suffix puts it after the number, as in `9.99 €`
+struct Currency {
+    symbol: String,
+    suffix: bool,
+}
 
-    match users.iter().find(|user| user.user == username) {
-        Some(user) => {
-            let total: i32 = user.games.values().sum();
-            Ok(total)
+impl Currency {
+    fn from_settings(symbol: Option<&str>, position: Option<&str>) -> Currency {
+        Currency {
+            symbol: symbol
+                .map(str::trim)
+                .filter(|symbol| !symbol.is_empty())
+                .unwrap_or("$")
+                .to_string(),
+            suffix: position.is_some_and(|position| position.trim().eq_ignore_ascii_case("suffix")),
+        }
+    }
+
+    fn parse(&self, input: &str) -> Option<i64> {
+        let (negative, rest) = match input.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, input),
+        };
+        let symbol = self.symbol.as_str();
+        let rest = rest
+            .strip_prefix('$')
+            .or_else(|| rest.strip_prefix(symbol))
+            .or_else(|| rest.strip_suffix(symbol))
+            .unwrap_or(rest)
+            .trim();
+        let (dollars, cents) = rest.split_once('.').unwrap_or((rest, ""));
+        let dollars = &ungroup_thousands(dollars)?;
+
+        let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if (dollars.is_empty() && cents.is_empty())
+            || cents.len() > 2
+            || !all_digits(dollars)
+            || !all_digits(cents)
+        {
+            return None;
+        }
+
+        let dollars: i64 = if dollars.is_empty() {
+            0
+        } else {
+            dollars.parse().ok()?
+        };
+        let cents: i64 = match cents.len() {
+            0 => 0,
+            1 => cents.parse::<i64>().ok()? * 10,
+            _ => cents.parse().ok()?,
+        };
+
+        let total = dollars.checked_mul(100)?.checked_add(cents)?;
+        Some(if negative { -total } else { total })
+    }
+
+    fn format(&self, cents: i64) -> String {
+        let sign = if cents < 0 { "-" } else { "" };
+        let cents = cents.unsigned_abs();
+        let amount = format!("{}.{:02}", group_thousands(cents / 100), cents % 100);
+
+        if self.suffix {
+            format!("{}{} {}", sign, amount, self.symbol)
+        } else {
+            format!("{}{}{}", sign, self.symbol, amount)
         }
-        None => Err(format!("User '{}' not found", username).into()),
     }
 }
 
-// Function to get specific user's games
-pub fn get_user_games(username: &str) -> Result<HashMap<String, i32>> {
-    let users = load_user_file();
+static CURRENCY: OnceLock<Currency> = OnceLock::new();
 
-    match users.iter().find(|user| user.user == username) {
-        Some(user) => Ok(user.games.clone()),
-        None => Err(format!("User '{}' not found", username).into()),
+fn currency() -> &'static Currency {
+    CURRENCY.get_or_init(|| {
+        Currency::from_settings(
+            std::env::var("SHAMEBOT_CURRENCY_SYMBOL").ok().as_deref(),
+            std::env::var("SHAMEBOT_CURRENCY_POSITION").ok().as_deref(),
+        )
+    })
+}
+
+// Parse an amount like `9.99`, `$10` or `-5.5` into cents. The configured currency
+// symbol is allowed on either side too
+pub fn parse_money(input: &str) -> Option<i64> {
+    currency().parse(input)
+}
+
+// Format cents in the configured currency with the dollars grouped in thousands,
+// e.g. 123456789 -> `$1,234,567.89` (or `1,234,567.89 €`).
+// Every amount the bot shows goes through here
+pub fn format_money(cents: i64) -> String {
+    currency().format(cents)
+}
+
+// Function to dump every user as JSON, e.g. to post it somewhere as a backup.
+// Goes through a serde_json Value so map keys come out sorted and the same data
+// always gives the same text
+pub fn read_data_file(guild_id: u64) -> Result<String> {
+    let users = serde_json::to_value(load_users(guild_id)?)?;
+    Ok(serde_json::to_string_pretty(&users)?)
+}
+
+// Serialize data and swap it into place through a temp file in the same directory.
+// Any failure removes the temp file and leaves the existing file untouched
+fn write_json_atomically<T: Serialize + ?Sized>(path: &str, data: &T) -> Result<String> {
+    // Serialize before touching the disk so a serde failure can't leave anything behind
+    let json = serde_json::to_string_pretty(data).map_err(serde_error(format!(
+        "Couldn't serialize data for '{}'",
+        path
+    )))?;
+
+    let temp_path = format!("{}.tmp", path);
+    let result = std::fs::write(&temp_path, &json)
+        .map_err(io_error(format!("Couldn't write '{}'", temp_path)))
+        .and_then(|_| {
+            std::fs::rename(&temp_path, path)
+                .map_err(io_error(format!("Couldn't replace '{}'", path)))
+        });
+
+    if let Err(e) = result {
+        std::fs::remove_file(&temp_path).ok();
+        return Err(e);
     }
+
+    Ok(json)
 }
 
-// Function to delete a game from a user
-pub fn remove_game(username: &str, game: &str) -> Result<()> {
-    let mut users = load_user_file();
+// The change log from before the database. A missing or unreadable log counts as empty
+fn load_legacy_history() -> Vec<Transaction> {
+    std::fs::read_to_string(history_file())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
 
-    let user_found = users.iter_mut().find(|user| user.user == username);
+// Every change logged in a server, oldest first
+fn load_history(guild_id: u64) -> Result<Vec<Transaction>> {
+    import_legacy_files(guild_id)?;
+    Ok(db::load_all_transactions(guild_id)?)
+}
 
-    match user_found {
-        Some(user) => {
-            if user.games.remove(game).is_some() {
-                println!("Removed game '{}' from user '{}'", game, username);
+// Append a change to a server's log
+pub fn record_transaction(guild_id: u64, transaction: Transaction) -> Result<()> {
+    import_legacy_files(guild_id)?;
+    Ok(db::record_transaction(guild_id, &transaction)?)
+}
 
-                // If user has no games left, optionally remove the user entirely
-                if user.games.is_empty() {
-                    users.retain(|u| u.user != username);
-                    println!("User '{}' had no games left and was removed", username);
-                }
-            } else {
-                return Err(format!("User '{}' doesn't have game '{}'", username, game).into());
+// Seconds since the Unix epoch, for timestamping logged changes
+pub fn now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+// Numbers worked out from every user, kept so stat commands don't rescan them all
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StatsCache {
+    pub grand_total: i64,
+    pub game_totals: HashMap<String, i64>, // game_name -> everyone's total on it
+    pub user_totals: HashMap<String, i64>, // username -> total across their games
+}
+
+impl StatsCache {
+    pub fn from_users(users: &[User]) -> Self {
+        let mut stats = StatsCache::default();
+
+        // Linked accounts count towards the user they link to
+        for (canonical, members) in canonical_groups(users) {
+            for (game, record) in members.iter().flat_map(|user| &user.games) {
+                stats.apply_update(&canonical, game, record.total);
             }
         }
-        None => {
-            return Err(format!("User '{}' not found", username).into());
+        stats
+    }
+
+    // Account for `amount` being added to one user's game
+    pub fn apply_update(&mut self, username: &str, game: &str, amount: i64) {
+        self.grand_total += amount;
+        *self.game_totals.entry(game.to_string()).or_default() += amount;
+        *self.user_totals.entry(username.to_string()).or_default() += amount;
+    }
+
+    // Users by their total, biggest spenders first. Ties break alphabetically
+    pub fn ranking(&self) -> Vec<(String, i64)> {
+        let mut ranking: Vec<(String, i64)> = self
+            .user_totals
+            .iter()
+            .map(|(username, &total)| (username.clone(), total))
+            .collect();
+
+        ranking.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        ranking
+    }
+}
+
+// The stats worked out from each server's stored users. Saves either bring them along or
+// drop them, so they're only rebuilt after a change they can't follow. Changes made to
+// the database outside the bot aren't seen until !reload
+static STATS_CACHE: Mutex<BTreeMap<u64, StatsCache>> = Mutex::new(BTreeMap::new());
+
+fn lock_stats() -> MutexGuard<'static, BTreeMap<u64, StatsCache>> {
+    STATS_CACHE
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+// Work the stats out again from the stored users. Holds the store lock so no save
+// lands between reading the users and caching what they add up to
+fn rebuild_stats(guild_id: u64) -> Result<(usize, StatsCache)> {
+    let _store = lock_store();
+    let users = load_users(guild_id)?;
+    let stats = StatsCache::from_users(&users);
+    lock_stats().insert(guild_id, stats.clone());
+    Ok((users.len(), stats))
+}
+
+// Function to read the database again after it was changed outside the bot. Drops the
+// cached users and stats and rebuilds them from what's stored. Every save is written
+// straight to the database, so there are never unsaved changes to lose. Returns how
+// many users were loaded
+pub fn reload_from_disk(guild_id: u64) -> Result<usize> {
+    lock_user_cache().remove(&guild_id);
+    let (count, _) = rebuild_stats(guild_id)?;
+    info!("Reloaded {} users from disk for server {}", count, guild_id);
+    Ok(count)
+}
+
+// The name a user is stored under, matching ignoring case so `q` finds `Q`. An exact
+// match wins, and unknown users are returned as typed. Doesn't follow account links,
+// for commands that act on an alias's own record
+fn stored_username(users: &[User], username: &str) -> String {
+    users
+        .iter()
+        .find(|user| user.user == username)
+        .or_else(|| {
+            users
+                .iter()
+                .find(|user| user.user.to_lowercase() == username.to_lowercase())
+        })
+        .map_or(username, |user| user.user.as_str())
+        .to_string()
+}
+
+// Follow account links so commands for an alias act on the canonical user.
+// Names match ignoring case, but an exact match wins
+fn resolve_username(users: &[User], username: &str) -> String {
+    let stored = stored_username(users, username);
+    let mut current = stored.as_str();
+
+    // Links can't form cycles, but never walk further than there are users
+    for _ in 0..users.len() {
+        match users
+            .iter()
+            .find(|user| user.user == current)
+            .and_then(|user| user.alias_of.as_deref())
+        {
+            Some(canonical) => current = canonical,
+            None => break,
         }
     }
 
-    save_users_to_file(&users)?;
-    Ok(())
+    current.to_string()
 }
 
-// Function to delete an entire user (all their games)
-pub fn delete_user(username: &str) -> Result<()> {
-    let mut users = load_user_file();
-    let original_len = users.len();
+// The name a user's game is stored under, matching ignoring case so `tekken 8` finds
+// `Tekken 8`. An exact match wins, and unknown games are returned as typed
+fn resolve_game(users: &[User], username: &str, game: &str) -> String {
+    users
+        .iter()
+        .find(|user| user.user == username)
+        .and_then(|user| stored_game_name(user, game))
+        .unwrap_or_else(|| game.to_string())
+}
 
-    // Remove the user entirely
+fn stored_game_name(user: &User, game: &str) -> Option<String> {
+    if user.games.contains_key(game) {
+        return Some(game.to_string());
+    }
+
+    user.games
+        .keys()
+        .find(|name| name.to_lowercase() == game.to_lowercase())
+        .cloned()
+}
+
+// Remove a user record, unlinking any accounts that pointed at it
+fn remove_user_entry(users: &mut Vec<User>, username: &str) {
     users.retain(|user| user.user != username);
 
-    if users.len() < original_len {
-        save_users_to_file(&users)?;
-        println!("Deleted user '{}' and all their games", username);
-        Ok(())
+    for user in users.iter_mut() {
+        if user.alias_of.as_deref() == Some(username) {
+            user.alias_of = None;
+        }
+    }
+}
+
+// Users can be left with zero games (by !removegames, or as alias-only links).
+// They still count as tracked: lookups find them, their total is $0, asking for
+// one of their games says they have none, and rankings leave them out.
+fn has_games(user: &User) -> bool {
+    !user.games.is_empty()
+}
+
+// The error for a game lookup that missed, worded for zero-game users too
+fn missing_game_error(user: &User, game: &str) -> UserError {
+    if has_games(user) {
+        UserError::GameNotFound {
+            user: user.user.clone(),
+            game: game.to_string(),
+            suggestion: closest_game(user, game),
+        }
     } else {
-        Err(format!("User '{}' not found", username).into())
+        UserError::NoGames(user.user.clone())
+    }
+}
+
+// The user's game closest to a misspelled name, ignoring case, if any is near enough
+fn closest_game(user: &User, game: &str) -> Option<String> {
+    let lowercase: Vec<String> = user.games.keys().map(|name| name.to_lowercase()).collect();
+    let candidates: Vec<&str> = lowercase.iter().map(String::as_str).collect();
+    let closest = fuzzy::closest(&game.to_lowercase(), &candidates, 3)?;
+
+    user.games
+        .keys()
+        .find(|name| name.to_lowercase() == closest)
+        .cloned()
+}
+
+// Longest user or game name allowed, so lists stay readable. SHAMEBOT_MAX_NAME_LENGTH overrides it
+const DEFAULT_MAX_NAME_LENGTH: usize = 100;
+
+fn max_name_length() -> usize {
+    std::env::var("SHAMEBOT_MAX_NAME_LENGTH")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|&length| length > 0)
+        .unwrap_or(DEFAULT_MAX_NAME_LENGTH)
+}
+
+fn check_name_length(kind: &str, name: &str) -> Result<()> {
+    let max = max_name_length();
+    if name.chars().count() > max {
+        return Err(UserError::InvalidName(format!(
+            "{} names can be at most {} characters",
+            kind, max
+        )));
+    }
+    Ok(())
+}
+
+// User names are single words, since commands split on spaces
+fn validate_username(username: &str) -> Result<()> {
+    if username.trim().is_empty() {
+        return Err(UserError::InvalidName(
+            "User names can't be empty".to_string(),
+        ));
+    }
+    if username.contains(char::is_whitespace) {
+        return Err(UserError::InvalidName(format!(
+            "User name '{}' can't contain spaces",
+            username
+        )));
+    }
+    check_name_length("User", username)
+}
+
+// Game names can have spaces, but need something besides them
+fn validate_game_name(game: &str) -> Result<()> {
+    if game.trim().is_empty() {
+        return Err(UserError::InvalidName(
+            "Game names can't be empty".to_string(),
+        ));
+    }
+    check_name_length("Game", game)
+}
+
+// A new game starts from what's been spent on it so far, which can't be less than nothing
+fn parse_starting_total(starting_total: &str) -> Result<i64> {
+    let total = parse_money(starting_total)
+        .ok_or_else(|| UserError::InvalidAmount("Invalid number for starting total".to_string()))?;
+    if total < 0 {
+        return Err(UserError::InvalidAmount(
+            "Starting total can't be negative".to_string(),
+        ));
+    }
+    Ok(total)
+}
+
+// Function to add a new game to an existing user. Returns the starting total in cents
+pub fn add_game(guild_id: u64, username: &str, game: &str, starting_total: &str) -> Result<i64> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    let username = canonical.as_str();
+    validate_game_name(game)?;
+    let total = parse_starting_total(starting_total)?;
+
+    // Find the user
+    let user_found = users.iter_mut().find(|user| user.user == username);
+
+    match user_found {
+        Some(user) => {
+            // User exists - check if game already exists, in any capitalization
+            if let Some(existing) = stored_game_name(user, game) {
+                return Err(UserError::GameAlreadyExists {
+                    user: username.to_string(),
+                    game: existing,
+                });
+            }
+
+            // Add new game to existing user
+            user.games.insert(game.to_string(), GameRecord::new(total));
+            info!(
+                "Added game '{}' with total {} to user '{}'",
+                game,
+                format_money(total),
+                username
+            );
+        }
+        None => {
+            return Err(UserError::UserNotFound(username.to_string()));
+        }
+    }
+
+    save_users(guild_id, &users)?;
+    Ok(total)
+}
+
+// Function to add a completely new user with their first game. Returns the starting total in cents
+pub fn add_user(guild_id: u64, username: &str, game: &str, starting_total: &str) -> Result<i64> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    validate_username(username)?;
+    validate_game_name(game)?;
+    let total = parse_starting_total(starting_total)?;
+
+    // Check if user already exists, in any capitalization
+    if users
+        .iter()
+        .any(|user| user.user.to_lowercase() == username.to_lowercase())
+    {
+        return Err(UserError::UserAlreadyExists(username.to_string()));
+    }
+
+    // Create new user with first game
+    let mut games = HashMap::new();
+    games.insert(game.to_string(), GameRecord::new(total));
+
+    let new_user = User {
+        user: username.to_string(),
+        games,
+        alias_of: None,
+        poke_opt_out: false,
+        caps: HashMap::new(),
+        goals: HashMap::new(),
+        tags: HashMap::new(),
+        display_name: None,
+        consented: false,
+        in_cents: true,
+        discord_id: None,
+        archived: false,
+    };
+
+    users.push(new_user);
+    save_users(guild_id, &users)?;
+
+    info!(
+        "Added new user '{}' with game '{}' and total {}",
+        username,
+        game,
+        format_money(total)
+    );
+    Ok(total)
+}
+
+// Updated function to update totals (now needs to specify which game).
+// Returns the old total, the amount added, the new total, and which threshold it crossed.
+// Additions that would take a game past its cap are refused unless override_cap is set
+pub fn update_total(
+    guild_id: u64,
+    username: &str,
+    game: &str,
+    additional_total: &str,
+    override_cap: bool,
+) -> Result<(i64, i64, i64, ThresholdCrossed)> {
+    let _store = lock_store();
+    let additional = parse_money(additional_total).ok_or_else(|| {
+        UserError::InvalidAmount("Invalid number for additional total".to_string())
+    })?;
+    change_total(guild_id, username, game, additional, override_cap)
+}
+
+// Function to add to several of a user's games at once, e.g. a whole shopping spree.
+// Either every update is made or, if any of them fails, none are.
+// Returns each game with what was added, its new total and which threshold it crossed
+pub fn update_totals(
+    guild_id: u64,
+    username: &str,
+    updates: &[(String, String)],
+) -> Result<Vec<(String, i64, i64, ThresholdCrossed)>> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    let username = canonical.as_str();
+    let troll_threshold = load_threshold(guild_id)?;
+
+    let mut applied = Vec::new();
+    for (game, amount) in updates {
+        let additional = parse_money(amount).ok_or_else(|| {
+            UserError::InvalidAmount(format!("Invalid number '{}' for '{}'", amount, game))
+        })?;
+        let game = resolve_game(&users, username, game);
+        let (_, new_total, crossed) = apply_change(
+            &mut users,
+            username,
+            &game,
+            additional,
+            false,
+            troll_threshold,
+        )?;
+        applied.push((game, additional, new_total, crossed));
+    }
+
+    save_users_and_stats(guild_id, &users, |stats| {
+        for (game, additional, _, _) in &applied {
+            stats.apply_update(username, game, *additional);
+        }
+    })?;
+    for (game, additional, new_total, _) in &applied {
+        info!(
+            "Updated {}'s {} total to {}",
+            username,
+            game,
+            format_money(*new_total)
+        );
+        record_transaction(
+            guild_id,
+            Transaction {
+                timestamp: now(),
+                username: username.to_string(),
+                game: game.clone(),
+                delta: *additional,
+                total: *new_total,
+                undoes: None,
+            },
+        )?;
+    }
+    Ok(applied)
+}
+
+// Function to take money back off a game total, e.g. for a refunded purchase.
+// Returns the same as update_total, with a negative amount added
+pub fn refund(
+    guild_id: u64,
+    username: &str,
+    game: &str,
+    amount: &str,
+) -> Result<(i64, i64, i64, ThresholdCrossed)> {
+    let _store = lock_store();
+    let amount = parse_money(amount)
+        .filter(|&amount| amount > 0)
+        .ok_or_else(|| {
+            UserError::InvalidAmount("Refund amount must be a number above zero".to_string())
+        })?;
+    change_total(guild_id, username, game, -amount, false)
+}
+
+// Add a (possibly negative) amount in cents to a game total and log it. Totals never go
+// below zero. Callers hold the store lock
+fn change_total(
+    guild_id: u64,
+    username: &str,
+    game: &str,
+    additional: i64,
+    override_cap: bool,
+) -> Result<(i64, i64, i64, ThresholdCrossed)> {
+    let mut users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    let username = canonical.as_str();
+    let stored_game = resolve_game(&users, username, game);
+    let game = stored_game.as_str();
+    let troll_threshold = load_threshold(guild_id)?;
+
+    let (old_total, new_total, crossed) = apply_change(
+        &mut users,
+        username,
+        game,
+        additional,
+        override_cap,
+        troll_threshold,
+    )?;
+    info!(
+        "Updated {}'s {} total to {}",
+        username,
+        game,
+        format_money(new_total)
+    );
+
+    save_users_and_stats(guild_id, &users, |stats| {
+        stats.apply_update(username, game, additional)
+    })?;
+    record_transaction(
+        guild_id,
+        Transaction {
+            timestamp: now(),
+            username: username.to_string(),
+            game: game.to_string(),
+            delta: additional,
+            total: new_total,
+            undoes: None,
+        },
+    )?;
+    Ok((old_total, additional, new_total, crossed))
+}
+
+// Add an amount to a game total in memory, checking its cap and the $0 floor.
+// The user and game names are already resolved. Returns the old total, the new
+// total, and which threshold was crossed
+fn apply_change(
+    users: &mut [User],
+    username: &str,
+    game: &str,
+    additional: i64,
+    override_cap: bool,
+    troll_threshold: i64,
+) -> Result<(i64, i64, ThresholdCrossed)> {
+    // Find the user
+    let Some(user) = users.iter_mut().find(|user| user.user == username) else {
+        return Err(UserError::UserNotFound(username.to_string()));
+    };
+
+    // Check if user has this game
+    let Some(old_total) = user.games.get(game).map(|record| record.total) else {
+        return Err(missing_game_error(user, game));
+    };
+
+    if let Some(&cap) = user.caps.get(game)
+        && !override_cap
+        && additional > 0
+        && old_total + additional > cap
+    {
+        return Err(UserError::Rejected(if old_total >= cap {
+            format!(
+                "Cap reached: '{}' is capped at {} for {}",
+                game,
+                format_money(cap),
+                username
+            )
+        } else {
+            format!(
+                "That would go over the {} cap on '{}' ({} left for {})",
+                format_money(cap),
+                game,
+                format_money(cap - old_total),
+                username
+            )
+        }));
+    }
+
+    if old_total + additional < 0 {
+        return Err(UserError::Rejected(format!(
+            "Cannot reduce '{}' below {} (current {}, requested {})",
+            game,
+            format_money(0),
+            format_money(old_total),
+            format_money(additional)
+        )));
+    }
+
+    let new_total = old_total + additional;
+    if let Some(record) = user.games.get_mut(game) {
+        record.set_total(new_total);
+    }
+
+    let crosses = |threshold| old_total < threshold && new_total >= threshold;
+    let crossed = if crosses(SUPER_TROLL_THRESHOLD) {
+        ThresholdCrossed::SuperTroll
+    } else if crosses(troll_threshold) {
+        ThresholdCrossed::Troll
+    } else {
+        ThresholdCrossed::None
+    };
+
+    Ok((old_total, new_total, crossed))
+}
+
+// Function to work out what an update would do without making it. Nothing is saved
+// or logged. Returns the game's new total and which threshold it would cross
+pub fn preview_update(
+    guild_id: u64,
+    username: &str,
+    game: &str,
+    additional_total: &str,
+) -> Result<(i64, ThresholdCrossed)> {
+    let additional = parse_money(additional_total).ok_or_else(|| {
+        UserError::InvalidAmount("Invalid number for additional total".to_string())
+    })?;
+
+    let mut users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    let stored_game = resolve_game(&users, &canonical, game);
+    let troll_threshold = load_threshold(guild_id)?;
+
+    let (_, new_total, crossed) = apply_change(
+        &mut users,
+        &canonical,
+        &stored_game,
+        additional,
+        false,
+        troll_threshold,
+    )?;
+    Ok((new_total, crossed))
+}
+
+// Function to get a user's total changes, newest first, optionally for just one game
+pub fn get_history(guild_id: u64, username: &str, game: Option<&str>) -> Result<Vec<Transaction>> {
+    let users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+
+    let user = users
+        .iter()
+        .find(|user| user.user == canonical)
+        .ok_or_else(|| UserError::UserNotFound(canonical.to_string()))?;
+
+    Ok(db::load_transactions(guild_id, &user.user, game)?)
+}
+
+// Function to revert one user's most recent change that hasn't been undone yet, even if
+// other users have changed things since. The undo is logged too, so undoing again
+// reverts the change before it. An undo that would leave the game below zero is
+// refused. Returns the game and its restored total
+pub fn undo_last_for(guild_id: u64, username: &str) -> Result<(String, i64)> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    let username = canonical.as_str();
+
+    let Some(user) = users.iter_mut().find(|user| user.user == username) else {
+        return Err(UserError::UserNotFound(username.to_string()));
+    };
+
+    let Some((change_id, change)) = db::last_undoable_transaction(guild_id, username)? else {
+        return Err(UserError::NotFound(format!(
+            "User '{}' has no changes to undo",
+            username
+        )));
+    };
+
+    let Some(record) = user.games.get_mut(&change.game) else {
+        return Err(missing_game_error(user, &change.game));
+    };
+    let restored = record.total - change.delta;
+    if restored < 0 {
+        return Err(UserError::Rejected(format!(
+            "Can't undo {} on '{}' because it would leave {} below {}",
+            format_money(change.delta),
+            change.game,
+            username,
+            format_money(0)
+        )));
+    }
+    record.set_total(restored);
+    info!(
+        "Undid {}'s change of {} to {}, now {}",
+        username,
+        format_money(change.delta),
+        change.game,
+        format_money(restored)
+    );
+
+    save_users_and_stats(guild_id, &users, |stats| {
+        stats.apply_update(username, &change.game, -change.delta)
+    })?;
+    db::record_transaction(
+        guild_id,
+        &Transaction {
+            timestamp: now(),
+            username: username.to_string(),
+            game: change.game.clone(),
+            delta: -change.delta,
+            total: restored,
+            undoes: Some(change_id),
+        },
+    )?;
+    Ok((change.game, restored))
+}
+
+// Function to get all users and their games (for listing). Archived users are only
+// included when asked for
+pub fn get_users(guild_id: u64, include_archived: bool) -> Result<Vec<User>> {
+    let users = load_users(guild_id)?;
+    Ok(if include_archived {
+        users
+    } else {
+        active_users(users)
+    })
+}
+
+// The users and stats the rankings work from, without archived users. The stats cache
+// counts everyone, so a server with anyone archived adds the rest up afresh
+fn active_stats(guild_id: u64) -> Result<(Vec<User>, StatsCache)> {
+    let users = load_users(guild_id)?;
+    if !users.iter().any(|user| user.archived) {
+        return Ok((users, get_cached_stats(guild_id)?));
+    }
+
+    let users = active_users(users);
+    let stats = StatsCache::from_users(&users);
+    Ok((users, stats))
+}
+
+// Leave out archived users, along with any accounts linked to them
+fn active_users(users: Vec<User>) -> Vec<User> {
+    let archived: Vec<String> = users
+        .iter()
+        .filter(|user| user.archived)
+        .map(|user| user.user.clone())
+        .collect();
+    if archived.is_empty() {
+        return users;
+    }
+
+    let keep: Vec<bool> = users
+        .iter()
+        .map(|user| !archived.contains(&resolve_username(&users, &user.user)))
+        .collect();
+    users
+        .into_iter()
+        .zip(keep)
+        .filter_map(|(user, keep)| keep.then_some(user))
+        .collect()
+}
+
+// Function to get a game's current total along with when it was added and last changed
+pub fn get_game_record(guild_id: u64, username: &str, game: &str) -> Result<GameRecord> {
+    let users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    let username = canonical.as_str();
+    let stored_game = resolve_game(&users, username, game);
+    let game = stored_game.as_str();
+
+    match users.iter().find(|user| user.user == username) {
+        Some(user) => match user.games.get(game) {
+            Some(record) => Ok(record.clone()),
+            None => Err(missing_game_error(user, game)),
+        },
+        None => Err(UserError::UserNotFound(username.to_string())),
+    }
+}
+
+// Function to get total across ALL games for a user
+pub fn get_user_total_all_games(guild_id: u64, username: &str) -> Result<i64> {
+    let users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    let username = canonical.as_str();
+
+    match users.iter().find(|user| user.user == username) {
+        Some(user) => {
+            let total: i64 = user.games.values().map(|record| record.total).sum();
+            Ok(total)
+        }
+        None => Err(UserError::UserNotFound(username.to_string())),
+    }
+}
+
+// Function to get the server-wide stats, from the cache when the data hasn't changed
+pub fn get_cached_stats(guild_id: u64) -> Result<StatsCache> {
+    if let Some(stats) = lock_stats().get(&guild_id) {
+        return Ok(stats.clone());
+    }
+    Ok(rebuild_stats(guild_id)?.1)
+}
+
+// Function to get specific user's games
+pub fn get_user_games(guild_id: u64, username: &str) -> Result<HashMap<String, i64>> {
+    let users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    let username = canonical.as_str();
+
+    match users.iter().find(|user| user.user == username) {
+        Some(user) => Ok(user
+            .games
+            .iter()
+            .map(|(game, record)| (game.clone(), record.total))
+            .collect()),
+        None => Err(UserError::UserNotFound(username.to_string())),
+    }
+}
+
+// Function to list a user's games that start with what's been typed so far, ignoring
+// case, in alphabetical order. Used to autocomplete game names
+pub fn suggest_games(guild_id: u64, username: &str, prefix: &str) -> Result<Vec<String>> {
+    let users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+
+    let user = users
+        .iter()
+        .find(|user| user.user == canonical)
+        .ok_or_else(|| UserError::UserNotFound(canonical.to_string()))?;
+
+    let prefix = prefix.to_lowercase();
+    let mut games: Vec<String> = user
+        .games
+        .keys()
+        .filter(|game| game.to_lowercase().starts_with(&prefix))
+        .cloned()
+        .collect();
+    games.sort_by_key(|game| game.to_lowercase());
+    Ok(games)
+}
+
+// Function to delete a game from a user
+pub fn remove_game(guild_id: u64, username: &str, game: &str) -> Result<()> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    let username = canonical.as_str();
+    let stored_game = resolve_game(&users, username, game);
+    let game = stored_game.as_str();
+
+    let user_found = users.iter_mut().find(|user| user.user == username);
+
+    match user_found {
+        Some(user) => {
+            if user.games.remove(game).is_some() {
+                user.caps.remove(game);
+                user.goals.remove(game);
+                user.tags.remove(game);
+                info!("Removed game '{}' from user '{}'", game, username);
+
+                // If user has no games left, optionally remove the user entirely
+                if user.games.is_empty() {
+                    remove_user_entry(&mut users, username);
+                    info!("User '{}' had no games left and was removed", username);
+                }
+            } else {
+                return Err(missing_game_error(user, game));
+            }
+        }
+        None => {
+            return Err(UserError::UserNotFound(username.to_string()));
+        }
+    }
+
+    save_users(guild_id, &users)?;
+    Ok(())
+}
+
+// Function to move a game and its total (and cap, goal and tags) from one user to another. Like
+// remove_game, a user left with no games is removed. The game's logged changes stay with whoever made
+// them unless move_history is set. Returns whether the user was removed
+pub fn move_game(
+    guild_id: u64,
+    from: &str,
+    to: &str,
+    game: &str,
+    move_history: bool,
+) -> Result<bool> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    let from = resolve_username(&users, from);
+    let to = resolve_username(&users, to);
+    let game = resolve_game(&users, &from, game);
+
+    let Some(target) = users.iter().find(|user| user.user == to) else {
+        return Err(UserError::UserNotFound(to));
+    };
+    // Matched in any capitalization, so the target can't end up with the game twice
+    if let Some(existing) = stored_game_name(target, &game) {
+        return Err(UserError::GameAlreadyExists {
+            user: to,
+            game: existing,
+        });
+    }
+
+    let Some(source) = users.iter_mut().find(|user| user.user == from) else {
+        return Err(UserError::UserNotFound(from));
+    };
+    let Some(record) = source.games.remove(&game) else {
+        return Err(missing_game_error(source, &game));
+    };
+    let cap = source.caps.remove(&game);
+    let goal = source.goals.remove(&game);
+    let tags = source.tags.remove(&game);
+    let emptied = source.games.is_empty();
+
+    if let Some(target) = users.iter_mut().find(|user| user.user == to) {
+        target.games.insert(game.clone(), record);
+        if let Some(cap) = cap {
+            target.caps.insert(game.clone(), cap);
+        }
+        if let Some(goal) = goal {
+            target.goals.insert(game.clone(), goal);
+        }
+        if let Some(tags) = tags {
+            target.tags.insert(game.clone(), tags);
+        }
+    }
+
+    if emptied {
+        remove_user_entry(&mut users, &from);
+        info!("User '{}' had no games left and was removed", from);
+    }
+
+    save_users(guild_id, &users)?;
+    if move_history {
+        db::reassign_transactions(guild_id, &from, &to, &game)?;
+    }
+    info!("Moved game '{}' from user '{}' to '{}'", game, from, to);
+    Ok(emptied)
+}
+
+// Function to delete an entire user (all their games)
+pub fn delete_user(guild_id: u64, username: &str) -> Result<()> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    let stored = stored_username(&users, username);
+    let username = stored.as_str();
+    let original_len = users.len();
+
+    // Remove the user entirely
+    remove_user_entry(&mut users, username);
+
+    if users.len() < original_len {
+        save_users(guild_id, &users)?;
+        info!("Deleted user '{}' and all their games", username);
+        Ok(())
+    } else {
+        Err(UserError::UserNotFound(username.to_string()))
+    }
+}
+
+// Function to give a user a new name, keeping their games, caps, links and history
+pub fn rename_user(guild_id: u64, old: &str, new: &str) -> Result<()> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    let stored = stored_username(&users, old);
+    let old = stored.as_str();
+
+    validate_username(new)?;
+
+    if !users.iter().any(|user| user.user == old) {
+        return Err(UserError::UserNotFound(old.to_string()));
+    }
+
+    // Changing only the capitalization of a name is fine, clashing with someone else isn't
+    if users
+        .iter()
+        .any(|user| user.user != old && user.user.to_lowercase() == new.to_lowercase())
+    {
+        return Err(UserError::UserAlreadyExists(new.to_string()));
+    }
+
+    for user in users.iter_mut() {
+        if user.user == old {
+            user.user = new.to_string();
+        }
+        if user.alias_of.as_deref() == Some(old) {
+            user.alias_of = Some(new.to_string());
+        }
+    }
+
+    save_users(guild_id, &users)?;
+    db::rename_transactions(guild_id, old, new)?;
+
+    info!("Renamed user '{}' to '{}'", old, new);
+    Ok(())
+}
+
+// Function to find the user a Discord account has been linked to, if any
+pub fn username_for_discord_id(guild_id: u64, discord_id: u64) -> Result<Option<String>> {
+    let users = load_users(guild_id)?;
+
+    Ok(users
+        .iter()
+        .find(|user| user.discord_id == Some(discord_id))
+        .map(|user| user.user.clone()))
+}
+
+// Function to tie a Discord account to the user with this name (ignoring case), so
+// mentions of the account find them from now on. Does nothing if the user doesn't
+// exist, already has an account, or the account is already someone else's.
+// Returns whether the link was made
+pub fn link_discord_id(guild_id: u64, username: &str, discord_id: u64) -> Result<bool> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+
+    if users.iter().any(|user| user.discord_id == Some(discord_id)) {
+        return Ok(false);
+    }
+    let Some(user) = users
+        .iter_mut()
+        .find(|user| user.user.to_lowercase() == username.to_lowercase())
+        .filter(|user| user.discord_id.is_none())
+    else {
+        return Ok(false);
+    };
+
+    user.discord_id = Some(discord_id);
+    let linked = user.user.clone();
+    save_users(guild_id, &users)?;
+
+    info!("Linked Discord account {} to user '{}'", discord_id, linked);
+    Ok(true)
+}
+
+// Function to fold a duplicate user into another. Their games move over, totals are
+// summed for games both have, links to them follow, and the duplicate is deleted.
+// A dry run reports without saving anything. Returns how many games moved
+pub fn merge_users(guild_id: u64, from: &str, into: &str, dry_run: bool) -> Result<usize> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    let from = resolve_username(&users, from);
+    let into = resolve_username(&users, into);
+
+    for name in [&from, &into] {
+        if !users.iter().any(|user| user.user == *name) {
+            return Err(UserError::UserNotFound(name.to_string()));
+        }
+    }
+    if from == into {
+        return Err(UserError::Rejected(format!(
+            "Can't merge '{}' into themselves",
+            from
+        )));
+    }
+
+    let Some(index) = users.iter().position(|user| user.user == from) else {
+        return Err(UserError::UserNotFound(from));
+    };
+    let duplicate = users.remove(index);
+    let Some(target) = users.iter_mut().find(|user| user.user == into) else {
+        return Err(UserError::UserNotFound(into));
+    };
+
+    let moved = duplicate.games.len();
+    for (game, record) in duplicate.games {
+        // Games match ignoring case, keeping the target's spelling
+        let name = stored_game_name(target, &game).unwrap_or_else(|| game.clone());
+        match target.games.get_mut(&name) {
+            Some(existing) => {
+                existing.total += record.total;
+                existing.last_updated = existing.last_updated.max(record.last_updated);
+            }
+            None => {
+                target.games.insert(name.clone(), record);
+            }
+        }
+
+        if let Some(&cap) = duplicate.caps.get(&game) {
+            target.caps.entry(name.clone()).or_insert(cap);
+        }
+        if let Some(&goal) = duplicate.goals.get(&game) {
+            target.goals.entry(name.clone()).or_insert(goal);
+        }
+        for tag in duplicate.tags.get(&game).into_iter().flatten() {
+            let tags = target.tags.entry(name.clone()).or_default();
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+    }
+
+    // The duplicate's Discord account now belongs to the user it was merged into
+    if target.discord_id.is_none() {
+        target.discord_id = duplicate.discord_id;
+    }
+
+    for user in users.iter_mut() {
+        if user.alias_of.as_deref() == Some(from.as_str()) {
+            user.alias_of = Some(into.clone());
+        }
+    }
+
+    if dry_run {
+        return Ok(moved);
+    }
+
+    save_users(guild_id, &users)?;
+    db::rename_transactions(guild_id, &from, &into)?;
+
+    info!("Merged user '{}' into '{}' ({} games)", from, into, moved);
+    Ok(moved)
+}
+
+// Function to rename a game for everyone in a server, e.g. to settle on one spelling.
+// Anyone who already has a game by the new name gets the totals summed, like !merge,
+// and their logged changes follow the new name. A dry run reports without saving
+// anything. Returns the users whose game was renamed
+pub fn rename_game_global(
+    guild_id: u64,
+    old: &str,
+    new: &str,
+    dry_run: bool,
+) -> Result<Vec<String>> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    validate_game_name(new)?;
+
+    let mut renamed = Vec::new();
+    for user in users.iter_mut() {
+        let Some(old_name) = stored_game_name(user, old) else {
+            continue;
+        };
+        // A rename that only changes the capitalization keeps the game as it is
+        let new_name = stored_game_name(user, new)
+            .filter(|name| *name != old_name)
+            .unwrap_or_else(|| new.to_string());
+        if new_name == old_name {
+            continue;
+        }
+
+        let Some(record) = user.games.remove(&old_name) else {
+            continue;
+        };
+        match user.games.get_mut(&new_name) {
+            Some(existing) => {
+                existing.total += record.total;
+                existing.last_updated = existing.last_updated.max(record.last_updated);
+            }
+            None => {
+                user.games.insert(new_name.clone(), record);
+            }
+        }
+
+        if let Some(cap) = user.caps.remove(&old_name) {
+            user.caps.entry(new_name.clone()).or_insert(cap);
+        }
+        if let Some(goal) = user.goals.remove(&old_name) {
+            user.goals.entry(new_name.clone()).or_insert(goal);
+        }
+        for tag in user.tags.remove(&old_name).into_iter().flatten() {
+            let tags = user.tags.entry(new_name.clone()).or_default();
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        renamed.push((user.user.clone(), old_name, new_name));
+    }
+
+    if renamed.is_empty() {
+        return Err(UserError::NotFound(format!(
+            "Nobody has a game called '{}'",
+            old
+        )));
+    }
+    if !dry_run {
+        save_users(guild_id, &users)?;
+        for (username, old_name, new_name) in &renamed {
+            db::rename_game_transactions(guild_id, username, old_name, new_name)?;
+        }
+        info!("Renamed '{}' to '{}' for {} users", old, new, renamed.len());
+    }
+
+    Ok(renamed
+        .into_iter()
+        .map(|(username, _, _)| username)
+        .collect())
+}
+
+// Function to link an alias account to a canonical user (shared consoles)
+pub fn link_account(guild_id: u64, alias: &str, canonical: &str) -> Result<()> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    validate_username(alias)?;
+    let stored_alias = stored_username(&users, alias);
+    let alias = stored_alias.as_str();
+    let stored_canonical = stored_username(&users, canonical);
+    let canonical = stored_canonical.as_str();
+
+    if !users.iter().any(|user| user.user == canonical) {
+        return Err(UserError::UserNotFound(canonical.to_string()));
+    }
+
+    // Linking to anything that already leads back to the alias would loop forever
+    if resolve_username(&users, canonical) == alias {
+        return Err(UserError::Rejected(format!(
+            "Can't link '{}' to '{}' because '{}' already links back to '{}'",
+            alias, canonical, canonical, alias
+        )));
+    }
+
+    match users.iter_mut().find(|user| user.user == alias) {
+        Some(user) => user.alias_of = Some(canonical.to_string()),
+        None => users.push(User {
+            user: alias.to_string(),
+            games: HashMap::new(),
+            alias_of: Some(canonical.to_string()),
+            poke_opt_out: false,
+            caps: HashMap::new(),
+            goals: HashMap::new(),
+            tags: HashMap::new(),
+            display_name: None,
+            consented: false,
+            in_cents: true,
+            discord_id: None,
+            archived: false,
+        }),
+    }
+
+    save_users(guild_id, &users)?;
+    info!("Linked '{}' to '{}'", alias, canonical);
+    Ok(())
+}
+
+// Function to remove an account link. Links with no games of their own are removed entirely
+pub fn unlink_account(guild_id: u64, alias: &str) -> Result<()> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    let stored = stored_username(&users, alias);
+    let alias = stored.as_str();
+
+    match users.iter_mut().find(|user| user.user == alias) {
+        Some(user) => {
+            if user.alias_of.take().is_none() {
+                return Err(UserError::Rejected(format!(
+                    "User '{}' isn't linked to anyone",
+                    alias
+                )));
+            }
+
+            if user.games.is_empty() {
+                users.retain(|user| user.user != alias);
+            }
+        }
+        None => {
+            return Err(UserError::UserNotFound(alias.to_string()));
+        }
+    }
+
+    save_users(guild_id, &users)?;
+    info!("Unlinked '{}'", alias);
+    Ok(())
+}
+
+// Match a game name against a pattern: `*` and `?` wildcards make it a glob,
+// otherwise the pattern matches anywhere in the name
+fn game_matches(pattern: &str, game: &str) -> bool {
+    if !pattern.contains(['*', '?']) {
+        return game.contains(pattern);
+    }
+
+    let pattern: Vec<char> = pattern.chars().collect();
+    let game: Vec<char> = game.chars().collect();
+
+    // matches[j] is true when the pattern so far matches the first j chars of the game
+    let mut matches = vec![false; game.len() + 1];
+    matches[0] = true;
+
+    for &p in &pattern {
+        let mut next = vec![false; game.len() + 1];
+        for j in 0..=game.len() {
+            next[j] = match p {
+                '*' => matches[j] || (j > 0 && next[j - 1]),
+                '?' => j > 0 && matches[j - 1],
+                _ => j > 0 && matches[j - 1] && game[j - 1] == p,
+            };
+        }
+        matches = next;
+    }
+
+    matches[game.len()]
+}
+
+// Function to find every (user, game, total) whose game name contains the query,
+// ignoring case. Sorted by user, then game
+pub fn search_games(guild_id: u64, query: &str) -> Result<Vec<(String, String, i64)>> {
+    let users = load_users(guild_id)?;
+    let query = query.to_lowercase();
+
+    let mut found: Vec<(String, String, i64)> = users
+        .iter()
+        .flat_map(|user| {
+            user.games
+                .iter()
+                .map(move |(game, record)| (user.user.clone(), game.clone(), record.total))
+        })
+        .filter(|(_, game, _)| game.to_lowercase().contains(&query))
+        .collect();
+
+    found.sort();
+    Ok(found)
+}
+
+// Function to list which of a user's games a pattern would match, without removing anything
+pub fn find_games_matching(guild_id: u64, username: &str, pattern: &str) -> Result<Vec<String>> {
+    let users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    let username = canonical.as_str();
+
+    match users.iter().find(|user| user.user == username) {
+        Some(user) => {
+            let mut matched: Vec<String> = user
+                .games
+                .keys()
+                .filter(|game| game_matches(pattern, game))
+                .cloned()
+                .collect();
+            matched.sort();
+            Ok(matched)
+        }
+        None => Err(UserError::UserNotFound(username.to_string())),
+    }
+}
+
+// Function to remove every game of a user that matches a pattern. Unlike
+// remove_game, the user is kept even if this removes all of their games
+pub fn remove_games_matching(guild_id: u64, username: &str, pattern: &str) -> Result<Vec<String>> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    let username = canonical.as_str();
+
+    let removed = match users.iter_mut().find(|user| user.user == username) {
+        Some(user) => {
+            let mut removed: Vec<String> = user
+                .games
+                .keys()
+                .filter(|game| game_matches(pattern, game))
+                .cloned()
+                .collect();
+            removed.sort();
+
+            for game in &removed {
+                user.games.remove(game);
+                user.caps.remove(game);
+                user.goals.remove(game);
+                user.tags.remove(game);
+            }
+            removed
+        }
+        None => {
+            return Err(UserError::UserNotFound(username.to_string()));
+        }
+    };
+
+    if removed.is_empty() {
+        return Err(UserError::NotFound(format!(
+            "User '{}' has no games matching '{}'",
+            username, pattern
+        )));
+    }
+
+    save_users(guild_id, &users)?;
+    info!(
+        "Removed {} games matching '{}' from user '{}'",
+        removed.len(),
+        pattern,
+        username
+    );
+    Ok(removed)
+}
+
+// Group linked accounts under the user they link to, so one person with two
+// gamertags is ranked once under their canonical name. Groups without games are left out
+fn canonical_groups(users: &[User]) -> Vec<(String, Vec<&User>)> {
+    let mut groups: Vec<(String, Vec<&User>)> = Vec::new();
+
+    for user in users {
+        let canonical = resolve_username(users, &user.user);
+        match groups.iter_mut().find(|(name, _)| *name == canonical) {
+            Some((_, members)) => members.push(user),
+            None => groups.push((canonical, vec![user])),
+        }
+    }
+
+    groups.retain(|(_, members)| members.iter().any(|user| has_games(user)));
+    groups
+}
+
+// Summary numbers for a server, from get_stats
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub total_users: usize, // users with games, linked accounts counted once
+    pub distinct_games: usize,
+    pub grand_total: i64,
+    pub average_per_user: i64,
+    pub biggest: Option<(String, String, i64)>, // the single largest (user, game, total)
+}
+
+// Function to add up a server's spending. With nobody tracked everything is zero
+pub fn get_stats(guild_id: u64) -> Result<Stats> {
+    let (users, stats) = active_stats(guild_id)?;
+    let total_users = stats.user_totals.len();
+
+    // Names match ignoring case everywhere else, so `tekken 8` on one user and
+    // `Tekken 8` on another are the same game
+    let mut games: Vec<String> = stats
+        .game_totals
+        .keys()
+        .map(|game| game.to_lowercase())
+        .collect();
+    games.sort();
+    games.dedup();
+
+    let biggest = users
+        .iter()
+        .flat_map(|user| {
+            user.games
+                .iter()
+                .map(move |(game, record)| (user.user.clone(), game.clone(), record.total))
+        })
+        .max_by(|a, b| {
+            a.2.cmp(&b.2)
+                .then_with(|| b.0.cmp(&a.0))
+                .then_with(|| b.1.cmp(&a.1))
+        });
+
+    Ok(Stats {
+        total_users,
+        distinct_games: games.len(),
+        grand_total: stats.grand_total,
+        average_per_user: if total_users == 0 {
+            0
+        } else {
+            stats.grand_total / total_users as i64
+        },
+        biggest,
+    })
+}
+
+// Two users side by side, from compare
+#[derive(Debug)]
+pub struct Comparison {
+    pub first: String, // both names as stored, after following links
+    pub second: String,
+    pub first_total: i64,
+    pub second_total: i64,
+    pub shared: Vec<(String, i64, i64)>, // (game, first's total, second's total), alphabetical
+    pub leader: Option<String>,          // who spends more overall; None when they're even
+}
+
+// Function to put two users head to head: their grand totals, the games they both
+// have (matched ignoring case), and who spends more
+pub fn compare(guild_id: u64, first: &str, second: &str) -> Result<Comparison> {
+    let users = load_users(guild_id)?;
+    let first = resolve_username(&users, first);
+    let second = resolve_username(&users, second);
+
+    let find = |name: &String| {
+        users
+            .iter()
+            .find(|user| user.user == *name)
+            .ok_or_else(|| UserError::UserNotFound(name.clone()))
+    };
+    let first_user = find(&first)?;
+    let second_user = find(&second)?;
+    if first == second {
+        return Err(UserError::Rejected(format!(
+            "'{}' can't be compared with themselves",
+            first
+        )));
+    }
+
+    let mut shared: Vec<(String, i64, i64)> = first_user
+        .games
+        .iter()
+        .filter_map(|(game, record)| {
+            let other = stored_game_name(second_user, game)?;
+            Some((game.clone(), record.total, second_user.games[&other].total))
+        })
+        .collect();
+    shared.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let first_total: i64 = first_user.games.values().map(|record| record.total).sum();
+    let second_total: i64 = second_user.games.values().map(|record| record.total).sum();
+    let leader = match first_total.cmp(&second_total) {
+        std::cmp::Ordering::Greater => Some(first.clone()),
+        std::cmp::Ordering::Less => Some(second.clone()),
+        std::cmp::Ordering::Equal => None,
+    };
+
+    Ok(Comparison {
+        first,
+        second,
+        first_total,
+        second_total,
+        shared,
+        leader,
+    })
+}
+
+// Function to rank users by their total across all games (linked accounts rolled up),
+// biggest spenders first. Ties break alphabetically so the order is stable, and users
+// without games are left out
+pub fn get_leaderboard(guild_id: u64, limit: usize) -> Result<Vec<(String, i64)>> {
+    let mut leaderboard = active_stats(guild_id)?.1.ranking();
+    leaderboard.truncate(limit);
+    Ok(leaderboard)
+}
+
+// Function to find where a user stands on the leaderboard. Users with the same total
+// share a rank, so two tied for first are both #1 and the next is #3.
+// Returns the rank, how many users are ranked, and the user's total
+pub fn get_rank(guild_id: u64, username: &str) -> Result<(usize, usize, i64)> {
+    let users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    if users
+        .iter()
+        .any(|user| user.user == canonical && user.archived)
+    {
+        return Err(UserError::NotFound(format!(
+            "User '{}' is archived and isn't ranked",
+            canonical
+        )));
+    }
+
+    let (users, stats) = active_stats(guild_id)?;
+    let ranking = stats.ranking();
+    let Some(&(_, total)) = ranking.iter().find(|(name, _)| *name == canonical) else {
+        return Err(if users.iter().any(|user| user.user == canonical) {
+            UserError::NoGames(canonical)
+        } else {
+            UserError::UserNotFound(canonical)
+        });
+    };
+
+    let rank = ranking.iter().filter(|(_, other)| *other > total).count() + 1;
+    Ok((rank, ranking.len(), total))
+}
+
+// Saved leaderboards by name. A missing or unreadable file counts as none
+fn read_snapshots(path: &str) -> HashMap<String, Vec<(String, i64)>> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+// Function to save the current leaderboard under a name, replacing any snapshot that
+// already has it. Returns how many users it holds
+pub fn save_snapshot(guild_id: u64, name: &str) -> Result<usize> {
+    if name == "now" {
+        return Err(UserError::Rejected(
+            "'now' always means the live leaderboard, pick another name".to_string(),
+        ));
+    }
+
+    let leaderboard = get_cached_stats(guild_id)?.ranking();
+    let _store = lock_store();
+    let mut snapshots = read_snapshots(&snapshots_file(guild_id));
+    let count = leaderboard.len();
+    snapshots.insert(name.to_string(), leaderboard);
+    write_json_atomically(&snapshots_file(guild_id), &snapshots)?;
+
+    info!("Saved leaderboard snapshot '{}' with {} users", name, count);
+    Ok(count)
+}
+
+// A saved leaderboard, or the live one for "now"
+fn snapshot_board(
+    guild_id: u64,
+    snapshots: &HashMap<String, Vec<(String, i64)>>,
+    name: &str,
+) -> Result<Vec<(String, i64)>> {
+    if name == "now" {
+        return Ok(get_cached_stats(guild_id)?.ranking());
+    }
+
+    match snapshots.get(name) {
+        Some(board) => Ok(board.clone()),
+        None => Err(UserError::NotFound(format!("No snapshot named '{}'", name))),
+    }
+}
+
+// Every user on either board with their rank on each, in the order of the later board
+// followed by anyone who has dropped off it
+fn rank_changes(before: &[(String, i64)], after: &[(String, i64)]) -> Vec<RankChange> {
+    let rank_in = |board: &[(String, i64)], username: &str| {
+        board
+            .iter()
+            .position(|(name, _)| name == username)
+            .map(|index| index + 1)
+    };
+
+    let still_here = after.iter().map(|(username, _)| RankChange {
+        username: username.clone(),
+        before: rank_in(before, username),
+        after: rank_in(after, username),
+    });
+    let gone = before
+        .iter()
+        .filter(|(username, _)| rank_in(after, username).is_none())
+        .map(|(username, _)| RankChange {
+            username: username.clone(),
+            before: rank_in(before, username),
+            after: None,
+        });
+
+    still_here.chain(gone).collect()
+}
+
+// Function to compare the leaderboards in two snapshots. Either name can be "now" for
+// the live leaderboard
+pub fn leaderboard_diff(guild_id: u64, a: &str, b: &str) -> Result<Vec<RankChange>> {
+    let snapshots = read_snapshots(&snapshots_file(guild_id));
+    let before = snapshot_board(guild_id, &snapshots, a)?;
+    let after = snapshot_board(guild_id, &snapshots, b)?;
+    Ok(rank_changes(&before, &after))
+}
+
+// What a user has put in since a time. Refunds and other decreases don't count
+fn added_since(history: &[Transaction], username: &str, since: i64) -> i64 {
+    history
+        .iter()
+        .filter(|change| change.username == username && change.timestamp >= since)
+        .map(|change| change.delta.max(0))
+        .sum()
+}
+
+// Function to add up what a user has put in over the last window_secs, for spotting
+// bursts of spending
+pub fn recent_velocity(guild_id: u64, username: &str, window_secs: i64) -> Result<i64> {
+    let users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    Ok(added_since(
+        &load_history(guild_id)?,
+        &canonical,
+        now() - window_secs,
+    ))
+}
+
+// The server's offset from UTC, for deciding which part of the week a change fell in.
+// SHAMEBOT_UTC_OFFSET sets it in hours, like `-5` or `5.5`; unset means UTC
+fn local_offset() -> chrono::FixedOffset {
+    std::env::var("SHAMEBOT_UTC_OFFSET")
+        .ok()
+        .and_then(|hours| hours.trim().parse::<f64>().ok())
+        .and_then(|hours| chrono::FixedOffset::east_opt((hours * 3600.0).round() as i32))
+        .unwrap_or(chrono::FixedOffset::east_opt(0).unwrap())
+}
+
+// Function to add up what a user has added during one part of the week, from the
+// change log, with accounts linked to them rolled up. Someone with nothing logged in
+// it comes to zero
+pub fn spend_in_window(guild_id: u64, username: &str, window: SpendWindow) -> Result<i64> {
+    let users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    if !users.iter().any(|user| user.user == canonical) {
+        return Err(UserError::UserNotFound(canonical));
+    }
+
+    let offset = local_offset();
+    let spent = load_history(guild_id)?
+        .iter()
+        .filter(|change| resolve_username(&users, &change.username) == canonical)
+        .filter(|change| {
+            chrono::DateTime::from_timestamp(change.timestamp, 0)
+                .is_some_and(|time| window.contains(time.with_timezone(&offset)))
+        })
+        .map(|change| change.delta)
+        .sum();
+    Ok(spent)
+}
+
+// Function to add up what each user has logged since a time (Unix seconds), biggest
+// first with ties broken alphabetically. Users with nothing logged since then are left out
+pub fn spend_since(guild_id: u64, since: i64) -> Result<Vec<(String, i64)>> {
+    let mut spent: HashMap<String, i64> = HashMap::new();
+    for change in load_history(guild_id)?
+        .into_iter()
+        .filter(|change| change.timestamp >= since)
+    {
+        *spent.entry(change.username).or_default() += change.delta;
+    }
+
+    let mut spent: Vec<(String, i64)> = spent.into_iter().collect();
+    spent.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(spent)
+}
+
+// Function to rank users by how much they've added since a point in time rather than
+// all-time, from the transaction log. Linked accounts are rolled up, and anyone who hasn't
+// spent anything in the window (or has since been deleted) is left out
+pub fn get_leaderboard_since(
+    guild_id: u64,
+    since: chrono::DateTime<chrono::Utc>,
+) -> Result<Vec<(String, i64)>> {
+    let users = active_users(load_users(guild_id)?);
+
+    let mut ranking: Vec<(String, i64)> = Vec::new();
+    for (username, delta) in spend_since(guild_id, since.timestamp())? {
+        let canonical = resolve_username(&users, &username);
+        if !users.iter().any(|user| user.user == canonical) {
+            continue;
+        }
+        match ranking.iter_mut().find(|(name, _)| *name == canonical) {
+            Some((_, total)) => *total += delta,
+            None => ranking.push((canonical, delta)),
+        }
+    }
+
+    ranking.retain(|&(_, total)| total > 0);
+    ranking.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(ranking)
+}
+
+// Function to rank users from the smallest total up, with the same alphabetical tie-break.
+// Zero-game users are left out here too; they aren't spending, they just aren't playing
+pub fn get_cheapskates(guild_id: u64, limit: usize) -> Result<Vec<(String, i64)>> {
+    let mut cheapskates = active_stats(guild_id)?.1.ranking();
+    cheapskates.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+    cheapskates.truncate(limit);
+    Ok(cheapskates)
+}
+
+// Function to rank everyone who owns a game by what they've spent on it, with linked
+// accounts rolled up and the same ordering as get_leaderboard. The game matches ignoring
+// case, like every other game lookup
+pub fn get_game_leaderboard(guild_id: u64, game: &str) -> Result<Vec<(String, i64)>> {
+    let users = active_users(load_users(guild_id)?);
+
+    let mut leaderboard: Vec<(String, i64)> = canonical_groups(&users)
+        .into_iter()
+        .filter_map(|(canonical, members)| {
+            let totals: Vec<i64> = members
+                .iter()
+                .filter_map(|user| {
+                    stored_game_name(user, game)
+                        .and_then(|name| user.games.get(&name).map(|record| record.total))
+                })
+                .collect();
+            (!totals.is_empty()).then(|| (canonical, totals.iter().sum()))
+        })
+        .collect();
+
+    if leaderboard.is_empty() {
+        let suggestions = similar_games(&users, game);
+        return Err(UserError::NotFound(if suggestions.is_empty() {
+            format!("Nobody has game '{}'", game)
+        } else {
+            format!(
+                "Nobody has game '{}'. Did you mean {}?",
+                game,
+                suggestions
+                    .iter()
+                    .map(|name| format!("'{}'", name))
+                    .collect::<Vec<_>>()
+                    .join(" or ")
+            )
+        }));
+    }
+
+    leaderboard.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    Ok(leaderboard)
+}
+
+// Tracked game names that look like a typo or a different capitalization of the input
+fn similar_games(users: &[User], game: &str) -> Vec<String> {
+    let wanted = game.to_lowercase();
+
+    let mut similar: Vec<(usize, String)> = Vec::new();
+    for name in users.iter().flat_map(|user| user.games.keys()) {
+        let distance = fuzzy::levenshtein(&wanted, &name.to_lowercase());
+        if distance <= 3 && !similar.iter().any(|(_, seen)| seen == name) {
+            similar.push((distance, name.clone()));
+        }
+    }
+
+    similar.sort();
+    similar.truncate(3);
+    similar.into_iter().map(|(_, name)| name).collect()
+}
+
+// A user's total with each game's dollars multiplied by its weight (1.0 when unset),
+// rounded to whole shame points
+pub fn weighted_total(user: &User, weights: &HashMap<String, f64>) -> i64 {
+    let total: f64 = user
+        .games
+        .iter()
+        .map(|(game, record)| {
+            record.total as f64 / 100.0 * weights.get(game).copied().unwrap_or(1.0)
+        })
+        .sum();
+
+    total.round() as i64
+}
+
+// Function to rank users by weighted total, with the same grouping and ordering as get_leaderboard
+pub fn get_weighted_leaderboard(
+    guild_id: u64,
+    weights: &HashMap<String, f64>,
+    limit: usize,
+) -> Result<Vec<(String, i64)>> {
+    let users = active_users(load_users(guild_id)?);
+
+    let mut leaderboard: Vec<(String, i64)> = canonical_groups(&users)
+        .into_iter()
+        .map(|(canonical, members)| {
+            let total = members
+                .iter()
+                .map(|user| weighted_total(user, weights))
+                .sum();
+            (canonical, total)
+        })
+        .collect();
+
+    leaderboard.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    leaderboard.truncate(limit);
+    Ok(leaderboard)
+}
+
+// Function to opt a user out of (or back into) the scheduled daily poke
+pub fn set_poke_opt_out(guild_id: u64, username: &str, opt_out: bool) -> Result<()> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    let username = canonical.as_str();
+
+    match users.iter_mut().find(|user| user.user == username) {
+        Some(user) => user.poke_opt_out = opt_out,
+        None => {
+            return Err(UserError::UserNotFound(username.to_string()));
+        }
+    }
+
+    save_users(guild_id, &users)?;
+    info!("Set poke opt-out for '{}' to {}", username, opt_out);
+    Ok(())
+}
+
+// Function to archive (or bring back) a user. Archived users keep their games and
+// history but are left out of listings, rankings and stats
+fn set_archived(guild_id: u64, username: &str, archived: bool) -> Result<()> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    let username = canonical.as_str();
+
+    match users.iter_mut().find(|user| user.user == username) {
+        Some(user) if user.archived == archived => {
+            return Err(UserError::Rejected(if archived {
+                format!("User '{}' is already archived", username)
+            } else {
+                format!("User '{}' isn't archived", username)
+            }));
+        }
+        Some(user) => user.archived = archived,
+        None => {
+            return Err(UserError::UserNotFound(username.to_string()));
+        }
+    }
+
+    // The stats cache counts archived users too, so it stays right as it is
+    save_users_and_stats(guild_id, &users, |_| {})?;
+    info!("Set archived for '{}' to {}", username, archived);
+    Ok(())
+}
+
+pub fn archive_user(guild_id: u64, username: &str) -> Result<()> {
+    set_archived(guild_id, username, true)
+}
+
+pub fn unarchive_user(guild_id: u64, username: &str) -> Result<()> {
+    set_archived(guild_id, username, false)
+}
+
+// The biggest spender who hasn't opted out and has spent at least min_total
+fn poke_target(users: &[User], min_total: i64) -> Option<(String, i64)> {
+    StatsCache::from_users(users)
+        .ranking()
+        .into_iter()
+        .filter(|(_, total)| *total >= min_total)
+        .find(|(username, _)| {
+            users
+                .iter()
+                .any(|user| user.user == *username && !user.poke_opt_out)
+        })
+}
+
+// Function to pick who the scheduled poke should roast, if anyone qualifies
+pub fn pick_poke_target(guild_id: u64, min_total: i64) -> Result<Option<(String, i64)>> {
+    Ok(poke_target(&active_users(load_users(guild_id)?), min_total))
+}
+
+// The server's own troll threshold, or the default when it hasn't set one
+fn load_threshold(guild_id: u64) -> Result<i64> {
+    match db::get_setting(guild_id, "troll_threshold")? {
+        Some(value) => value.parse().map_err(|e| UserError::Database(Box::new(e))),
+        None => Ok(TROLL_THRESHOLD),
+    }
+}
+
+// Function to get the total that sets off the first callout in a server, in cents
+pub fn get_threshold(guild_id: u64) -> Result<i64> {
+    load_threshold(guild_id)
+}
+
+// Function to set the total that sets off the first callout in a server. `none` goes
+// back to the default. It has to stay under the super troll threshold so that one
+// still comes second. Returns the threshold now in effect, in cents
+pub fn set_threshold(guild_id: u64, amount: &str) -> Result<i64> {
+    let _store = lock_store();
+
+    if amount.eq_ignore_ascii_case("none") {
+        db::set_setting(guild_id, "troll_threshold", None)?;
+        info!("Reset troll threshold for server {}", guild_id);
+        return Ok(TROLL_THRESHOLD);
+    }
+
+    let threshold = parse_money(amount)
+        .ok_or_else(|| UserError::InvalidAmount("Invalid number for threshold".to_string()))?;
+    if threshold <= 0 || threshold >= SUPER_TROLL_THRESHOLD {
+        return Err(UserError::InvalidAmount(format!(
+            "Threshold must be more than {} and less than {}",
+            format_money(0),
+            format_money(SUPER_TROLL_THRESHOLD)
+        )));
+    }
+
+    db::set_setting(guild_id, "troll_threshold", Some(&threshold.to_string()))?;
+    info!(
+        "Set troll threshold for server {} to {}",
+        guild_id,
+        format_money(threshold)
+    );
+    Ok(threshold)
+}
+
+// The next shame threshold a total hasn't reached yet
+fn next_threshold(total: i64, troll_threshold: i64) -> Option<i64> {
+    [troll_threshold, SUPER_TROLL_THRESHOLD]
+        .into_iter()
+        .find(|&threshold| total < threshold)
+}
+
+// Updates adding more than this in one go ask to be confirmed first, since they're
+// usually typos. SHAMEBOT_CONFIRM_OVER sets it, like `200`; unset means never ask
+fn confirm_over(setting: Option<&str>) -> Option<i64> {
+    setting
+        .and_then(|limit| parse_money(limit.trim()))
+        .filter(|&limit| limit > 0)
+}
+
+// The limit an amount goes over, if adding it needs confirming
+pub fn needs_confirmation(additional: i64) -> Option<i64> {
+    let setting = std::env::var("SHAMEBOT_CONFIRM_OVER").ok();
+    confirm_over(setting.as_deref()).filter(|&limit| additional > limit)
+}
+
+// Function to find every (user, game, total, next threshold) within margin cents below
+// a shame threshold, closest to crossing first
+pub fn near_threshold(guild_id: u64, margin: i64) -> Result<Vec<(String, String, i64, i64)>> {
+    let users = active_users(load_users(guild_id)?);
+    let troll_threshold = load_threshold(guild_id)?;
+
+    let mut near: Vec<(String, String, i64, i64)> = users
+        .iter()
+        .flat_map(|user| {
+            user.games.iter().filter_map(move |(game, record)| {
+                next_threshold(record.total, troll_threshold)
+                    .map(|threshold| (user.user.clone(), game.clone(), record.total, threshold))
+            })
+        })
+        .filter(|(_, _, total, threshold)| threshold - total <= margin)
+        .collect();
+
+    near.sort_by_key(|(username, game, total, threshold)| {
+        (threshold - total, username.clone(), game.clone())
+    });
+    Ok(near)
+}
+
+// Function to find every (user, game, last updated) not changed in the last `days` days,
+// longest untouched first. Games with no recorded update count as untouched the longest
+pub fn inactive_since(guild_id: u64, days: u32) -> Result<Vec<(String, String, i64)>> {
+    let users = active_users(load_users(guild_id)?);
+    let cutoff = now() - i64::from(days) * 24 * 60 * 60;
+
+    let mut inactive: Vec<(String, String, i64)> = users
+        .iter()
+        .flat_map(|user| {
+            user.games
+                .iter()
+                .map(move |(game, record)| (user.user.clone(), game.clone(), record.last_updated))
+        })
+        .filter(|(_, _, last_updated)| *last_updated < cutoff)
+        .collect();
+
+    inactive.sort_by_key(|(username, game, last_updated)| {
+        (*last_updated, username.clone(), game.clone())
+    });
+    Ok(inactive)
+}
+
+// Function to set a hard spending cap on one of a user's games. None removes the cap,
+// and a cap has to be more than zero. Returns the cap that was set, in cents
+pub fn set_cap(
+    guild_id: u64,
+    username: &str,
+    game: &str,
+    cap: Option<&str>,
+) -> Result<Option<i64>> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    let username = canonical.as_str();
+    let stored_game = resolve_game(&users, username, game);
+    let game = stored_game.as_str();
+    let cap = match cap {
+        Some(cap) => Some(
+            parse_money(cap)
+                .ok_or_else(|| UserError::InvalidAmount("Invalid number for cap".to_string()))?,
+        ),
+        None => None,
+    };
+    if let Some(cap) = cap
+        && cap <= 0
+    {
+        return Err(UserError::InvalidAmount(
+            "Cap must be more than zero".to_string(),
+        ));
+    }
+
+    match users.iter_mut().find(|user| user.user == username) {
+        Some(user) => {
+            if !user.games.contains_key(game) {
+                return Err(missing_game_error(user, game));
+            }
+
+            match cap {
+                Some(cap) => user.caps.insert(game.to_string(), cap),
+                None => user.caps.remove(game),
+            };
+        }
+        None => {
+            return Err(UserError::UserNotFound(username.to_string()));
+        }
+    }
+
+    save_users(guild_id, &users)?;
+    info!("Set cap for {}'s {} to {:?}", username, game, cap);
+    Ok(cap)
+}
+
+// Function to set a spending goal on one of a user's games. Unlike a cap, going past it
+// is allowed but pointed out. None removes the goal. Returns the goal that was set, in cents
+pub fn set_goal(
+    guild_id: u64,
+    username: &str,
+    game: &str,
+    goal: Option<&str>,
+) -> Result<Option<i64>> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    let username = canonical.as_str();
+    let stored_game = resolve_game(&users, username, game);
+    let game = stored_game.as_str();
+    let goal = match goal {
+        Some(goal) => Some(parse_money(goal).filter(|&goal| goal > 0).ok_or_else(|| {
+            UserError::InvalidAmount("Goal must be a number above zero".to_string())
+        })?),
+        None => None,
+    };
+
+    match users.iter_mut().find(|user| user.user == username) {
+        Some(user) => {
+            if !user.games.contains_key(game) {
+                return Err(missing_game_error(user, game));
+            }
+
+            match goal {
+                Some(goal) => user.goals.insert(game.to_string(), goal),
+                None => user.goals.remove(game),
+            };
+        }
+        None => {
+            return Err(UserError::UserNotFound(username.to_string()));
+        }
+    }
+
+    save_users(guild_id, &users)?;
+    info!("Set goal for {}'s {} to {:?}", username, game, goal);
+    Ok(goal)
+}
+
+// Tags are single lowercase words, so `Gacha` and `gacha` are the same category
+fn normalize_tag(tag: &str) -> Result<String> {
+    let tag = tag.trim().to_lowercase();
+    if tag.is_empty() || tag.contains(char::is_whitespace) {
+        return Err(UserError::InvalidName(
+            "Tags must be a single word".to_string(),
+        ));
+    }
+    check_name_length("Tag", &tag)?;
+    Ok(tag)
+}
+
+// Function to add (or with add false, remove) a category tag on one of a user's games.
+// Returns the game's name as stored
+fn change_tag(guild_id: u64, username: &str, game: &str, tag: &str, add: bool) -> Result<String> {
+    let _store = lock_store();
+    let tag = normalize_tag(tag)?;
+    let mut users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    let username = canonical.as_str();
+    let stored_game = resolve_game(&users, username, game);
+    let game = stored_game.as_str();
+
+    let Some(user) = users.iter_mut().find(|user| user.user == username) else {
+        return Err(UserError::UserNotFound(username.to_string()));
+    };
+    if !user.games.contains_key(game) {
+        return Err(missing_game_error(user, game));
+    }
+
+    let tags = user.tags.entry(game.to_string()).or_default();
+    let tagged = tags.contains(&tag);
+    if add {
+        if tagged {
+            return Err(UserError::Rejected(format!(
+                "'{}' is already tagged '{}'",
+                game, tag
+            )));
+        }
+        tags.push(tag.clone());
+    } else {
+        if !tagged {
+            return Err(UserError::NotFound(format!(
+                "'{}' isn't tagged '{}'",
+                game, tag
+            )));
+        }
+        tags.retain(|existing| *existing != tag);
+        if tags.is_empty() {
+            user.tags.remove(game);
+        }
+    }
+
+    save_users(guild_id, &users)?;
+    info!(
+        "{} tag '{}' on {}'s {}",
+        if add { "Added" } else { "Removed" },
+        tag,
+        username,
+        game
+    );
+    Ok(stored_game)
+}
+
+pub fn tag_game(guild_id: u64, username: &str, game: &str, tag: &str) -> Result<String> {
+    change_tag(guild_id, username, game, tag, true)
+}
+
+pub fn untag_game(guild_id: u64, username: &str, game: &str, tag: &str) -> Result<String> {
+    change_tag(guild_id, username, game, tag, false)
+}
+
+// Function to get the tags on each of a user's games (games without tags are left out)
+pub fn get_user_tags(guild_id: u64, username: &str) -> Result<HashMap<String, Vec<String>>> {
+    let users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+
+    match users.iter().find(|user| user.user == canonical) {
+        Some(user) => Ok(user.tags.clone()),
+        None => Err(UserError::UserNotFound(canonical)),
+    }
+}
+
+// Function to add up every game tagged with a category, across the whole server.
+// Archived users are left out, like everywhere else totals are summed
+pub fn get_category_total(guild_id: u64, tag: &str) -> Result<i64> {
+    let tag = normalize_tag(tag)?;
+    let users = active_users(load_users(guild_id)?);
+
+    let mut found = false;
+    let mut total = 0;
+    for user in &users {
+        for (game, record) in &user.games {
+            if user.tags.get(game).is_some_and(|tags| tags.contains(&tag)) {
+                found = true;
+                total += record.total;
+            }
+        }
+    }
+
+    if !found {
+        return Err(UserError::NotFound(format!(
+            "No games are tagged '{}'",
+            tag
+        )));
+    }
+    Ok(total)
+}
+
+// Function to get the spending goal on one of a user's games, if there is one
+pub fn get_goal(guild_id: u64, username: &str, game: &str) -> Result<Option<i64>> {
+    let users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    let stored_game = resolve_game(&users, &canonical, game);
+
+    match users.iter().find(|user| user.user == canonical) {
+        Some(user) => Ok(user.goals.get(&stored_game).copied()),
+        None => Err(UserError::UserNotFound(canonical)),
+    }
+}
+
+// How far a total is through its goal, as a whole percentage. Can go past 100
+pub fn goal_percent(total: i64, goal: i64) -> i64 {
+    if goal <= 0 {
+        return 100;
+    }
+    total.max(0) * 100 / goal
+}
+
+// Function to set (or with None, clear) the name a user is shown as
+pub fn set_display_name(guild_id: u64, username: &str, display_name: Option<&str>) -> Result<()> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+    let username = canonical.as_str();
+
+    match users.iter_mut().find(|user| user.user == username) {
+        Some(user) => user.display_name = display_name.map(str::to_string),
+        None => {
+            return Err(UserError::UserNotFound(username.to_string()));
+        }
+    }
+
+    save_users(guild_id, &users)?;
+    info!("Set display name for '{}' to {:?}", username, display_name);
+    Ok(())
+}
+
+// Function to get the name to show for a user. Unknown users are shown as typed
+pub fn get_display_name(guild_id: u64, username: &str) -> String {
+    let Ok(users) = load_users(guild_id) else {
+        return username.to_string();
+    };
+    let canonical = resolve_username(&users, username);
+
+    match users.iter().find(|user| user.user == canonical) {
+        Some(user) => user.display_name().to_string(),
+        None => username.to_string(),
+    }
+}
+
+// A consent request goes stale after a day without an answer, in seconds
+pub const CONSENT_EXPIRY: i64 = 24 * 60 * 60;
+
+// Tracking that's waiting on someone's !consent yes
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PendingConsent {
+    pub discord_id: u64, // who was asked, and who has to answer
+    pub guild_id: u64,   // the server the tracking is for
+    pub username: String,
+    pub game: String,
+    pub total: String, // as typed, checked when the data is written
+    pub requested_at: i64,
+}
+
+// How a !consent answer turned out
+#[derive(Debug, PartialEq)]
+pub enum ConsentAnswer {
+    Accepted(PendingConsent), // the data has been written
+    Declined(PendingConsent), // the request is gone and nothing was written
+    Expired,
+    NotAsked,
+}
+
+// Outstanding requests by the Discord id of the person asked. A missing or unreadable
+// file counts as none
+fn load_pending_consent() -> HashMap<u64, PendingConsent> {
+    std::fs::read_to_string(consent_file())
+        .ok()
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default()
+}
+
+// Function to hold some tracking until the person it's for consents. A newer request
+// for the same person replaces theirs, and stale requests are dropped along the way
+pub fn request_consent(request: PendingConsent) -> Result<()> {
+    let _store = lock_store();
+    let mut pending = load_pending_consent();
+    pending.retain(|_, waiting| request.requested_at - waiting.requested_at < CONSENT_EXPIRY);
+
+    info!(
+        "Asked {} for consent to track '{}'",
+        request.discord_id, request.username
+    );
+    pending.insert(request.discord_id, request);
+    write_json_atomically(&consent_file(), &pending)?;
+    Ok(())
+}
+
+// Function to answer someone's consent request. Accepting writes the held data and
+// marks the user as consented; declining or letting it expire writes nothing
+pub fn answer_consent(discord_id: u64, accept: bool, now: i64) -> Result<ConsentAnswer> {
+    let store = lock_store();
+    let mut pending = load_pending_consent();
+    let Some(request) = pending.remove(&discord_id) else {
+        return Ok(ConsentAnswer::NotAsked);
+    };
+    write_json_atomically(&consent_file(), &pending)?;
+    // add_game and add_user take the lock themselves
+    drop(store);
+
+    if now - request.requested_at >= CONSENT_EXPIRY {
+        return Ok(ConsentAnswer::Expired);
+    }
+    if !accept {
+        info!("'{}' declined to be tracked", request.username);
+        return Ok(ConsentAnswer::Declined(request));
+    }
+
+    let guild_id = request.guild_id;
+    let users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, &request.username);
+    if users.iter().any(|user| user.user == canonical) {
+        add_game(guild_id, &request.username, &request.game, &request.total)?;
+    } else {
+        add_user(guild_id, &request.username, &request.game, &request.total)?;
+    }
+
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    if let Some(user) = users.iter_mut().find(|user| user.user == canonical) {
+        user.consented = true;
+    }
+    save_users(guild_id, &users)?;
+
+    info!("'{}' consented to be tracked", request.username);
+    Ok(ConsentAnswer::Accepted(request))
+}
+
+// Whether a user has agreed to be tracked. Unknown users haven't
+pub fn has_consented(guild_id: u64, username: &str) -> bool {
+    let Ok(users) = load_users(guild_id) else {
+        return false;
+    };
+    let canonical = resolve_username(&users, username);
+    users
+        .iter()
+        .any(|user| user.user == canonical && user.consented)
+}
+
+// Longest raw dump that still fits in a Discord message with its code block
+const DEBUG_DUMP_LIMIT: usize = 1800;
+
+// Function to dump a user's record exactly as it's stored, for maintainers.
+// Looks up the raw record, so an alias shows its own link rather than the canonical user
+pub fn debug_dump(guild_id: u64, username: &str) -> Result<String> {
+    let users = load_users(guild_id)?;
+    let stored = stored_username(&users, username);
+    let username = stored.as_str();
+
+    let user = users
+        .iter()
+        .find(|user| user.user == username)
+        .ok_or_else(|| UserError::UserNotFound(username.to_string()))?;
+
+    let mut dump = serde_json::to_string_pretty(user)?;
+    if dump.len() > DEBUG_DUMP_LIMIT {
+        let mut cut = DEBUG_DUMP_LIMIT;
+        while !dump.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        dump.truncate(cut);
+        dump.push_str("\n... (truncated)");
+    }
+
+    Ok(format!("```json\n{}\n```", dump))
+}
+
+// What merge_data_files did
+#[derive(Debug, Default, PartialEq)]
+pub struct MergeReport {
+    pub users_merged: usize,      // users in both files, combined into one
+    pub users_copied: usize,      // users only in one file, copied as-is
+    pub games_conflicting: usize, // games both files had for the same user, totals summed
+}
+
+// Read a users file strictly: unlike load_user_file, a missing file is an error too
+fn read_users_file(path: &str) -> Result<Vec<User>> {
+    let contents =
+        std::fs::read_to_string(path).map_err(io_error(format!("Couldn't read '{}'", path)))?;
+
+    if contents.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut users: Vec<User> = serde_json::from_str(&contents)
+        .map_err(serde_error(format!("Couldn't parse '{}'", path)))?;
+    migrate_to_cents(&mut users);
+    Ok(users)
+}
+
+// Function to combine two users files into a third. Users in both files are merged,
+// summing the totals of games they both have. Where both set the same cap, goal, tags,
+// display name or Discord account, or differ on archived, the first file wins. A dry
+// run reports without writing anything
+pub fn merge_data_files(
+    path_a: &str,
+    path_b: &str,
+    out: &str,
+    dry_run: bool,
+) -> Result<MergeReport> {
+    let mut merged = read_users_file(path_a)?;
+    let other = read_users_file(path_b)?;
+    let mut report = MergeReport::default();
+
+    for incoming in other {
+        match merged.iter_mut().find(|user| user.user == incoming.user) {
+            Some(existing) => {
+                for (game, record) in incoming.games {
+                    match existing.games.get_mut(&game) {
+                        Some(existing_record) => {
+                            existing_record.total += record.total;
+                            existing_record.last_updated =
+                                existing_record.last_updated.max(record.last_updated);
+                            report.games_conflicting += 1;
+                        }
+                        None => {
+                            existing.games.insert(game, record);
+                        }
+                    }
+                }
+
+                // Caps, goals and tags are per game, so the other file fills in the
+                // games this one has none for. Single values are only taken when this
+                // file has none, and flags like archived stay as they are here
+                for (game, cap) in incoming.caps {
+                    existing.caps.entry(game).or_insert(cap);
+                }
+                for (game, goal) in incoming.goals {
+                    existing.goals.entry(game).or_insert(goal);
+                }
+                for (game, tags) in incoming.tags {
+                    existing.tags.entry(game).or_insert(tags);
+                }
+                if existing.alias_of.is_none() {
+                    existing.alias_of = incoming.alias_of;
+                }
+                if existing.display_name.is_none() {
+                    existing.display_name = incoming.display_name;
+                }
+                if existing.discord_id.is_none() {
+                    existing.discord_id = incoming.discord_id;
+                }
+                report.users_merged += 1;
+            }
+            None => merged.push(incoming),
+        }
+    }
+
+    report.users_copied = merged.len() - report.users_merged;
+
+    if dry_run {
+        return Ok(report);
+    }
+
+    write_json_atomically(out, &merged)?;
+
+    info!(
+        "Merged '{}' and '{}' into '{}': {:?}",
+        path_a, path_b, out, report
+    );
+    Ok(report)
+}
+
+// Function to write every user to a JSON file
+pub fn export_users(guild_id: u64, path: &str) -> Result<usize> {
+    let users = load_users(guild_id)?;
+    write_json_atomically(path, &users)?;
+    Ok(users.len())
+}
+
+// Function to wipe every user, game and logged change in a server, e.g. for a new
+// season. Server settings like the threshold are kept
+pub fn reset_all(guild_id: u64) -> Result<()> {
+    let _store = lock_store();
+    save_users(guild_id, &[])?;
+    db::clear_transactions(guild_id)?;
+
+    info!("Reset all data for server {}", guild_id);
+    Ok(())
+}
+
+// Function to snapshot a server's users to a timestamped JSON file, e.g. before
+// something risky. Returns the path of the new file
+pub fn backup(guild_id: u64) -> Result<String> {
+    let users = load_users(guild_id)?;
+
+    let dir = backup_dir(guild_id);
+    std::fs::create_dir_all(&dir).map_err(io_error(format!("Couldn't create '{}'", dir)))?;
+
+    let stamp = chrono::Utc::now().format("%Y%m%d-%H%M%S");
+    let path = format!("{}/users-{}.json", dir, stamp);
+    write_json_atomically(&path, &users)?;
+
+    info!("Backed up {} users to '{}'", users.len(), path);
+    Ok(path)
+}
+
+// Function to list a server's backups by file name, newest first
+pub fn list_backups(guild_id: u64) -> Result<Vec<String>> {
+    let dir = backup_dir(guild_id);
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(io_error(format!("Couldn't read '{}'", dir))(e)),
+    };
+
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|name| name.starts_with("users-") && name.ends_with(".json"))
+        .collect();
+
+    // The timestamps sort the same way as the times they stand for
+    names.sort_by(|a, b| b.cmp(a));
+    Ok(names)
+}
+
+// Function to replace a server's users with one of its backups. The backup is read
+// and parsed in full first, so a corrupt one leaves the live data alone
+pub fn restore(guild_id: u64, backup_name: &str) -> Result<usize> {
+    let _store = lock_store();
+
+    // Only bare file names, so a restore can't reach outside the server's backups
+    if backup_name.is_empty() || backup_name.contains(['/', '\\']) || backup_name.starts_with('.') {
+        return Err(UserError::InvalidName(format!(
+            "'{}' isn't a backup name",
+            backup_name
+        )));
+    }
+
+    let path = format!("{}/{}", backup_dir(guild_id), backup_name);
+    if !std::path::Path::new(&path).is_file() {
+        return Err(UserError::NotFound(format!(
+            "No backup named '{}'. Use !restore to list them",
+            backup_name
+        )));
+    }
+
+    let users = read_users_file(&path)?;
+    save_users(guild_id, &users)?;
+
+    info!("Restored {} users from '{}'", users.len(), path);
+    Ok(users.len())
+}
+
+// Quote a CSV field when it holds a comma, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// Function to write out one user's data as JSON or CSV, games sorted by name
+pub fn export_user(guild_id: u64, username: &str, format: ExportFormat) -> Result<String> {
+    let users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+
+    let user = users
+        .iter()
+        .find(|user| user.user == canonical)
+        .ok_or_else(|| UserError::UserNotFound(canonical.to_string()))?;
+
+    match format {
+        // Through a Value so the games come out sorted
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(&serde_json::to_value(user)?)?),
+        ExportFormat::Csv => {
+            let mut games: Vec<(&String, &i64)> = user
+                .games
+                .iter()
+                .map(|(game, record)| (game, &record.total))
+                .collect();
+            games.sort();
+
+            let mut csv = String::from("game,total\n");
+            for (game, total) in games {
+                let sign = if *total < 0 { "-" } else { "" };
+                let cents = total.unsigned_abs();
+                csv.push_str(&format!(
+                    "{},{}{}.{:02}\n",
+                    csv_field(game),
+                    sign,
+                    cents / 100,
+                    cents % 100
+                ));
+            }
+            Ok(csv)
+        }
+    }
+}
+
+// Split one CSV row into its fields, undoing the quoting csv_field adds.
+// None when a quoted field is never closed
+fn parse_csv_row(line: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return None;
+    }
+    fields.push(field);
+    Some(fields)
+}
+
+// Function to set a user's games from `game,total` CSV lines, adding new games and
+// overwriting the totals of ones they have. Bad rows are skipped and reported by line
+// number rather than stopping the import. Each change is logged like an update, so
+// history and undo cover it. A dry run reports the same without saving or logging
+// anything. Returns the number imported and those problems
+pub fn import_games(
+    guild_id: u64,
+    username: &str,
+    csv: &str,
+    dry_run: bool,
+) -> Result<(usize, Vec<String>)> {
+    let _store = lock_store();
+    let mut users = load_users(guild_id)?;
+    let canonical = resolve_username(&users, username);
+
+    let user = users
+        .iter_mut()
+        .find(|user| user.user == canonical)
+        .ok_or_else(|| UserError::UserNotFound(canonical.to_string()))?;
+
+    let mut imported = 0;
+    let mut changes = Vec::new();
+    let mut problems = Vec::new();
+
+    for (index, line) in csv.lines().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+
+        // Blank lines and the header !export writes are fine to skip
+        if line.is_empty() || (line_number == 1 && line.eq_ignore_ascii_case("game,total")) {
+            continue;
+        }
+
+        let fields = match parse_csv_row(line) {
+            Some(fields) if fields.len() == 2 => fields,
+            Some(_) => {
+                problems.push(format!("Line {}: expected `game,total`", line_number));
+                continue;
+            }
+            None => {
+                problems.push(format!("Line {}: unmatched quote", line_number));
+                continue;
+            }
+        };
+
+        let game = fields[0].trim();
+        if let Err(e) = validate_game_name(game) {
+            problems.push(format!("Line {}: {}", line_number, e));
+            continue;
+        }
+        let Some(total) = parse_money(fields[1].trim()) else {
+            problems.push(format!(
+                "Line {}: invalid total '{}'",
+                line_number,
+                fields[1].trim()
+            ));
+            continue;
+        };
+        if total < 0 {
+            problems.push(format!("Line {}: total can't be negative", line_number));
+            continue;
+        }
+
+        let game = stored_game_name(user, game).unwrap_or_else(|| game.to_string());
+        let old_total = match user.games.get_mut(&game) {
+            Some(record) => {
+                let old_total = record.total;
+                record.set_total(total);
+                old_total
+            }
+            None => {
+                user.games.insert(game.clone(), GameRecord::new(total));
+                0
+            }
+        };
+        if total != old_total {
+            changes.push((game, total - old_total, total));
+        }
+        imported += 1;
+    }
+
+    if imported > 0 && !dry_run {
+        save_users(guild_id, &users)?;
+        let timestamp = now();
+        for (game, delta, total) in changes {
+            db::record_transaction(
+                guild_id,
+                &Transaction {
+                    timestamp,
+                    username: canonical.clone(),
+                    game,
+                    delta,
+                    total,
+                    undoes: None,
+                },
+            )?;
+        }
+        info!("Imported {} games for user '{}'", imported, canonical);
+    }
+    Ok((imported, problems))
+}
+
+// How import_users settles a game a user already has
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    Replace, // the imported total wins
+    Sum,     // the imported total is added on
+    KeepMax, // whichever total is bigger is kept
+}
+
+impl std::str::FromStr for MergeStrategy {
+    type Err = String;
+
+    fn from_str(name: &str) -> std::result::Result<Self, Self::Err> {
+        match name.to_lowercase().as_str() {
+            "replace" => Ok(MergeStrategy::Replace),
+            "sum" => Ok(MergeStrategy::Sum),
+            "max" | "keepmax" => Ok(MergeStrategy::KeepMax),
+            _ => Err(format!(
+                "Unknown merge strategy '{}' (use replace, sum or max)",
+                name
+            )),
+        }
+    }
+}
+
+// What import_users did
+#[derive(Debug, Default, PartialEq)]
+pub struct ImportReport {
+    pub imported: usize,        // users read from the file
+    pub conflicts: Vec<String>, // how each game a user already had was settled
+}
+
+// The total a game ends up with when an import meets one the user already has
+fn merge_totals(existing: i64, imported: i64, strategy: MergeStrategy) -> i64 {
+    match strategy {
+        MergeStrategy::Replace => imported,
+        MergeStrategy::Sum => existing.saturating_add(imported),
+        MergeStrategy::KeepMax => existing.max(imported),
+    }
+}
+
+fn describe_merge(
+    username: &str,
+    game: &str,
+    existing: i64,
+    imported: i64,
+    merged: i64,
+    strategy: MergeStrategy,
+) -> String {
+    match strategy {
+        MergeStrategy::Replace => format!(
+            "{} / {}: replaced {} with {}",
+            username,
+            game,
+            format_money(existing),
+            format_money(imported)
+        ),
+        MergeStrategy::Sum => format!(
+            "{} / {}: added {} to {}, now {}",
+            username,
+            game,
+            format_money(imported),
+            format_money(existing),
+            format_money(merged)
+        ),
+        MergeStrategy::KeepMax => format!(
+            "{} / {}: kept {} over {}",
+            username,
+            game,
+            format_money(merged),
+            format_money(existing.min(imported))
+        ),
+    }
+}
+
+// Function to load users from a JSON file into the current ones. New users and games
+// come in as they are, and a game a user already has is settled by the merge strategy.
+// Users only in the current data are kept. Each changed total is logged like an update.
+// The file is parsed in full first, so a bad file leaves the current data alone.
+// A dry run works out the same report without writing anything
+pub fn import_users(
+    guild_id: u64,
+    path: &str,
+    strategy: MergeStrategy,
+    dry_run: bool,
+) -> Result<ImportReport> {
+    let _store = lock_store();
+    let incoming = read_users_file(path)?;
+    let mut users = load_users(guild_id)?;
+    let mut report = ImportReport::default();
+    let mut changes = Vec::new();
+
+    for imported in incoming {
+        report.imported += 1;
+        let Some(existing) = users.iter_mut().find(|user| user.user == imported.user) else {
+            for (game, record) in &imported.games {
+                let total = record.total;
+                changes.push((imported.user.clone(), game.clone(), total, total));
+            }
+            users.push(imported);
+            continue;
+        };
+
+        for (game, record) in imported.games {
+            let total = record.total;
+            let old_total = existing.games.get(&game).map(|record| record.total);
+            let merged = match old_total {
+                Some(old_total) => {
+                    let merged = merge_totals(old_total, total, strategy);
+                    if total != old_total {
+                        report.conflicts.push(describe_merge(
+                            &existing.user,
+                            &game,
+                            old_total,
+                            total,
+                            merged,
+                            strategy,
+                        ));
+                    }
+                    merged
+                }
+                None => total,
+            };
+
+            let delta = merged - old_total.unwrap_or(0);
+            if delta != 0 {
+                changes.push((existing.user.clone(), game.clone(), delta, merged));
+            }
+            if let Some(cap) = imported.caps.get(&game) {
+                existing.caps.entry(game.clone()).or_insert(*cap);
+            }
+            if let Some(goal) = imported.goals.get(&game) {
+                existing.goals.entry(game.clone()).or_insert(*goal);
+            }
+            for tag in imported.tags.get(&game).into_iter().flatten() {
+                let tags = existing.tags.entry(game.clone()).or_default();
+                if !tags.contains(tag) {
+                    tags.push(tag.clone());
+                }
+            }
+            // A game the user already has keeps its own timestamps
+            match existing.games.get_mut(&game) {
+                Some(existing_record) if existing_record.total != merged => {
+                    existing_record.set_total(merged)
+                }
+                Some(_) => {}
+                None => {
+                    existing.games.insert(game, record);
+                }
+            }
+        }
+    }
+
+    if dry_run {
+        return Ok(report);
+    }
+
+    save_users(guild_id, &users)?;
+    let timestamp = now();
+    for (username, game, delta, total) in changes {
+        record_transaction(
+            guild_id,
+            Transaction {
+                timestamp,
+                username,
+                game,
+                delta,
+                total,
+                undoes: None,
+            },
+        )?;
+    }
+
+    info!(
+        "Imported {} users from '{}' ({:?}, {} conflicts)",
+        report.imported,
+        path,
+        strategy,
+        report.conflicts.len()
+    );
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linked_accounts_act_on_the_canonical_user() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "100").unwrap();
+        link_account(TEST_GUILD, "Qalt", "Q").unwrap();
+
+        update_total(TEST_GUILD, "Qalt", "Tekken 8", "10", false).unwrap();
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            11000
+        );
+        assert_eq!(get_user_total_all_games(TEST_GUILD, "Qalt").unwrap(), 11000);
+
+        // A link can't point back at itself, and unlinking drops an empty alias
+        assert!(link_account(TEST_GUILD, "Q", "Qalt").is_err());
+        unlink_account(TEST_GUILD, "Qalt").unwrap();
+        assert!(
+            get_users(TEST_GUILD, true)
+                .unwrap()
+                .iter()
+                .all(|user| user.user != "Qalt")
+        );
+    }
+
+    #[test]
+    fn removing_games_by_pattern_keeps_the_user() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 7", "10").unwrap();
+        add_game(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_game(TEST_GUILD, "Q", "Street Fighter 6", "10").unwrap();
+
+        assert_eq!(
+            find_games_matching(TEST_GUILD, "Q", "Tekken ?")
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            find_games_matching(TEST_GUILD, "Q", "Fighter")
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            remove_games_matching(TEST_GUILD, "Q", "Tekken*").unwrap(),
+            ["Tekken 7", "Tekken 8"]
+        );
+        assert!(remove_games_matching(TEST_GUILD, "Q", "Tekken*").is_err());
+
+        remove_games_matching(TEST_GUILD, "Q", "*").unwrap();
+        assert!(get_user_games(TEST_GUILD, "Q").unwrap().is_empty());
+    }
+
+    #[test]
+    fn cached_stats_match_a_full_recompute() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "100").unwrap();
+        add_game(TEST_GUILD, "Q", "Street Fighter 6", "20").unwrap();
+        add_user(TEST_GUILD, "Z", "Tekken 8", "50").unwrap();
+        let before = get_cached_stats(TEST_GUILD).unwrap();
+        assert_eq!(before.grand_total, 17000);
+
+        // The update is applied to the cached stats rather than rebuilt from scratch
+        update_total(TEST_GUILD, "Z", "Tekken 8", "80", false).unwrap();
+        update_total(TEST_GUILD, "Q", "Street Fighter 6", "-5", false).unwrap();
+        let cached = get_cached_stats(TEST_GUILD).unwrap();
+        assert_eq!(
+            cached,
+            StatsCache::from_users(&load_users(TEST_GUILD).unwrap())
+        );
+        assert_eq!(cached.game_totals["Tekken 8"], 23000);
+        assert_eq!(
+            cached.ranking(),
+            [("Z".to_string(), 13000), ("Q".to_string(), 11500)]
+        );
+
+        // A save the cache can't follow drops it, so the stats are rebuilt
+        delete_user(TEST_GUILD, "Z").unwrap();
+        assert_eq!(get_cached_stats(TEST_GUILD).unwrap().grand_total, 11500);
+    }
+
+    #[test]
+    fn leaderboard_puts_the_biggest_spender_first() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "100").unwrap();
+        add_user(TEST_GUILD, "Z", "Tekken 8", "50").unwrap();
+        add_game(TEST_GUILD, "Z", "Street Fighter 6", "50").unwrap();
+        add_user(TEST_GUILD, "A", "Tekken 8", "10").unwrap();
+
+        // Q and Z tie on 100, so they go alphabetically
+        assert_eq!(
+            get_leaderboard(TEST_GUILD, 2).unwrap(),
+            [("Q".to_string(), 10000), ("Z".to_string(), 10000)]
+        );
+        assert_eq!(get_leaderboard(TEST_GUILD, 10).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn game_leaderboard_ranks_owners_and_suggests_close_names() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "30").unwrap();
+        add_user(TEST_GUILD, "Z", "Tekken 8", "50").unwrap();
+        add_user(TEST_GUILD, "A", "Street Fighter 6", "90").unwrap();
+
+        assert_eq!(
+            get_game_leaderboard(TEST_GUILD, "Tekken 8").unwrap(),
+            [("Z".to_string(), 5000), ("Q".to_string(), 3000)]
+        );
+
+        let error = get_game_leaderboard(TEST_GUILD, "Tekken 9")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            error,
+            "Nobody has game 'Tekken 9'. Did you mean 'Tekken 8'?"
+        );
+        assert_eq!(
+            get_game_leaderboard(TEST_GUILD, "Halo")
+                .unwrap_err()
+                .to_string(),
+            "Nobody has game 'Halo'"
+        );
+    }
+
+    #[test]
+    fn names_match_regardless_of_case() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_user(TEST_GUILD, "Z", "TEKKEN 8", "20").unwrap();
+
+        update_total(TEST_GUILD, "q", "tekken 8", "5", false).unwrap();
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "TeKKeN 8").unwrap().total,
+            1500
+        );
+        assert_eq!(
+            get_game_leaderboard(TEST_GUILD, "tekken 8").unwrap(),
+            [("Z".to_string(), 2000), ("Q".to_string(), 1500)]
+        );
+
+        // The name stays stored as it was first typed, and case variants are refused
+        assert!(
+            load_users(TEST_GUILD).unwrap()[0]
+                .games
+                .contains_key("Tekken 8")
+        );
+        assert_eq!(
+            add_game(TEST_GUILD, "Q", "tekken 8", "1")
+                .unwrap_err()
+                .to_string(),
+            "User Q already has game 'Tekken 8'"
+        );
+        assert!(add_user(TEST_GUILD, "q", "Halo", "1").is_err());
+
+        remove_game(TEST_GUILD, "Q", "TEKKEN 8").unwrap();
+        assert!(get_game_record(TEST_GUILD, "Q", "Tekken 8").is_err());
+    }
+
+    #[test]
+    fn renaming_a_user_keeps_their_games_links_and_history() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_game(TEST_GUILD, "Q", "Halo", "20").unwrap();
+        update_total(TEST_GUILD, "Q", "Halo", "5", false).unwrap();
+        add_user(TEST_GUILD, "Couch", "Mario Kart", "1").unwrap();
+        link_account(TEST_GUILD, "Couch", "Q").unwrap();
+        add_user(TEST_GUILD, "Bob", "Halo", "1").unwrap();
+
+        rename_user(TEST_GUILD, "Q", "Quinn").unwrap();
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Quinn", "Tekken 8")
+                .unwrap()
+                .total,
+            1000
+        );
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Quinn", "Halo").unwrap().total,
+            2500
+        );
+        assert!(get_game_record(TEST_GUILD, "Q", "Halo").is_err());
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Couch", "Halo").unwrap().total,
+            2500
+        );
+        assert_eq!(get_history(TEST_GUILD, "Quinn", None).unwrap().len(), 1);
+
+        assert_eq!(
+            rename_user(TEST_GUILD, "Nobody", "Someone")
+                .unwrap_err()
+                .to_string(),
+            "User 'Nobody' not found"
+        );
+        assert_eq!(
+            rename_user(TEST_GUILD, "Quinn", "bob")
+                .unwrap_err()
+                .to_string(),
+            "User 'bob' already exists"
+        );
+        rename_user(TEST_GUILD, "Quinn", "QUINN").unwrap();
+        assert_eq!(
+            get_game_record(TEST_GUILD, "QUINN", "Tekken 8")
+                .unwrap()
+                .total,
+            1000
+        );
+    }
+
+    #[test]
+    fn servers_keep_their_own_users_stats_and_snapshots() {
+        let _store = fresh_store();
+        let other = TEST_GUILD + 1;
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_user(other, "Q", "Halo", "30").unwrap();
+        save_snapshot(TEST_GUILD, "monday").unwrap();
+
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            1000
+        );
+        assert!(get_game_record(other, "Q", "Tekken 8").is_err());
+        assert_eq!(get_cached_stats(TEST_GUILD).unwrap().grand_total, 1000);
+        assert_eq!(get_cached_stats(other).unwrap().grand_total, 3000);
+
+        // A change in one server leaves the other's cached stats alone
+        update_total(other, "Q", "Halo", "5", false).unwrap();
+        assert_eq!(get_cached_stats(TEST_GUILD).unwrap().grand_total, 1000);
+        assert!(leaderboard_diff(TEST_GUILD, "monday", "now").is_ok());
+        assert!(leaderboard_diff(other, "monday", "now").is_err());
+    }
+
+    #[test]
+    fn only_the_first_server_takes_over_the_old_data() {
+        let _store = fresh_store();
+        std::fs::write(
+            users_file(),
+            r#"[{"user": "Q", "games": {"Tekken 8": 1000}, "in_cents": true}]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            1000
+        );
+        assert!(get_users(TEST_GUILD + 1, true).unwrap().is_empty());
+        assert!(!std::path::Path::new(&users_file()).exists());
+        assert!(std::path::Path::new(&format!("{}.imported", users_file())).exists());
+
+        // The old single database wins over users.json, and is moved rather than copied
+        std::fs::rename(db::db_path(TEST_GUILD), db::legacy_db_path()).unwrap();
+        assert_eq!(
+            get_game_record(TEST_GUILD + 2, "Q", "Tekken 8")
+                .unwrap()
+                .total,
+            1000
+        );
+        assert!(!std::path::Path::new(&db::legacy_db_path()).exists());
+        assert!(get_users(TEST_GUILD + 3, true).unwrap().is_empty());
+    }
+
+    #[test]
+    fn search_finds_games_by_part_of_their_name() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_game(TEST_GUILD, "Q", "Halo", "5").unwrap();
+        add_user(TEST_GUILD, "Bob", "Tekken 7", "20").unwrap();
+
+        assert_eq!(
+            search_games(TEST_GUILD, "TEKKEN").unwrap(),
+            [
+                ("Bob".to_string(), "Tekken 7".to_string(), 2000),
+                ("Q".to_string(), "Tekken 8".to_string(), 1000)
+            ]
+        );
+        assert!(search_games(TEST_GUILD, "Mario").unwrap().is_empty());
+    }
+
+    #[test]
+    fn stats_add_up_the_server() {
+        let _store = fresh_store();
+        let empty = get_stats(TEST_GUILD).unwrap();
+        assert_eq!(
+            (empty.total_users, empty.distinct_games, empty.grand_total),
+            (0, 0, 0)
+        );
+        assert_eq!((empty.average_per_user, empty.biggest), (0, None));
+
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_game(TEST_GUILD, "Q", "Halo", "50").unwrap();
+        add_user(TEST_GUILD, "Bob", "TEKKEN 8", "30").unwrap();
+        add_user(TEST_GUILD, "Couch", "Mario Kart", "5").unwrap();
+        link_account(TEST_GUILD, "Couch", "Q").unwrap();
+
+        let stats = get_stats(TEST_GUILD).unwrap();
+        assert_eq!(stats.total_users, 2);
+        assert_eq!(stats.distinct_games, 3);
+        assert_eq!(stats.grand_total, 9500);
+        assert_eq!(stats.average_per_user, 4750);
+        assert_eq!(
+            stats.biggest,
+            Some(("Q".to_string(), "Halo".to_string(), 5000))
+        );
+    }
+
+    #[test]
+    fn blank_and_spaced_names_are_rejected() {
+        let _store = fresh_store();
+        let error = |result: Result<i64>| result.unwrap_err().to_string();
+
+        assert_eq!(
+            error(add_user(TEST_GUILD, "", "Tekken 8", "10")),
+            "User names can't be empty"
+        );
+        assert_eq!(
+            error(add_user(TEST_GUILD, "   ", "Tekken 8", "10")),
+            "User names can't be empty"
+        );
+        assert_eq!(
+            error(add_user(TEST_GUILD, "Big Q", "Tekken 8", "10")),
+            "User name 'Big Q' can't contain spaces"
+        );
+        assert_eq!(
+            error(add_user(TEST_GUILD, "Q", " ", "10")),
+            "Game names can't be empty"
+        );
+        assert!(get_users(TEST_GUILD, true).unwrap().is_empty());
+
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        assert_eq!(
+            error(add_game(TEST_GUILD, "Q", "", "10")),
+            "Game names can't be empty"
+        );
+        assert!(rename_user(TEST_GUILD, "Q", "Big Q").is_err());
+        assert!(link_account(TEST_GUILD, " ", "Q").is_err());
+    }
+
+    #[test]
+    fn overlong_names_are_rejected() {
+        let _store = fresh_store();
+        let long = "x".repeat(DEFAULT_MAX_NAME_LENGTH + 1);
+
+        assert_eq!(
+            add_user(TEST_GUILD, "Q", &long, "10")
+                .unwrap_err()
+                .to_string(),
+            "Game names can be at most 100 characters"
+        );
+        assert_eq!(
+            add_user(TEST_GUILD, &long, "Tekken 8", "10")
+                .unwrap_err()
+                .to_string(),
+            "User names can be at most 100 characters"
+        );
+        add_user(TEST_GUILD, "Q", &long[1..], "10").unwrap();
+        assert!(rename_user(TEST_GUILD, "Q", &long).is_err());
+    }
+
+    #[test]
+    fn a_user_exports_as_json_or_csv() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10.50").unwrap();
+        add_game(TEST_GUILD, "Q", "Halo, Reach", "5").unwrap();
+        add_game(TEST_GUILD, "Q", "\"Quoted\"", "0.07").unwrap();
+
+        assert_eq!(
+            export_user(TEST_GUILD, "Q", ExportFormat::Csv).unwrap(),
+            "game,total\n\"\"\"Quoted\"\"\",0.07\n\"Halo, Reach\",5.00\nTekken 8,10.50\n"
+        );
+
+        let json = export_user(TEST_GUILD, "Q", ExportFormat::Json).unwrap();
+        let exported: User = serde_json::from_str(&json).unwrap();
+        assert_eq!(exported.user, "Q");
+        assert_eq!(
+            exported.games.get("Halo, Reach").map(|record| record.total),
+            Some(500)
+        );
+        assert_eq!(
+            exported.games.get("Tekken 8").map(|record| record.total),
+            Some(1050)
+        );
+        assert!(json.find("Halo").unwrap() < json.find("Tekken").unwrap());
+
+        assert!(export_user(TEST_GUILD, "Nobody", ExportFormat::Json).is_err());
+    }
+
+    #[test]
+    fn csv_imports_set_games_and_report_bad_rows() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+
+        let csv = "game,total\ntekken 8,25.50\n\"Halo, Reach\",5\n\nMario,lots\nBroken\n\"Open,1\n";
+        let (imported, problems) = import_games(TEST_GUILD, "Q", csv, false).unwrap();
+        assert_eq!(imported, 2);
+        assert_eq!(
+            problems,
+            [
+                "Line 5: invalid total 'lots'",
+                "Line 6: expected `game,total`",
+                "Line 7: unmatched quote"
+            ]
+        );
+
+        // Existing games keep their stored name, whatever case the file used
+        let users = get_users(TEST_GUILD, true).unwrap();
+        assert_eq!(
+            users[0].games.get("Tekken 8").map(|record| record.total),
+            Some(2550)
+        );
+        assert_eq!(
+            users[0].games.get("Halo, Reach").map(|record| record.total),
+            Some(500)
+        );
+        assert_eq!(users[0].games.len(), 2);
+
+        // What !export writes comes back in unchanged
+        let exported = export_user(TEST_GUILD, "Q", ExportFormat::Csv).unwrap();
+        assert_eq!(
+            import_games(TEST_GUILD, "Q", &exported, false).unwrap(),
+            (2, vec![])
+        );
+        assert_eq!(
+            get_users(TEST_GUILD, true).unwrap()[0].games,
+            users[0].games
+        );
+
+        assert!(import_games(TEST_GUILD, "Nobody", csv, false).is_err());
+    }
+
+    #[test]
+    fn reset_wipes_users_and_history_but_keeps_settings() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        update_total(TEST_GUILD, "Q", "Tekken 8", "5", false).unwrap();
+        add_user(TEST_GUILD + 1, "Bob", "Halo", "20").unwrap();
+        set_threshold(TEST_GUILD, "150").unwrap();
+
+        reset_all(TEST_GUILD).unwrap();
+
+        assert!(get_users(TEST_GUILD, true).unwrap().is_empty());
+        assert!(db::load_all_transactions(TEST_GUILD).unwrap().is_empty());
+        assert_eq!(get_threshold(TEST_GUILD).unwrap(), 15000);
+        assert_eq!(get_users(TEST_GUILD + 1, true).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn backups_hold_exactly_the_live_data() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_game(TEST_GUILD, "Q", "Halo", "2.50").unwrap();
+        add_user(TEST_GUILD, "Bob", "Halo", "20").unwrap();
+        set_display_name(TEST_GUILD, "Bob", Some("Bobby")).unwrap();
+        add_user(TEST_GUILD + 1, "Other", "Halo", "1").unwrap();
+
+        let path = backup(TEST_GUILD).unwrap();
+        assert!(path.starts_with(&backup_dir(TEST_GUILD)));
+        assert!(path.ends_with(".json"));
+
+        let backed_up: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            backed_up,
+            serde_json::to_value(get_users(TEST_GUILD, true).unwrap()).unwrap()
+        );
+        // No temp file is left behind
+        assert!(!std::path::Path::new(&format!("{}.tmp", path)).exists());
+    }
+
+    #[test]
+    fn a_backup_restores_what_was_there() {
+        let _store = fresh_store();
+        assert!(list_backups(TEST_GUILD).unwrap().is_empty());
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_user(TEST_GUILD, "Bob", "Halo", "20").unwrap();
+        let before = serde_json::to_value(get_users(TEST_GUILD, true).unwrap()).unwrap();
+
+        let name = backup(TEST_GUILD)
+            .unwrap()
+            .rsplit('/')
+            .next()
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            list_backups(TEST_GUILD).unwrap(),
+            std::slice::from_ref(&name)
+        );
+
+        reset_all(TEST_GUILD).unwrap();
+        add_user(TEST_GUILD, "Newcomer", "Halo", "1").unwrap();
+        assert_eq!(restore(TEST_GUILD, &name).unwrap(), 2);
+        assert_eq!(
+            serde_json::to_value(get_users(TEST_GUILD, true).unwrap()).unwrap(),
+            before
+        );
+    }
+
+    #[test]
+    fn missing_or_corrupt_backups_leave_the_data_alone() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        let dir = backup_dir(TEST_GUILD);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            format!("{}/users-20250101-000000.json", dir),
+            "[{\"user\": ",
+        )
+        .unwrap();
+
+        assert!(restore(TEST_GUILD, "users-20250101-000000.json").is_err());
+        assert!(restore(TEST_GUILD, "users-20990101-000000.json").is_err());
+        assert!(restore(TEST_GUILD, "../users.json").is_err());
+        assert!(restore(TEST_GUILD, "").is_err());
+
+        let users = get_users(TEST_GUILD, true).unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(
+            users[0].games.get("Tekken 8").map(|record| record.total),
+            Some(1000)
+        );
+    }
+
+    #[test]
+    fn failures_come_back_as_their_own_variants() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+
+        let exists = add_user(TEST_GUILD, "q", "Halo", "1").unwrap_err();
+        assert!(matches!(&exists, UserError::UserAlreadyExists(user) if user == "q"));
+        assert_eq!(exists.to_string(), "User 'q' already exists");
+
+        assert!(matches!(
+            add_game(TEST_GUILD, "Nobody", "Halo", "1"),
+            Err(UserError::UserNotFound(_))
+        ));
+        assert!(matches!(
+            add_game(TEST_GUILD, "Q", "tekken 8", "1"),
+            Err(UserError::GameAlreadyExists { game, .. }) if game == "Tekken 8"
+        ));
+        assert!(matches!(
+            add_game(TEST_GUILD, "Q", "Halo", "lots"),
+            Err(UserError::InvalidAmount(_))
+        ));
+        assert!(matches!(
+            add_game(TEST_GUILD, "Q", "  ", "1"),
+            Err(UserError::InvalidName(_))
+        ));
+        assert!(matches!(
+            update_total(TEST_GUILD, "Q", "Halo", "1", false),
+            Err(UserError::GameNotFound { .. })
+        ));
+        assert!(matches!(
+            refund(TEST_GUILD, "Q", "Tekken 8", "50"),
+            Err(UserError::Rejected(_))
+        ));
+    }
+
+    #[test]
+    fn merging_folds_a_duplicate_into_the_real_user() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_user(TEST_GUILD, "Q2", "tekken 8", "5").unwrap();
+        add_game(TEST_GUILD, "Q2", "Halo", "2").unwrap();
+        set_cap(TEST_GUILD, "Q2", "Halo", Some("50")).unwrap();
+        update_total(TEST_GUILD, "Q2", "Halo", "1", false).unwrap();
+        add_user(TEST_GUILD, "Couch", "Mario", "1").unwrap();
+        link_account(TEST_GUILD, "Couch", "Q2").unwrap();
+
+        assert!(matches!(
+            merge_users(TEST_GUILD, "Q2", "Nobody", false),
+            Err(UserError::UserNotFound(_))
+        ));
+        assert!(matches!(
+            merge_users(TEST_GUILD, "Q", "q", false),
+            Err(UserError::Rejected(_))
+        ));
+
+        assert_eq!(merge_users(TEST_GUILD, "Q2", "Q", false).unwrap(), 2);
+
+        let users = get_users(TEST_GUILD, true).unwrap();
+        assert!(!users.iter().any(|user| user.user == "Q2"));
+        let q = users.iter().find(|user| user.user == "Q").unwrap();
+        assert_eq!(
+            q.games.get("Tekken 8").map(|record| record.total),
+            Some(1500)
+        );
+        assert_eq!(q.games.get("Halo").map(|record| record.total), Some(300));
+        assert_eq!(q.caps.get("Halo"), Some(&5000));
+        let couch = users.iter().find(|user| user.user == "Couch").unwrap();
+        assert_eq!(couch.alias_of.as_deref(), Some("Q"));
+        assert_eq!(get_history(TEST_GUILD, "Q", Some("Halo")).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn game_suggestions_match_the_start_of_the_name_in_any_case() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_game(TEST_GUILD, "Q", "tetris", "5").unwrap();
+        add_game(TEST_GUILD, "Q", "Street Fighter 6", "5").unwrap();
+
+        assert_eq!(
+            suggest_games(TEST_GUILD, "q", "TE").unwrap(),
+            ["Tekken 8", "tetris"]
+        );
+        assert_eq!(suggest_games(TEST_GUILD, "Q", "").unwrap().len(), 3);
+        assert!(suggest_games(TEST_GUILD, "Q", "zelda").unwrap().is_empty());
+        assert!(matches!(
+            suggest_games(TEST_GUILD, "nobody", "te"),
+            Err(UserError::UserNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn a_discord_account_links_to_one_user_only() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_user(TEST_GUILD, "Kev", "Tekken 8", "10").unwrap();
+
+        assert_eq!(username_for_discord_id(TEST_GUILD, 123).unwrap(), None);
+        assert!(link_discord_id(TEST_GUILD, "q", 123).unwrap());
+        assert_eq!(
+            username_for_discord_id(TEST_GUILD, 123).unwrap().as_deref(),
+            Some("Q")
+        );
+
+        // Neither the account nor the user can be linked a second time
+        assert!(!link_discord_id(TEST_GUILD, "Kev", 123).unwrap());
+        assert!(!link_discord_id(TEST_GUILD, "Q", 456).unwrap());
+        assert!(!link_discord_id(TEST_GUILD, "nobody", 789).unwrap());
+        assert_eq!(username_for_discord_id(TEST_GUILD, 456).unwrap(), None);
+    }
+
+    #[test]
+    fn a_preview_predicts_the_update_without_saving_it() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "190").unwrap();
+        set_cap(TEST_GUILD, "Q", "Tekken 8", Some("250")).unwrap();
+        let before = serde_json::to_value(load_users(TEST_GUILD).unwrap()).unwrap();
+
+        let (total, crossed) = preview_update(TEST_GUILD, "q", "tekken 8", "20").unwrap();
+        assert_eq!(total, 21000);
+        assert!(matches!(crossed, ThresholdCrossed::Troll));
+        assert!(matches!(
+            preview_update(TEST_GUILD, "Q", "Tekken 8", "100"),
+            Err(UserError::Rejected(_))
+        ));
+
+        assert_eq!(
+            serde_json::to_value(load_users(TEST_GUILD).unwrap()).unwrap(),
+            before
+        );
+        assert!(get_history(TEST_GUILD, "Q", None).unwrap().is_empty());
+
+        let (_, _, updated, _) = update_total(TEST_GUILD, "Q", "Tekken 8", "20", false).unwrap();
+        assert_eq!(updated, total);
+    }
+
+    #[test]
+    fn recent_spenders_are_ranked_by_what_they_added_in_the_window() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "0").unwrap();
+        add_user(TEST_GUILD, "Z", "Tekken 8", "0").unwrap();
+        link_account(TEST_GUILD, "Qalt", "Q").unwrap();
+
+        let day = 24 * 60 * 60;
+        let log = |username: &str, delta: i64, days_ago: i64| {
+            record_transaction(
+                TEST_GUILD,
+                Transaction {
+                    timestamp: now() - days_ago * day,
+                    username: username.to_string(),
+                    game: "Tekken 8".to_string(),
+                    delta,
+                    total: 0,
+                    undoes: None,
+                },
+            )
+            .unwrap();
+        };
+        log("Q", 1000, 10);
+        log("Q", 200, 1);
+        log("Qalt", 500, 2);
+        log("Z", 300, 1);
+        log("Z", -300, 0);
+        log("Gone", 5000, 0);
+
+        let week_ago = chrono::Utc::now() - chrono::Duration::weeks(1);
+        assert_eq!(
+            get_leaderboard_since(TEST_GUILD, week_ago).unwrap(),
+            [("Q".to_string(), 700)]
+        );
+        let month_ago = chrono::Utc::now() - chrono::Duration::days(30);
+        assert_eq!(
+            get_leaderboard_since(TEST_GUILD, month_ago).unwrap(),
+            [("Q".to_string(), 1700)]
+        );
+    }
+
+    #[test]
+    fn tied_users_share_a_rank_and_the_next_one_skips_ahead() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Amy", "Tekken 8", "300").unwrap();
+        add_user(TEST_GUILD, "Bob", "Tekken 8", "300").unwrap();
+        add_user(TEST_GUILD, "Cat", "Tekken 8", "100").unwrap();
+        add_user(TEST_GUILD, "Dan", "Tekken 8", "250").unwrap();
+        add_user(TEST_GUILD, "Dan2", "Tetris", "50").unwrap();
+        link_account(TEST_GUILD, "Dan2", "Dan").unwrap();
+        add_user(TEST_GUILD, "Eve", "Tekken 8", "10").unwrap();
+        remove_games_matching(TEST_GUILD, "Eve", "*").unwrap();
+
+        // Dan's linked account brings him level with the leaders
+        assert_eq!(get_rank(TEST_GUILD, "amy").unwrap(), (1, 4, 30000));
+        assert_eq!(get_rank(TEST_GUILD, "Bob").unwrap(), (1, 4, 30000));
+        assert_eq!(get_rank(TEST_GUILD, "Dan2").unwrap(), (1, 4, 30000));
+        assert_eq!(get_rank(TEST_GUILD, "Cat").unwrap(), (4, 4, 10000));
+        assert!(matches!(
+            get_rank(TEST_GUILD, "Eve"),
+            Err(UserError::NoGames(_))
+        ));
+        assert!(matches!(
+            get_rank(TEST_GUILD, "Nobody"),
+            Err(UserError::UserNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn a_goal_tracks_progress_but_never_blocks_an_update() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "150").unwrap();
+        add_user(TEST_GUILD, "Z", "Tetris", "5").unwrap();
+
+        assert_eq!(
+            set_goal(TEST_GUILD, "q", "tekken 8", Some("200")).unwrap(),
+            Some(20000)
+        );
+        assert_eq!(goal_percent(15000, 20000), 75);
+        assert_eq!(goal_percent(25000, 20000), 125);
+        assert_eq!(goal_percent(-500, 20000), 0);
+        assert_eq!(goal_percent(100, 0), 100);
+
+        update_total(TEST_GUILD, "Q", "Tekken 8", "100", false).unwrap();
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            25000
+        );
+
+        // The goal goes wherever the game goes
+        move_game(TEST_GUILD, "Q", "Z", "Tekken 8", false).unwrap();
+        assert_eq!(get_goal(TEST_GUILD, "Z", "Tekken 8").unwrap(), Some(20000));
+        assert_eq!(set_goal(TEST_GUILD, "Z", "Tekken 8", None).unwrap(), None);
+        assert_eq!(get_goal(TEST_GUILD, "Z", "Tekken 8").unwrap(), None);
+    }
+
+    #[test]
+    fn several_updates_go_in_together_or_not_at_all() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_game(TEST_GUILD, "Q", "Street Fighter 6", "5").unwrap();
+        set_cap(TEST_GUILD, "Q", "Street Fighter 6", Some("20")).unwrap();
+        let pairs = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(game, amount)| (game.to_string(), amount.to_string()))
+                .collect()
+        };
+
+        let over_cap = pairs(&[("Tekken 8", "5"), ("Street Fighter 6", "30")]);
+        assert!(matches!(
+            update_totals(TEST_GUILD, "Q", &over_cap),
+            Err(UserError::Rejected(_))
+        ));
+        let bad_amount = pairs(&[("Tekken 8", "5"), ("Street Fighter 6", "lots")]);
+        assert!(matches!(
+            update_totals(TEST_GUILD, "Q", &bad_amount),
+            Err(UserError::InvalidAmount(_))
+        ));
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            1000
+        );
+        assert!(load_history(TEST_GUILD).unwrap().is_empty());
+
+        let spree = pairs(&[
+            ("tekken 8", "5"),
+            ("Street Fighter 6", "5"),
+            ("Tekken 8", "1"),
+        ]);
+        let results = update_totals(TEST_GUILD, "q", &spree).unwrap();
+        assert_eq!(
+            results
+                .iter()
+                .map(|(game, added, total, _)| (game.as_str(), *added, *total))
+                .collect::<Vec<_>>(),
+            [
+                ("Tekken 8", 500, 1500),
+                ("Street Fighter 6", 500, 1000),
+                ("Tekken 8", 100, 1600)
+            ]
+        );
+        assert_eq!(load_history(TEST_GUILD).unwrap().len(), 3);
+        assert_eq!(
+            get_leaderboard(TEST_GUILD, 1).unwrap(),
+            [("Q".to_string(), 2600)]
+        );
+    }
+
+    #[test]
+    fn archived_users_keep_their_games_but_drop_out_of_lists_and_rankings() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "300").unwrap();
+        add_user(TEST_GUILD, "Bob", "Tekken 8", "500").unwrap();
+        add_user(TEST_GUILD, "Alice", "Tekken 8", "100").unwrap();
+        archive_user(TEST_GUILD, "Bob").unwrap();
+        assert!(archive_user(TEST_GUILD, "Bob").is_err());
+
+        let listed: Vec<String> = get_users(TEST_GUILD, false)
+            .unwrap()
+            .into_iter()
+            .map(|user| user.user)
+            .collect();
+        assert!(!listed.contains(&"Bob".to_string()));
+        let bob = get_users(TEST_GUILD, true)
+            .unwrap()
+            .into_iter()
+            .find(|user| user.user == "Bob")
+            .unwrap();
+        assert_eq!(
+            bob.games.get("Tekken 8").map(|record| record.total),
+            Some(50000)
+        );
+
+        assert_eq!(
+            get_leaderboard(TEST_GUILD, 10).unwrap(),
+            vec![("Q".to_string(), 30000), ("Alice".to_string(), 10000)]
+        );
+        assert_eq!(get_cheapskates(TEST_GUILD, 10).unwrap().len(), 2);
+        let stats = get_stats(TEST_GUILD).unwrap();
+        assert_eq!((stats.total_users, stats.grand_total), (2, 40000));
+        assert_eq!(get_rank(TEST_GUILD, "Q").unwrap(), (1, 2, 30000));
+        assert!(get_rank(TEST_GUILD, "Bob").is_err());
+
+        unarchive_user(TEST_GUILD, "Bob").unwrap();
+        assert_eq!(get_rank(TEST_GUILD, "Bob").unwrap(), (1, 3, 50000));
+        assert_eq!(get_users(TEST_GUILD, false).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn a_comparison_lines_up_shared_games_and_names_the_bigger_spender() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "300").unwrap();
+        add_game(TEST_GUILD, "Q", "Street Fighter 6", "50").unwrap();
+        add_user(TEST_GUILD, "Alice", "tekken 8", "100").unwrap();
+        add_user(TEST_GUILD, "Bob", "Genshin Impact", "300").unwrap();
+        add_game(TEST_GUILD, "Bob", "Halo", "50").unwrap();
+
+        let comparison = compare(TEST_GUILD, "Q", "Alice").unwrap();
+        assert_eq!(
+            comparison.shared,
+            vec![("Tekken 8".to_string(), 30000, 10000)]
+        );
+        assert_eq!(
+            (comparison.first_total, comparison.second_total),
+            (35000, 10000)
+        );
+        assert_eq!(comparison.leader.as_deref(), Some("Q"));
+
+        let comparison = compare(TEST_GUILD, "Q", "Bob").unwrap();
+        assert!(comparison.shared.is_empty());
+        assert_eq!(comparison.leader, None);
+
+        assert!(matches!(
+            compare(TEST_GUILD, "Q", "q"),
+            Err(UserError::Rejected(_))
+        ));
+        assert!(matches!(
+            compare(TEST_GUILD, "Q", "Nobody"),
+            Err(UserError::UserNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn tagged_games_add_up_by_category_and_follow_the_game() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Genshin Impact", "300").unwrap();
+        add_game(TEST_GUILD, "Q", "Tekken 8", "50").unwrap();
+        add_user(TEST_GUILD, "Alice", "Honkai Star Rail", "100").unwrap();
+        add_user(TEST_GUILD, "Bob", "Fate Grand Order", "900").unwrap();
+
+        tag_game(TEST_GUILD, "Q", "genshin impact", "Gacha").unwrap();
+        tag_game(TEST_GUILD, "Alice", "Honkai Star Rail", "gacha").unwrap();
+        tag_game(TEST_GUILD, "Bob", "Fate Grand Order", "gacha").unwrap();
+        assert!(tag_game(TEST_GUILD, "Q", "Genshin Impact", "GACHA").is_err());
+        assert!(tag_game(TEST_GUILD, "Q", "Genshin Impact", "two words").is_err());
+        archive_user(TEST_GUILD, "Bob").unwrap();
+
+        assert_eq!(get_category_total(TEST_GUILD, "gacha").unwrap(), 40000);
+        assert!(get_category_total(TEST_GUILD, "fighting").is_err());
+
+        // Tags are saved with the game and move with it
+        reload_from_disk(TEST_GUILD).unwrap();
+        move_game(TEST_GUILD, "Q", "Alice", "Genshin Impact", false).unwrap();
+        assert_eq!(
+            get_user_tags(TEST_GUILD, "Alice")
+                .unwrap()
+                .get("Genshin Impact"),
+            Some(&vec!["gacha".to_string()])
+        );
+        assert!(get_user_tags(TEST_GUILD, "Q").unwrap().is_empty());
+
+        untag_game(TEST_GUILD, "Alice", "Genshin Impact", "gacha").unwrap();
+        assert!(untag_game(TEST_GUILD, "Alice", "Genshin Impact", "gacha").is_err());
+        assert_eq!(get_category_total(TEST_GUILD, "gacha").unwrap(), 10000);
+    }
+
+    #[test]
+    fn games_read_back_from_either_json_shape() {
+        let old: User =
+            serde_json::from_str(r#"{"user": "Q", "games": {"Tekken 8": 1000}}"#).unwrap();
+        assert_eq!(
+            old.games["Tekken 8"],
+            GameRecord {
+                total: 1000,
+                ..GameRecord::default()
+            }
+        );
+
+        let new: User = serde_json::from_str(
+            r#"{"user": "Q", "games": {"Tekken 8": {"total": 1000, "created_at": 5, "last_updated": 7}}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            new.games["Tekken 8"],
+            GameRecord {
+                total: 1000,
+                created_at: 5,
+                last_updated: 7,
+            }
+        );
+
+        // And the timestamps survive a trip through the database
+        let _store = fresh_store();
+        save_users(TEST_GUILD, &[new]).unwrap();
+        assert_eq!(
+            db::load_users(TEST_GUILD).unwrap()[0].games["Tekken 8"].last_updated,
+            7
+        );
+    }
+
+    #[test]
+    fn updates_move_a_games_last_updated_but_not_when_it_was_added() {
+        let _store = fresh_store();
+        let before = now();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        let record = get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap();
+        assert!(record.created_at >= before);
+        assert_eq!(record.created_at, record.last_updated);
+
+        // Pretend it was added a long time ago
+        let mut users = get_users(TEST_GUILD, true).unwrap();
+        let record = users[0].games.get_mut("Tekken 8").unwrap();
+        record.created_at = 100;
+        record.last_updated = 100;
+        save_users(TEST_GUILD, &users).unwrap();
+
+        update_total(TEST_GUILD, "Q", "Tekken 8", "5", false).unwrap();
+        let record = get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap();
+        assert_eq!(record.created_at, 100);
+        assert!(record.last_updated >= before);
+    }
+
+    #[test]
+    fn games_left_alone_past_the_cutoff_are_inactive_oldest_first() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_game(TEST_GUILD, "Q", "Halo", "10").unwrap();
+        add_user(TEST_GUILD, "Alice", "Tekken 8", "10").unwrap();
+        add_user(TEST_GUILD, "Bob", "Tekken 8", "10").unwrap();
+
+        let day = 24 * 60 * 60;
+        let mut users = get_users(TEST_GUILD, true).unwrap();
+        for user in &mut users {
+            // Alice's games are from before updates were recorded
+            let last_updated = if user.user == "Alice" {
+                0
+            } else {
+                now() - 40 * day
+            };
+            for record in user.games.values_mut() {
+                record.last_updated = last_updated;
+            }
+        }
+        save_users(TEST_GUILD, &users).unwrap();
+        update_total(TEST_GUILD, "Q", "Halo", "5", false).unwrap();
+        archive_user(TEST_GUILD, "Bob").unwrap();
+
+        let inactive: Vec<(String, String)> = inactive_since(TEST_GUILD, 30)
+            .unwrap()
+            .into_iter()
+            .map(|(username, game, _)| (username, game))
+            .collect();
+        assert_eq!(
+            inactive,
+            vec![
+                ("Alice".to_string(), "Tekken 8".to_string()),
+                ("Q".to_string(), "Tekken 8".to_string()),
+            ]
+        );
+        assert_eq!(inactive_since(TEST_GUILD, 50).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn a_missing_game_suggests_the_users_closest_one() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_game(TEST_GUILD, "Q", "Street Fighter 6", "10").unwrap();
+
+        assert_eq!(
+            update_total(TEST_GUILD, "Q", "tekken8", "5", false)
+                .unwrap_err()
+                .to_string(),
+            "User 'Q' doesn't have game 'tekken8'. Did you mean 'Tekken 8'?"
+        );
+        assert_eq!(
+            remove_game(TEST_GUILD, "Q", "Zelda")
+                .unwrap_err()
+                .to_string(),
+            "User 'Q' doesn't have game 'Zelda'"
+        );
+    }
+
+    #[test]
+    fn the_confirmation_limit_comes_from_a_positive_amount() {
+        assert_eq!(confirm_over(Some("200")), Some(20000));
+        assert_eq!(confirm_over(Some(" 12.50 ")), Some(1250));
+        assert_eq!(confirm_over(Some("0")), None);
+        assert_eq!(confirm_over(Some("lots")), None);
+        assert_eq!(confirm_over(None), None);
+    }
+
+    #[test]
+    fn a_flush_writes_whats_cached_to_each_servers_database() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+
+        // Change the cached copy alone, as if a save hadn't reached the database
+        let mut users = get_users(TEST_GUILD, true).unwrap();
+        users[0].games.get_mut("Tekken 8").unwrap().total = 4200;
+        lock_user_cache().insert(TEST_GUILD, users);
+        assert_eq!(
+            db::load_users(TEST_GUILD).unwrap()[0].games["Tekken 8"].total,
+            1000
+        );
+
+        assert_eq!(flush().unwrap(), 1);
+        assert_eq!(
+            db::load_users(TEST_GUILD).unwrap()[0].games["Tekken 8"].total,
+            4200
+        );
+    }
+
+    #[test]
+    fn zero_game_users_are_still_tracked() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_game(TEST_GUILD, "Q", "Street Fighter 6", "10").unwrap();
+        remove_games_matching(TEST_GUILD, "Q", "*").unwrap();
+
+        assert_eq!(get_user_total_all_games(TEST_GUILD, "Q").unwrap(), 0);
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8")
+                .unwrap_err()
+                .to_string(),
+            "User 'Q' has no games"
+        );
+        assert!(get_leaderboard(TEST_GUILD, 10).unwrap().is_empty());
+
+        // remove_game still takes a user along with their last game
+        add_user(TEST_GUILD, "Z", "Tekken 8", "10").unwrap();
+        remove_game(TEST_GUILD, "Z", "Tekken 8").unwrap();
+        assert!(get_user_games(TEST_GUILD, "Z").is_err());
+    }
+
+    #[test]
+    fn debug_dump_shows_the_raw_record() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        link_account(TEST_GUILD, "Qalt", "Q").unwrap();
+
+        // The timestamps depend on when the test runs
+        let added = get_game_record(TEST_GUILD, "Q", "Tekken 8")
+            .unwrap()
+            .created_at
+            .to_string();
+        assert_eq!(
+            debug_dump(TEST_GUILD, "Q").unwrap().replace(&added, "0"),
+            "```json\n{\n  \"user\": \"Q\",\n  \"games\": {\n    \"Tekken 8\": {\n      \"total\": 1000,\n      \"created_at\": 0,\n      \"last_updated\": 0\n    }\n  },\n  \"alias_of\": null,\n  \"poke_opt_out\": false,\n  \"caps\": {},\n  \"goals\": {},\n  \"tags\": {},\n  \"display_name\": null,\n  \"consented\": false,\n  \"in_cents\": true,\n  \"discord_id\": null,\n  \"archived\": false\n}\n```"
+        );
+        assert!(
+            debug_dump(TEST_GUILD, "Qalt")
+                .unwrap()
+                .contains("\"alias_of\": \"Q\"")
+        );
+        assert!(debug_dump(TEST_GUILD, "Nobody").is_err());
+    }
+
+    #[test]
+    fn debug_dump_is_cut_short_to_fit_a_message() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Game number 0", "10").unwrap();
+        for i in 1..100 {
+            add_game(TEST_GUILD, "Q", &format!("Game number {}", i), "10").unwrap();
+        }
+
+        let dump = debug_dump(TEST_GUILD, "Q").unwrap();
+        assert!(dump.ends_with("\n... (truncated)\n```"));
+        let wrapping = "```json\n".len() + "\n... (truncated)\n```".len();
+        assert_eq!(dump.len(), DEBUG_DUMP_LIMIT + wrapping);
+    }
+
+    #[test]
+    fn cheapskates_start_from_the_smallest_spender() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Zed", "Tekken 8", "10").unwrap();
+        add_user(TEST_GUILD, "Amy", "Tekken 8", "10").unwrap();
+        add_user(TEST_GUILD, "Bob", "Tekken 8", "500").unwrap();
+        link_account(TEST_GUILD, "Alt", "Bob").unwrap();
+
+        // Ties still break alphabetically, and the game-less link isn't listed
+        assert_eq!(
+            get_cheapskates(TEST_GUILD, 10).unwrap(),
+            [
+                ("Amy".to_string(), 1000),
+                ("Zed".to_string(), 1000),
+                ("Bob".to_string(), 50000)
+            ]
+        );
+        assert_eq!(get_cheapskates(TEST_GUILD, 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn update_total_reports_the_super_threshold_once() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "450").unwrap();
+
+        assert_eq!(
+            update_total(TEST_GUILD, "Q", "Tekken 8", "60", false).unwrap(),
+            (45000, 6000, 51000, ThresholdCrossed::SuperTroll)
+        );
+        assert_eq!(
+            update_total(TEST_GUILD, "Q", "Tekken 8", "10", false).unwrap(),
+            (51000, 1000, 52000, ThresholdCrossed::None)
+        );
+    }
+
+    #[test]
+    fn one_update_past_both_thresholds_only_reports_the_bigger_one() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "150").unwrap();
+        add_game(TEST_GUILD, "Q", "Halo", "150").unwrap();
+
+        assert_eq!(
+            update_total(TEST_GUILD, "Q", "Tekken 8", "50", false)
+                .unwrap()
+                .3,
+            ThresholdCrossed::Troll
+        );
+        assert_eq!(
+            update_total(TEST_GUILD, "Q", "Halo", "400", false)
+                .unwrap()
+                .3,
+            ThresholdCrossed::SuperTroll
+        );
+    }
+
+    #[test]
+    fn each_server_can_move_its_first_threshold() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "90").unwrap();
+        add_user(TEST_GUILD + 1, "Q", "Tekken 8", "90").unwrap();
+
+        assert_eq!(get_threshold(TEST_GUILD).unwrap(), TROLL_THRESHOLD);
+        assert!(set_threshold(TEST_GUILD, "0").is_err());
+        assert!(set_threshold(TEST_GUILD, "500").is_err());
+        assert!(set_threshold(TEST_GUILD, "lots").is_err());
+        assert_eq!(set_threshold(TEST_GUILD, "100").unwrap(), 10000);
+        assert_eq!(get_threshold(TEST_GUILD).unwrap(), 10000);
+        assert_eq!(get_threshold(TEST_GUILD + 1).unwrap(), TROLL_THRESHOLD);
+
+        assert_eq!(
+            near_threshold(TEST_GUILD, 1500).unwrap(),
+            [("Q".to_string(), "Tekken 8".to_string(), 9000, 10000)]
+        );
+        assert_eq!(
+            update_total(TEST_GUILD, "Q", "Tekken 8", "10", false)
+                .unwrap()
+                .3,
+            ThresholdCrossed::Troll
+        );
+        assert_eq!(
+            update_total(TEST_GUILD + 1, "Q", "Tekken 8", "10", false)
+                .unwrap()
+                .3,
+            ThresholdCrossed::None
+        );
+
+        assert_eq!(set_threshold(TEST_GUILD, "none").unwrap(), TROLL_THRESHOLD);
+        assert_eq!(get_threshold(TEST_GUILD).unwrap(), TROLL_THRESHOLD);
+    }
+
+    #[test]
+    fn merging_files_sums_shared_games() {
+        let (a, b, out) = (
+            scratch_file("merge_a.json"),
+            scratch_file("merge_b.json"),
+            scratch_file("merge_out.json"),
+        );
+        std::fs::write(
+            &a,
+            r#"[{"user": "Q", "games": {"Tekken 8": 100}}, {"user": "A", "games": {"Tekken 8": 5}}]"#,
+        )
+        .unwrap();
+        std::fs::write(
+            &b,
+            r#"[{"user": "Q", "games": {"Tekken 8": 50, "Street Fighter 6": 20}}, {"user": "B", "games": {}}]"#,
+        )
+        .unwrap();
+
+        let report = merge_data_files(&a, &b, &out, false).unwrap();
+        assert_eq!(
+            (
+                report.users_merged,
+                report.users_copied,
+                report.games_conflicting
+            ),
+            (1, 2, 1)
+        );
+
+        let merged = read_users_file(&out).unwrap();
+        let q = merged.iter().find(|user| user.user == "Q").unwrap();
+        assert_eq!(q.games["Tekken 8"].total, 15000);
+        assert_eq!(q.games["Street Fighter 6"].total, 2000);
+        assert_eq!(merged.len(), 3);
+
+        // A file that isn't there is an error rather than no users
+        assert!(merge_data_files(&a, &scratch_file("missing.json"), &out, false).is_err());
+    }
+
+    #[test]
+    fn merging_files_keeps_each_users_settings_with_the_first_file_winning() {
+        let (a, b, out) = (
+            scratch_file("merge_settings_a.json"),
+            scratch_file("merge_settings_b.json"),
+            scratch_file("merge_settings_out.json"),
+        );
+        std::fs::write(
+            &a,
+            r#"[{"user": "Q", "in_cents": true, "games": {"Tekken 8": 10000, "Halo": 500},
+                "caps": {"Tekken 8": 40000}, "goals": {"Tekken 8": 30000},
+                "tags": {"Tekken 8": ["fighting"]}, "display_name": "Quinn",
+                "archived": true}]"#,
+        )
+        .unwrap();
+        std::fs::write(
+            &b,
+            r#"[{"user": "Q", "in_cents": true, "games": {"Tekken 8": 5000, "Halo": 500},
+                "caps": {"Tekken 8": 90000, "Halo": 2000}, "goals": {"Halo": 1000},
+                "tags": {"Tekken 8": ["gacha"], "Halo": ["shooter"]},
+                "display_name": "Q-dog", "discord_id": 42, "archived": false}]"#,
+        )
+        .unwrap();
+
+        merge_data_files(&a, &b, &out, false).unwrap();
+        let merged = read_users_file(&out).unwrap();
+        let q = &merged[0];
+
+        assert_eq!(q.caps["Tekken 8"], 40000);
+        assert_eq!(q.caps["Halo"], 2000);
+        assert_eq!(q.goals["Tekken 8"], 30000);
+        assert_eq!(q.goals["Halo"], 1000);
+        assert_eq!(q.tags["Tekken 8"], vec!["fighting"]);
+        assert_eq!(q.tags["Halo"], vec!["shooter"]);
+        assert_eq!(q.display_name.as_deref(), Some("Quinn"));
+        assert_eq!(q.discord_id, Some(42));
+        assert!(q.archived);
+    }
+
+    #[test]
+    fn poke_skips_opted_out_users_and_small_spenders() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "900").unwrap();
+        add_user(TEST_GUILD, "Z", "Tekken 8", "300").unwrap();
+        add_user(TEST_GUILD, "A", "Tekken 8", "50").unwrap();
+        assert_eq!(
+            pick_poke_target(TEST_GUILD, 20000).unwrap(),
+            Some(("Q".to_string(), 90000))
+        );
+
+        set_poke_opt_out(TEST_GUILD, "Q", true).unwrap();
+        assert_eq!(
+            pick_poke_target(TEST_GUILD, 20000).unwrap(),
+            Some(("Z".to_string(), 30000))
+        );
+        assert_eq!(pick_poke_target(TEST_GUILD, 50000).unwrap(), None);
+
+        set_poke_opt_out(TEST_GUILD, "Q", false).unwrap();
+        assert_eq!(
+            pick_poke_target(TEST_GUILD, 50000).unwrap(),
+            Some(("Q".to_string(), 90000))
+        );
+    }
+
+    // Stands in for data that can't be serialized
+    struct Unserializable;
+
+    impl Serialize for Unserializable {
+        fn serialize<S: serde::Serializer>(&self, _: S) -> std::result::Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("injected failure"))
+        }
+    }
+
+    #[test]
+    fn a_failed_save_leaves_the_file_alone_and_no_temp_file() {
+        let path = scratch_file("atomic.json");
+        let temp_path = format!("{}.tmp", path);
+        std::fs::write(&path, "original").unwrap();
+
+        let error = write_json_atomically(&path, &Unserializable).unwrap_err();
+        assert!(error.to_string().contains("injected failure"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "original");
+        assert!(!std::path::Path::new(&temp_path).exists());
+
+        // A rename that fails (here, onto a directory) cleans up the temp file too
+        let dir = scratch_file("atomic_dir");
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(write_json_atomically(&dir, &[] as &[User]).is_err());
+        assert!(!std::path::Path::new(&format!("{}.tmp", dir)).exists());
+
+        write_json_atomically(&path, &[] as &[User]).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "[]");
+    }
+
+    #[test]
+    fn updates_are_logged_and_add_up_since_a_time() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "100").unwrap();
+        add_user(TEST_GUILD, "Z", "Tekken 8", "10").unwrap();
+        link_account(TEST_GUILD, "Qalt", "Q").unwrap();
+        let start = now();
+
+        update_total(TEST_GUILD, "Qalt", "Tekken 8", "20", false).unwrap();
+        update_total(TEST_GUILD, "Z", "Tekken 8", "5", false).unwrap();
+        update_total(TEST_GUILD, "Q", "Tekken 8", "-5", false).unwrap();
+
+        let history = load_history(TEST_GUILD).unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(
+            (
+                history[0].username.as_str(),
+                history[0].delta,
+                history[0].total
+            ),
+            ("Q", 2000, 12000)
+        );
+        assert_eq!(
+            spend_since(TEST_GUILD, start).unwrap(),
+            [("Q".to_string(), 1500), ("Z".to_string(), 500)]
+        );
+        assert!(spend_since(TEST_GUILD, start + 60).unwrap().is_empty());
+    }
+
+    #[test]
+    fn a_game_moves_to_the_right_person_with_its_total_and_cap() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "50").unwrap();
+        set_cap(TEST_GUILD, "Q", "Tekken 8", Some("100")).unwrap();
+        add_user(TEST_GUILD, "Z", "Halo", "10").unwrap();
+        add_user(TEST_GUILD, "Bob", "HALO", "1").unwrap();
+
+        // Bob already has Halo, whatever the capitalization
+        assert!(matches!(
+            move_game(TEST_GUILD, "Z", "Bob", "halo", false),
+            Err(UserError::GameAlreadyExists { game, .. }) if game == "HALO"
+        ));
+        assert!(matches!(
+            move_game(TEST_GUILD, "Q", "Nobody", "Tekken 8", false),
+            Err(UserError::UserNotFound(_))
+        ));
+        assert!(matches!(
+            move_game(TEST_GUILD, "Q", "Z", "Mario", false),
+            Err(UserError::GameNotFound { .. })
+        ));
+
+        // Q's only game goes, so Q goes too
+        assert!(move_game(TEST_GUILD, "Q", "Z", "tekken 8", false).unwrap());
+        let users = get_users(TEST_GUILD, true).unwrap();
+        assert!(!users.iter().any(|user| user.user == "Q"));
+        let z = users.iter().find(|user| user.user == "Z").unwrap();
+        assert_eq!(
+            z.games.get("Tekken 8").map(|record| record.total),
+            Some(5000)
+        );
+        assert_eq!(z.caps.get("Tekken 8"), Some(&10000));
+    }
+
+    #[test]
+    fn moving_a_game_can_take_its_history_along() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "50").unwrap();
+        add_game(TEST_GUILD, "Q", "Halo", "5").unwrap();
+        add_user(TEST_GUILD, "Z", "Street Fighter 6", "10").unwrap();
+        update_total(TEST_GUILD, "Q", "Tekken 8", "5", false).unwrap();
+        update_total(TEST_GUILD, "Q", "Halo", "1", false).unwrap();
+        let owners = |game: &str| -> Vec<String> {
+            load_history(TEST_GUILD)
+                .unwrap()
+                .into_iter()
+                .filter(|change| change.game == game)
+                .map(|change| change.username)
+                .collect()
+        };
+
+        // By default the history stays with whoever made the changes
+        assert!(!move_game(TEST_GUILD, "Q", "Z", "Tekken 8", false).unwrap());
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Z", "Tekken 8").unwrap().total,
+            5500
+        );
+        assert_eq!(owners("Tekken 8"), ["Q"]);
+
+        move_game(TEST_GUILD, "Z", "Q", "Tekken 8", false).unwrap();
+        move_game(TEST_GUILD, "Q", "Z", "Tekken 8", true).unwrap();
+        assert_eq!(owners("Tekken 8"), ["Z"]);
+        // Other games' history doesn't go with it
+        assert_eq!(owners("Halo"), ["Q"]);
+    }
+
+    #[test]
+    fn history_lists_a_users_changes_newest_first() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_game(TEST_GUILD, "Q", "Halo", "0").unwrap();
+        add_user(TEST_GUILD, "Bob", "Halo", "0").unwrap();
+        update_total(TEST_GUILD, "Q", "Tekken 8", "5", false).unwrap();
+        update_total(TEST_GUILD, "Q", "Halo", "2.50", false).unwrap();
+        update_total(TEST_GUILD, "Bob", "Halo", "9", false).unwrap();
+        update_total(TEST_GUILD, "Q", "Tekken 8", "-1", false).unwrap();
+
+        let history = get_history(TEST_GUILD, "Q", None).unwrap();
+        let changes: Vec<(&str, i64, i64)> = history
+            .iter()
+            .map(|change| (change.game.as_str(), change.delta, change.total))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("Tekken 8", -100, 1400),
+                ("Halo", 250, 250),
+                ("Tekken 8", 500, 1500)
+            ]
+        );
+        assert_eq!(get_history(TEST_GUILD, "Q", Some("Halo")).unwrap().len(), 1);
+        assert!(get_history(TEST_GUILD, "Nobody", None).is_err());
+
+        let change = Transaction {
+            timestamp: 1_755_205_620,
+            ..history[0].clone()
+        };
+        assert_eq!(change.when(), "2025-08-14 21:07 UTC");
+    }
+
+    #[test]
+    fn undo_only_reverts_that_users_changes() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_user(TEST_GUILD, "Bob", "Halo", "10").unwrap();
+        update_total(TEST_GUILD, "Q", "Tekken 8", "5", false).unwrap();
+        update_total(TEST_GUILD, "Q", "Tekken 8", "7", false).unwrap();
+        update_total(TEST_GUILD, "Bob", "Halo", "20", false).unwrap();
+
+        // Bob changed something last, but undoing for Q leaves it alone
+        assert_eq!(
+            undo_last_for(TEST_GUILD, "Q").unwrap(),
+            ("Tekken 8".to_string(), 1500)
+        );
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            1500
+        );
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Bob", "Halo").unwrap().total,
+            3000
+        );
+
+        // The undo is logged, and undoing again walks back to the change before
+        // rather than reverting the same one twice
+        let history = get_history(TEST_GUILD, "Q", None).unwrap();
+        assert_eq!(
+            (history[0].delta, history[0].undoes.is_some()),
+            (-700, true)
+        );
+        assert_eq!(
+            undo_last_for(TEST_GUILD, "Q").unwrap(),
+            ("Tekken 8".to_string(), 1000)
+        );
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            1000
+        );
+        assert!(undo_last_for(TEST_GUILD, "Q").is_err());
+        assert_eq!(
+            get_cached_stats(TEST_GUILD).unwrap(),
+            StatsCache::from_users(&load_users(TEST_GUILD).unwrap())
+        );
+    }
+
+    #[test]
+    fn undo_with_no_history_says_so() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+
+        let error = undo_last_for(TEST_GUILD, "Q").unwrap_err().to_string();
+        assert_eq!(error, "User 'Q' has no changes to undo");
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            1000
+        );
+        assert!(undo_last_for(TEST_GUILD, "Nobody").is_err());
+    }
+
+    #[test]
+    fn update_total_refuses_to_go_below_zero() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+
+        let error = update_total(TEST_GUILD, "Q", "Tekken 8", "-20", false)
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            error,
+            "Cannot reduce 'Tekken 8' below $0.00 (current $10.00, requested -$20.00)"
+        );
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            1000
+        );
+        assert!(get_history(TEST_GUILD, "Q", None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn refunds_come_off_the_total_and_can_be_undone() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+
+        let (_, refunded, new_total, _) = refund(TEST_GUILD, "Q", "Tekken 8", "2.50").unwrap();
+        assert_eq!((refunded, new_total), (-250, 750));
+        assert_eq!(get_history(TEST_GUILD, "Q", None).unwrap()[0].delta, -250);
+
+        assert!(refund(TEST_GUILD, "Q", "Tekken 8", "0").is_err());
+        assert!(refund(TEST_GUILD, "Q", "Tekken 8", "-5").is_err());
+        assert!(refund(TEST_GUILD, "Q", "Tekken 8", "20").is_err());
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            750
+        );
+
+        assert_eq!(
+            undo_last_for(TEST_GUILD, "Q").unwrap(),
+            ("Tekken 8".to_string(), 1000)
+        );
+    }
+
+    #[test]
+    fn near_threshold_keeps_totals_just_inside_the_margin() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "150").unwrap();
+        add_game(TEST_GUILD, "Q", "Halo", "149.99").unwrap();
+        add_user(TEST_GUILD, "Z", "Street Fighter 6", "199").unwrap();
+        add_game(TEST_GUILD, "Z", "Genshin Impact", "460").unwrap();
+        add_game(TEST_GUILD, "Z", "Apex Legends", "600").unwrap();
+
+        // 150 is exactly $50 short of 200 so it counts; 149.99 is a cent too far
+        assert_eq!(
+            near_threshold(TEST_GUILD, 5000).unwrap(),
+            [
+                (
+                    "Z".to_string(),
+                    "Street Fighter 6".to_string(),
+                    19900,
+                    20000
+                ),
+                ("Z".to_string(), "Genshin Impact".to_string(), 46000, 50000),
+                ("Q".to_string(), "Tekken 8".to_string(), 15000, 20000),
+            ]
+        );
+        assert!(near_threshold(TEST_GUILD, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reload_picks_up_changes_made_outside_the_bot() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        assert_eq!(
+            get_cached_stats(TEST_GUILD).unwrap().ranking(),
+            [("Q".to_string(), 1000)]
+        );
+
+        let mut users = load_users(TEST_GUILD).unwrap();
+        users[0]
+            .games
+            .insert("Tekken 8".to_string(), GameRecord::new(4200));
+        users.push(users[0].clone());
+        users[1].user = "Bob".to_string();
+        db::save_users(TEST_GUILD, &users).unwrap();
+
+        // Until the reload the cached stats don't know about it
+        assert_eq!(get_cached_stats(TEST_GUILD).unwrap().grand_total, 1000);
+        assert_eq!(reload_from_disk(TEST_GUILD).unwrap(), 2);
+        assert_eq!(
+            get_cached_stats(TEST_GUILD).unwrap().ranking(),
+            [("Bob".to_string(), 4200), ("Q".to_string(), 4200)]
+        );
+
+        // A broken database is reported instead of being read as nobody
+        std::fs::write(db::db_path(TEST_GUILD), "[{").unwrap();
+        assert!(reload_from_disk(TEST_GUILD).is_err());
+    }
+
+    #[test]
+    fn concurrent_updates_are_all_applied() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "0").unwrap();
+        add_user(TEST_GUILD, "Z", "Tekken 8", "0").unwrap();
+
+        let workers: Vec<_> = ["Q", "Z", "Q", "Z"]
+            .into_iter()
+            .map(|username| {
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        update_total(TEST_GUILD, username, "Tekken 8", "1", false).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            2000
+        );
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Z", "Tekken 8").unwrap().total,
+            2000
+        );
+        assert_eq!(load_history(TEST_GUILD).unwrap().len(), 40);
+    }
+
+    #[test]
+    fn cached_reads_leave_the_database_alone() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        let path = db::db_path(TEST_GUILD);
+        std::fs::remove_file(&path).unwrap();
+
+        // Opening the database would have created the file again
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            1000
+        );
+        assert_eq!(get_users(TEST_GUILD, true).unwrap().len(), 1);
+        assert_eq!(get_rank(TEST_GUILD, "Q").unwrap(), (1, 1, 1000));
+        assert!(!std::path::Path::new(&path).exists());
+
+        // Saves still go straight through to the database
+        update_total(TEST_GUILD, "Q", "Tekken 8", "5", false).unwrap();
+        assert_eq!(
+            db::load_users(TEST_GUILD).unwrap()[0].games["Tekken 8"].total,
+            1500
+        );
+    }
+
+    #[test]
+    fn a_save_cut_short_leaves_the_previous_data() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+
+        // A second Q breaks the unique name partway through the save, which rolls it back
+        let mut users = get_users(TEST_GUILD, true).unwrap();
+        users[0]
+            .games
+            .insert("Tekken 8".to_string(), GameRecord::new(5000));
+        users.push(users[0].clone());
+        assert!(save_users(TEST_GUILD, &users).is_err());
+
+        // Neither the cached users nor the database took any of it
+        assert_eq!(get_users(TEST_GUILD, true).unwrap().len(), 1);
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            1000
+        );
+        assert_eq!(reload_from_disk(TEST_GUILD).unwrap(), 1);
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            1000
+        );
+    }
+
+    #[test]
+    fn a_corrupt_users_file_is_backed_up_and_not_imported() {
+        let _store = fresh_store();
+        let truncated = r#"[{"user": "Q", "games": {"Tekken 8""#;
+        std::fs::write(users_file(), truncated).unwrap();
+
+        assert!(get_users(TEST_GUILD, true).is_err());
+        assert!(get_leaderboard(TEST_GUILD, 10).is_err());
+        let error = add_user(TEST_GUILD, "Bob", "Tekken 8", "10")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("backed up to"));
+
+        // Nothing was saved over the broken file, a copy was kept, and nothing was imported
+        assert_eq!(std::fs::read_to_string(users_file()).unwrap(), truncated);
+        assert_eq!(
+            std::fs::read_to_string(format!("{}.bak", users_file())).unwrap(),
+            truncated
+        );
+        assert!(!db::exists(TEST_GUILD));
+
+        // Once the file is fixed it's imported
+        std::fs::write(
+            users_file(),
+            r#"[{"user": "Q", "games": {"Tekken 8": 10}}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            1000
+        );
+    }
+
+    #[test]
+    fn a_missing_users_file_imports_as_no_users() {
+        let _store = fresh_store();
+        assert!(get_users(TEST_GUILD, true).unwrap().is_empty());
+        assert!(db::exists(TEST_GUILD));
+
+        // The old file is only read when the database is created
+        std::fs::write(users_file(), "[{").unwrap();
+        assert!(get_users(TEST_GUILD, true).unwrap().is_empty());
+        assert_eq!(
+            history_file(),
+            users_file().replace("users.json", "history.json")
+        );
+    }
+
+    #[test]
+    fn rank_changes_cover_movers_entrants_and_departures() {
+        let board = |names: &[&str]| -> Vec<(String, i64)> {
+            names.iter().map(|name| (name.to_string(), 0)).collect()
+        };
+        let change = |username: &str, before, after| RankChange {
+            username: username.to_string(),
+            before,
+            after,
+        };
+
+        assert_eq!(
+            rank_changes(&board(&["Q", "Bob", "Amy"]), &board(&["Bob", "Zed", "Q"])),
+            [
+                change("Bob", Some(2), Some(1)),
+                change("Zed", None, Some(2)),
+                change("Q", Some(1), Some(3)),
+                change("Amy", Some(3), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn leaderboard_diff_compares_snapshots_and_the_live_board() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "100").unwrap();
+        add_user(TEST_GUILD, "Bob", "Halo", "50").unwrap();
+        assert_eq!(save_snapshot(TEST_GUILD, "monday").unwrap(), 2);
+
+        update_total(TEST_GUILD, "Bob", "Halo", "100", false).unwrap();
+        let changes = leaderboard_diff(TEST_GUILD, "monday", "now").unwrap();
+        assert_eq!(changes[0].username, "Bob");
+        assert_eq!((changes[0].before, changes[0].after), (Some(2), Some(1)));
+
+        assert!(leaderboard_diff(TEST_GUILD, "monday", "tuesday").is_err());
+        assert!(save_snapshot(TEST_GUILD, "now").is_err());
+    }
+
+    #[test]
+    fn caps_refuse_updates_past_them_unless_overridden() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "90").unwrap();
+        set_cap(TEST_GUILD, "Q", "Tekken 8", Some("100")).unwrap();
+
+        let error = update_total(TEST_GUILD, "Q", "Tekken 8", "20", false).unwrap_err();
+        assert!(error.to_string().contains("$10.00 left"), "{}", error);
+        update_total(TEST_GUILD, "Q", "Tekken 8", "10", false).unwrap();
+        let error = update_total(TEST_GUILD, "Q", "Tekken 8", "1", false).unwrap_err();
+        assert!(error.to_string().starts_with("Cap reached"), "{}", error);
+
+        // Refunds still go through, and an admin can push past the cap
+        update_total(TEST_GUILD, "Q", "Tekken 8", "-5", false).unwrap();
+        assert_eq!(
+            update_total(TEST_GUILD, "Q", "Tekken 8", "50", true)
+                .unwrap()
+                .2,
+            14500
+        );
+
+        set_cap(TEST_GUILD, "Q", "Tekken 8", None).unwrap();
+        update_total(TEST_GUILD, "Q", "Tekken 8", "1", false).unwrap();
+    }
+
+    #[test]
+    fn a_cap_must_be_more_than_zero() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "90").unwrap();
+        for cap in ["0", "-10", "abc"] {
+            assert!(matches!(
+                set_cap(TEST_GUILD, "Q", "Tekken 8", Some(cap)),
+                Err(UserError::InvalidAmount(_))
+            ));
+        }
+        assert_eq!(
+            set_cap(TEST_GUILD, "Q", "Tekken 8", Some("0.01")).unwrap(),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn display_names_change_output_but_not_lookups() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        link_account(TEST_GUILD, "Qalt", "Q").unwrap();
+        assert_eq!(get_display_name(TEST_GUILD, "Q"), "Q");
+
+        set_display_name(TEST_GUILD, "Q", Some("Quentin the Whale")).unwrap();
+        assert_eq!(get_display_name(TEST_GUILD, "Q"), "Quentin the Whale");
+        assert_eq!(get_display_name(TEST_GUILD, "Qalt"), "Quentin the Whale");
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            1000
+        );
+        // Someone who isn't tracked is shown as typed
+        assert_eq!(get_display_name(TEST_GUILD, "Nobody"), "Nobody");
+
+        set_display_name(TEST_GUILD, "Q", None).unwrap();
+        assert_eq!(get_display_name(TEST_GUILD, "Q"), "Q");
+        assert!(set_display_name(TEST_GUILD, "Nobody", Some("Ghost")).is_err());
+    }
+
+    #[test]
+    fn each_merge_strategy_settles_overlapping_games() {
+        let path = scratch_file("merge_import.json");
+        for (strategy, tekken, halo, conflict) in [
+            (
+                MergeStrategy::Replace,
+                3000,
+                500,
+                "Q / Tekken 8: replaced $50.00 with $30.00",
+            ),
+            (
+                MergeStrategy::Sum,
+                8000,
+                1500,
+                "Q / Tekken 8: added $30.00 to $50.00, now $80.00",
+            ),
+            (
+                MergeStrategy::KeepMax,
+                5000,
+                1000,
+                "Q / Tekken 8: kept $50.00 over $30.00",
+            ),
+        ] {
+            let store = fresh_store();
+            add_user(TEST_GUILD, "Q", "Tekken 8", "30").unwrap();
+            add_game(TEST_GUILD, "Q", "Halo", "5").unwrap();
+            add_game(TEST_GUILD, "Q", "New Game", "2").unwrap();
+            add_user(TEST_GUILD, "Z", "Apex Legends", "1").unwrap();
+            export_users(TEST_GUILD, &path).unwrap();
+            drop(store);
+
+            let _store = fresh_store();
+            add_user(TEST_GUILD, "Q", "Tekken 8", "50").unwrap();
+            add_game(TEST_GUILD, "Q", "Halo", "10").unwrap();
+            add_user(TEST_GUILD, "Bob", "Halo", "3").unwrap();
+
+            let report = import_users(TEST_GUILD, &path, strategy, false).unwrap();
+            assert_eq!(report.imported, 2);
+            assert_eq!(report.conflicts.len(), 2, "{:?}", strategy);
+            assert!(
+                report.conflicts.contains(&conflict.to_string()),
+                "{:?}",
+                report
+            );
+            assert_eq!(
+                get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+                tekken
+            );
+            assert_eq!(
+                get_game_record(TEST_GUILD, "Q", "Halo").unwrap().total,
+                halo
+            );
+            // New games and users come in as they are, and nobody is dropped
+            assert_eq!(
+                get_game_record(TEST_GUILD, "Q", "New Game").unwrap().total,
+                200
+            );
+            assert_eq!(
+                get_game_record(TEST_GUILD, "Z", "Apex Legends")
+                    .unwrap()
+                    .total,
+                100
+            );
+            assert_eq!(
+                get_game_record(TEST_GUILD, "Bob", "Halo").unwrap().total,
+                300
+            );
+        }
+    }
+
+    #[test]
+    fn velocity_counts_only_spending_inside_the_window() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_user(TEST_GUILD, "Z", "Halo", "10").unwrap();
+        link_account(TEST_GUILD, "Qalt", "Q").unwrap();
+
+        // A burst from two hours ago falls outside a one hour window
+        record_transaction(
+            TEST_GUILD,
+            Transaction {
+                timestamp: now() - 2 * 60 * 60,
+                username: "Q".to_string(),
+                game: "Tekken 8".to_string(),
+                delta: 50000,
+                total: 51000,
+                undoes: None,
+            },
+        )
+        .unwrap();
+        update_total(TEST_GUILD, "Qalt", "Tekken 8", "100", false).unwrap();
+        update_total(TEST_GUILD, "Q", "Tekken 8", "200", false).unwrap();
+        update_total(TEST_GUILD, "Q", "Tekken 8", "-50", false).unwrap();
+        update_total(TEST_GUILD, "Z", "Halo", "400", false).unwrap();
+
+        assert_eq!(recent_velocity(TEST_GUILD, "Q", 60 * 60).unwrap(), 30000);
+        assert_eq!(recent_velocity(TEST_GUILD, "Qalt", 60 * 60).unwrap(), 30000);
+        assert_eq!(
+            recent_velocity(TEST_GUILD, "Q", 3 * 60 * 60).unwrap(),
+            80000
+        );
+        assert_eq!(recent_velocity(TEST_GUILD, "Nobody", 60 * 60).unwrap(), 0);
+    }
+
+    // A logged change at a time given as `2026-10-17 23:30` UTC
+    fn log_at(username: &str, time: &str, delta: i64) {
+        let timestamp = chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M")
+            .unwrap()
+            .and_utc()
+            .timestamp();
+        record_transaction(
+            TEST_GUILD,
+            Transaction {
+                timestamp,
+                username: username.to_string(),
+                game: "Tekken 8".to_string(),
+                delta,
+                total: 0,
+                undoes: None,
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn spend_windows_classify_times_by_local_day_and_hour() {
+        let utc = chrono::FixedOffset::east_opt(0).unwrap();
+        let at = |time: &str| {
+            chrono::NaiveDateTime::parse_from_str(time, "%Y-%m-%d %H:%M")
+                .unwrap()
+                .and_utc()
+                .with_timezone(&utc)
+        };
+
+        // 2026-10-16 is a Friday and 2026-10-17 a Saturday
+        let friday_noon = at("2026-10-16 12:00");
+        let friday_late = at("2026-10-16 23:30");
+        let saturday_early = at("2026-10-17 02:00");
+        let saturday_noon = at("2026-10-17 12:00");
+
+        assert!(SpendWindow::Weekdays.contains(friday_noon));
+        assert!(!SpendWindow::Nights.contains(friday_noon));
+        assert!(SpendWindow::Weeknights.contains(friday_late));
+        assert!(SpendWindow::Weekend.contains(saturday_early));
+        assert!(SpendWindow::Nights.contains(saturday_early));
+        assert!(!SpendWindow::Weeknights.contains(saturday_early));
+        assert!(!SpendWindow::Nights.contains(saturday_noon));
+
+        // Two hours ahead, late Friday UTC is already the weekend
+        let ahead = chrono::FixedOffset::east_opt(2 * 3600).unwrap();
+        assert!(SpendWindow::Weekend.contains(friday_late.with_timezone(&ahead)));
+    }
+
+    #[test]
+    fn window_spending_comes_from_the_log() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_user(TEST_GUILD, "Amy", "Tekken 8", "10").unwrap();
+        log_at("Q", "2026-10-17 12:00", 50);
+        log_at("Q", "2026-10-16 23:30", 20);
+        log_at("Amy", "2026-10-14 12:00", 90);
+
+        assert_eq!(
+            spend_in_window(TEST_GUILD, "Q", SpendWindow::Weekend).unwrap(),
+            50
+        );
+        assert_eq!(
+            spend_in_window(TEST_GUILD, "Q", SpendWindow::Nights).unwrap(),
+            20
+        );
+        assert_eq!(
+            spend_in_window(TEST_GUILD, "Amy", SpendWindow::Nights).unwrap(),
+            0
+        );
+
+        // Changes logged under a linked account count for the user it's linked to
+        link_account(TEST_GUILD, "Q2", "Q").unwrap();
+        log_at("Q2", "2026-10-18 12:00", 10);
+        assert_eq!(
+            spend_in_window(TEST_GUILD, "Q", SpendWindow::Weekend).unwrap(),
+            60
+        );
+        assert!(spend_in_window(TEST_GUILD, "Nobody", SpendWindow::Weekend).is_err());
+    }
+
+    fn consent_request(discord_id: u64, username: &str, requested_at: i64) -> PendingConsent {
+        PendingConsent {
+            discord_id,
+            guild_id: TEST_GUILD,
+            username: username.to_string(),
+            game: "Tekken 8".to_string(),
+            total: "40".to_string(),
+            requested_at,
+        }
+    }
+
+    #[test]
+    fn accepting_consent_writes_the_held_data() {
+        let _store = fresh_store();
+        request_consent(consent_request(1, "Q", 1000)).unwrap();
+        assert!(get_users(TEST_GUILD, true).unwrap().is_empty());
+
+        let answer = answer_consent(1, true, 1000 + 60).unwrap();
+        assert_eq!(
+            answer,
+            ConsentAnswer::Accepted(consent_request(1, "Q", 1000))
+        );
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            4000
+        );
+        assert!(has_consented(TEST_GUILD, "Q"));
+
+        // The request is used up
+        assert_eq!(
+            answer_consent(1, true, 1000 + 60).unwrap(),
+            ConsentAnswer::NotAsked
+        );
+
+        // Someone already tracked who consents to another game just gets the game
+        request_consent(PendingConsent {
+            game: "Street Fighter 6".to_string(),
+            ..consent_request(1, "Q", 2000)
+        })
+        .unwrap();
+        answer_consent(1, true, 2000).unwrap();
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Street Fighter 6")
+                .unwrap()
+                .total,
+            4000
+        );
+    }
+
+    #[test]
+    fn declined_or_expired_consent_writes_nothing() {
+        let _store = fresh_store();
+        request_consent(consent_request(1, "Q", 1000)).unwrap();
+        request_consent(consent_request(2, "Amy", 1000)).unwrap();
+
+        assert!(matches!(
+            answer_consent(1, false, 1000).unwrap(),
+            ConsentAnswer::Declined(_)
+        ));
+        assert_eq!(
+            answer_consent(2, true, 1000 + CONSENT_EXPIRY).unwrap(),
+            ConsentAnswer::Expired
+        );
+        assert!(get_users(TEST_GUILD, true).unwrap().is_empty());
+        assert!(!has_consented(TEST_GUILD, "Q"));
+        assert_eq!(
+            answer_consent(3, true, 1000).unwrap(),
+            ConsentAnswer::NotAsked
+        );
+
+        // A newer request replaces the old one, and stale ones are dropped
+        request_consent(consent_request(1, "Q", 1000)).unwrap();
+        request_consent(consent_request(2, "Amy", 1000)).unwrap();
+        request_consent(consent_request(1, "Q2", 1000 + CONSENT_EXPIRY)).unwrap();
+        let pending = load_pending_consent();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[&1].username, "Q2");
+    }
+
+    #[test]
+    fn linked_accounts_roll_up_into_the_canonical_user_in_rankings() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "100").unwrap();
+        add_user(TEST_GUILD, "Qalt", "Street Fighter 6", "40").unwrap();
+        add_user(TEST_GUILD, "Z", "Tekken 8", "120").unwrap();
+        assert_eq!(
+            get_leaderboard(TEST_GUILD, 10).unwrap()[0],
+            ("Z".to_string(), 12000)
+        );
+
+        // Qalt keeps its own games but is ranked once, under Q
+        link_account(TEST_GUILD, "Qalt", "Q").unwrap();
+        assert_eq!(
+            get_leaderboard(TEST_GUILD, 10).unwrap(),
+            [("Q".to_string(), 14000), ("Z".to_string(), 12000)]
+        );
+
+        let weights = HashMap::from([("Street Fighter 6".to_string(), 2.0)]);
+        assert_eq!(
+            get_weighted_leaderboard(TEST_GUILD, &weights, 10).unwrap(),
+            [("Q".to_string(), 180), ("Z".to_string(), 120)]
+        );
+    }
+
+    #[test]
+    fn weights_scale_each_games_dollars_into_shame_points() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Genshin Impact", "100.40").unwrap();
+        add_game(TEST_GUILD, "Q", "Tekken 8", "50").unwrap();
+        add_user(TEST_GUILD, "Z", "Tekken 8", "200").unwrap();
+
+        let weights = HashMap::from([
+            ("Genshin Impact".to_string(), 2.5),
+            ("Tekken 8".to_string(), 0.0),
+        ]);
+        let q = &get_users(TEST_GUILD, false).unwrap()[0];
+        assert_eq!(weighted_total(q, &weights), 251);
+        assert_eq!(weighted_total(q, &HashMap::new()), 150);
+
+        // A zero weight takes a game out of the ranking without dropping its player
+        assert_eq!(
+            get_weighted_leaderboard(TEST_GUILD, &weights, 10).unwrap(),
+            [("Q".to_string(), 251), ("Z".to_string(), 0)]
+        );
+    }
+
+    #[test]
+    fn dry_runs_write_nothing_and_match_the_real_run() {
+        let store = fresh_store();
+        let path = scratch_file("dry_run_import.json");
+        add_user(TEST_GUILD, "Q", "Tekken 8", "30").unwrap();
+        add_user(TEST_GUILD, "Z", "Halo", "5").unwrap();
+        export_users(TEST_GUILD, &path).unwrap();
+        drop(store);
+
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "50").unwrap();
+        let users_before = read_data_file(TEST_GUILD).unwrap();
+        let history_before = load_history(TEST_GUILD).unwrap();
+
+        let preview = import_users(TEST_GUILD, &path, MergeStrategy::Sum, true).unwrap();
+        assert_eq!(read_data_file(TEST_GUILD).unwrap(), users_before);
+        assert_eq!(load_history(TEST_GUILD).unwrap(), history_before);
+
+        let report = import_users(TEST_GUILD, &path, MergeStrategy::Sum, false).unwrap();
+        assert_eq!(preview, report);
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            8000
+        );
+
+        // Merging files previews the same way, without creating the output
+        let current = scratch_file("dry_run_current.json");
+        export_users(TEST_GUILD, &current).unwrap();
+        let out = scratch_file("dry_run_merge.json");
+        let _ = std::fs::remove_file(&out);
+        let preview = merge_data_files(&path, &current, &out, true).unwrap();
+        assert!(!std::path::Path::new(&out).exists());
+        assert_eq!(
+            preview,
+            merge_data_files(&path, &current, &out, false).unwrap()
+        );
+    }
+
+    #[test]
+    fn game_imports_merges_and_renames_can_be_previewed() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "30").unwrap();
+        add_game(TEST_GUILD, "Q", "Halo", "5").unwrap();
+        add_user(TEST_GUILD, "Q2", "tekken 8", "10").unwrap();
+        let users_before = read_data_file(TEST_GUILD).unwrap();
+        let history_before = load_history(TEST_GUILD).unwrap();
+
+        let csv = "Tekken 8,40\nHalo,oops\nGolf,2";
+        let import = import_games(TEST_GUILD, "Q", csv, true).unwrap();
+        let merge = merge_users(TEST_GUILD, "Q2", "Q", true).unwrap();
+        let rename = rename_game_global(TEST_GUILD, "TEKKEN 8", "Tekken", true).unwrap();
+        assert_eq!(read_data_file(TEST_GUILD).unwrap(), users_before);
+        assert_eq!(load_history(TEST_GUILD).unwrap(), history_before);
+
+        assert_eq!(import_games(TEST_GUILD, "Q", csv, false).unwrap(), import);
+        assert_eq!(import.0, 2);
+        assert_eq!(
+            rename_game_global(TEST_GUILD, "TEKKEN 8", "Tekken", false).unwrap(),
+            rename
+        );
+        assert_eq!(rename, vec!["Q", "Q2"]);
+        assert_eq!(merge_users(TEST_GUILD, "Q2", "Q", false).unwrap(), merge);
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken").unwrap().total,
+            5000
+        );
+        assert!(
+            get_history(TEST_GUILD, "Q", None)
+                .unwrap()
+                .iter()
+                .all(|change| change.game != "Tekken 8")
+        );
+        assert!(rename_game_global(TEST_GUILD, "Tekken 8", "Tekken", true).is_err());
+    }
+
+    #[test]
+    fn starting_totals_cant_be_negative() {
+        let _store = fresh_store();
+        for total in ["-5", "-0.01", "-$1,000"] {
+            assert!(matches!(
+                add_user(TEST_GUILD, "Q", "Tekken 8", total),
+                Err(UserError::InvalidAmount(_))
+            ));
+        }
+        assert_eq!(add_user(TEST_GUILD, "Q", "Tekken 8", "0").unwrap(), 0);
+        assert!(matches!(
+            add_game(TEST_GUILD, "Q", "Street Fighter 6", "-1"),
+            Err(UserError::InvalidAmount(_))
+        ));
+        assert_eq!(
+            add_game(TEST_GUILD, "Q", "Street Fighter 6", "$0.01").unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn import_skips_negative_totals() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        let (imported, problems) =
+            import_games(TEST_GUILD, "Q", "game,total\nTekken 8,-5\nHalo,20", false).unwrap();
+
+        assert_eq!(imported, 1);
+        assert_eq!(problems, vec!["Line 2: total can't be negative"]);
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            1000
+        );
+    }
+
+    #[test]
+    fn undo_wont_go_below_zero() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_game(TEST_GUILD, "Q", "Halo", "1").unwrap();
+        update_total(TEST_GUILD, "Q", "Tekken 8", "5", false).unwrap();
+        // Start the game over behind the log's back, leaving the update to undo
+        remove_game(TEST_GUILD, "Q", "Tekken 8").unwrap();
+        add_game(TEST_GUILD, "Q", "Tekken 8", "2").unwrap();
+
+        assert!(matches!(
+            undo_last_for(TEST_GUILD, "Q"),
+            Err(UserError::Rejected(_))
+        ));
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            200
+        );
+    }
+
+    #[test]
+    fn parse_money_handles_edge_cases() {
+        assert_eq!(parse_money("0"), Some(0));
+        assert_eq!(parse_money("-0"), Some(0));
+        assert_eq!(parse_money("5."), Some(500));
+        assert_eq!(parse_money("0.07"), Some(7));
+        assert_eq!(parse_money("-$5"), Some(-500));
+        assert_eq!(parse_money("$ 5"), Some(500));
+        // Too big to hold in cents
+        assert_eq!(parse_money("99999999999999999999"), None);
+        assert_eq!(parse_money("92233720368547758.08"), None);
+        assert_eq!(parse_money("92233720368547758.07"), Some(i64::MAX));
+    }
+
+    #[test]
+    fn admin_commands_find_users_ignoring_case() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        add_user(TEST_GUILD, "Bob", "Halo", "5").unwrap();
+
+        link_account(TEST_GUILD, "q2", "q").unwrap();
+        assert_eq!(get_user_total_all_games(TEST_GUILD, "Q2").unwrap(), 1000);
+        assert!(
+            debug_dump(TEST_GUILD, "Q2")
+                .unwrap()
+                .contains("\"alias_of\": \"Q\"")
+        );
+        unlink_account(TEST_GUILD, "Q2").unwrap();
+        assert!(matches!(
+            get_user_total_all_games(TEST_GUILD, "q2"),
+            Err(UserError::UserNotFound(_))
+        ));
+
+        rename_user(TEST_GUILD, "q", "Quinn").unwrap();
+        assert!(
+            debug_dump(TEST_GUILD, "quinn")
+                .unwrap()
+                .contains("\"user\": \"Quinn\"")
+        );
+        // Renaming into someone else's name is still refused, whatever its case
+        assert!(matches!(
+            rename_user(TEST_GUILD, "QUINN", "bob"),
+            Err(UserError::UserAlreadyExists(_))
+        ));
+
+        delete_user(TEST_GUILD, "QUINN").unwrap();
+        assert!(matches!(
+            debug_dump(TEST_GUILD, "Quinn"),
+            Err(UserError::UserNotFound(_))
+        ));
+        assert!(debug_dump(TEST_GUILD, "bob").is_ok());
+    }
+
+    #[test]
+    fn imported_totals_are_logged_and_can_be_undone() {
+        let _store = fresh_store();
+        add_user(TEST_GUILD, "Q", "Tekken 8", "10").unwrap();
+        import_games(TEST_GUILD, "Q", "Tekken 8,25\nHalo,5\nNew Game,0", false).unwrap();
+
+        let history = get_history(TEST_GUILD, "Q", None).unwrap();
+        let mut logged: Vec<(&str, i64, i64)> = history
+            .iter()
+            .map(|t| (t.game.as_str(), t.delta, t.total))
+            .collect();
+        logged.sort();
+        assert_eq!(logged, vec![("Halo", 500, 500), ("Tekken 8", 1500, 2500)]);
+
+        // Undo walks back through the imported rows like any other update
+        undo_last_for(TEST_GUILD, "Q").unwrap();
+        undo_last_for(TEST_GUILD, "Q").unwrap();
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            1000
+        );
+        assert_eq!(get_game_record(TEST_GUILD, "Q", "Halo").unwrap().total, 0);
+    }
+
+    #[test]
+    fn parse_money_reads_dollars_and_cents() {
+        assert_eq!(parse_money("9.99"), Some(999));
+        assert_eq!(parse_money("10"), Some(1000));
+        assert_eq!(parse_money("$10"), Some(1000));
+        assert_eq!(parse_money("-5.5"), Some(-550));
+        assert_eq!(parse_money(".5"), Some(50));
+    }
+
+    #[test]
+    fn parse_money_rejects_malformed_amounts() {
+        for input in ["", "$", "abc", "1.234", "1.2.3", "--5", "9e3"] {
+            assert_eq!(parse_money(input), None, "{:?} should be rejected", input);
+        }
+    }
+
+    #[test]
+    fn formatted_money_parses_back() {
+        assert_eq!(format_money(5), "$0.05");
+        assert_eq!(format_money(-550), "-$5.50");
+        for cents in [0, 1, 99, 100_000, 123456789] {
+            assert_eq!(parse_money(&format_money(cents)), Some(cents));
+        }
+    }
+
+    #[test]
+    fn thousands_are_grouped_when_shown_and_when_typed() {
+        assert_eq!(format_money(0), "$0.00");
+        assert_eq!(format_money(99_900), "$999.00");
+        assert_eq!(format_money(100_000), "$1,000.00");
+        assert_eq!(format_money(123_456_700), "$1,234,567.00");
+        assert_eq!(format_money(-120_000), "-$1,200.00");
+
+        assert_eq!(parse_money("1,200"), Some(120_000));
+        assert_eq!(parse_money("$1,234,567.89"), Some(123_456_789));
+        for misplaced in ["1,20", "12,00", "1,2000", ",200", "1,,200", "1200,"] {
+            assert_eq!(parse_money(misplaced), None, "{misplaced}");
+        }
+    }
+
+    #[test]
+    fn a_configured_currency_is_written_and_read_back() {
+        let euro = Currency::from_settings(Some(" € "), Some("Suffix"));
+        assert_eq!(euro.format(999), "9.99 €");
+        assert_eq!(euro.format(-150), "-1.50 €");
+        assert_eq!(euro.parse("9.99 €"), Some(999));
+        assert_eq!(euro.parse("€5"), Some(500));
+        assert_eq!(euro.parse("-2.5€"), Some(-250));
+        assert_eq!(euro.parse("$3"), Some(300));
+        assert_eq!(euro.parse("£3"), None);
+
+        // Left unset, amounts look the way they always have
+        let dollars = Currency::from_settings(None, Some("prefix"));
+        assert_eq!(dollars.format(999), "$9.99");
+        assert_eq!(Currency::from_settings(Some("  "), None).format(0), "$0.00");
+        assert_eq!(dollars.parse("$10"), Some(1000));
+    }
+
+    #[test]
+    fn dollar_records_are_scaled_to_cents() {
+        let mut users: Vec<User> = serde_json::from_str(
+            r#"[{"user": "Q", "games": {"Tekken 8": 5}, "caps": {"Tekken 8": 9}}]"#,
+        )
+        .unwrap();
+        assert!(migrate_to_cents(&mut users));
+        assert_eq!(users[0].games["Tekken 8"].total, 500);
+        assert_eq!(users[0].caps["Tekken 8"], 900);
+        assert!(!migrate_to_cents(&mut users));
+    }
+
+    #[test]
+    fn a_dollar_data_file_is_converted_once_along_with_its_logs() {
+        let _store = fresh_store();
+        std::fs::write(
+            users_file(),
+            r#"[{"user": "Q", "games": {"Tekken 8": 120}}]"#,
+        )
+        .unwrap();
+        std::fs::write(
+            history_file(),
+            r#"[{"timestamp": 1, "username": "Q", "game": "Tekken 8", "delta": 20, "total": 120}]"#,
+        )
+        .unwrap();
+        std::fs::write(legacy_snapshots_file(), r#"{"monday": [["Q", 100]]}"#).unwrap();
+
+        assert_eq!(
+            get_game_record(TEST_GUILD, "Q", "Tekken 8").unwrap().total,
+            12000
+        );
+        assert_eq!(
+            (
+                load_history(TEST_GUILD).unwrap()[0].delta,
+                load_history(TEST_GUILD).unwrap()[0].total
+            ),
+            (2000, 12000)
+        );
+        assert_eq!(
+            read_snapshots(&snapshots_file(TEST_GUILD))["monday"],
+            [("Q".to_string(), 10000)]
+        );
+
+        // It's only imported once, so reading again doesn't scale it a second time
+        assert_eq!(
+            get_leaderboard(TEST_GUILD, 1).unwrap(),
+            [("Q".to_string(), 12000)]
+        );
+        assert_eq!(load_history(TEST_GUILD).unwrap()[0].delta, 2000);
     }
 }